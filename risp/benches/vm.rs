@@ -0,0 +1,45 @@
+use std::time::{Duration, Instant};
+
+use risp::{Backend, Interpreter};
+
+const SETUP: &str = r#"
+(defn fib (n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))
+(defn sum-to (n acc) (if (= n 0) acc (sum-to (- n 1) (+ acc n))))
+(defn count-even (n)
+  (def loop (fn (i acc) (if (= i n) acc (loop (+ i 1) (if (= 0 (mod i 2)) (+ acc 1) acc)))))
+  (loop 0 0))
+"#;
+
+const CASES: [(&str, &str); 3] = [
+    ("fib 18", "(fib 18)"),
+    ("sum-to 100000", "(sum-to 100000 0)"),
+    ("count-even 50000", "(count-even 50000)"),
+];
+
+// 每个用例多次执行取平均时间
+fn bench(backend: Backend, src: &str) -> Duration {
+    let mut risp = Interpreter::new();
+    risp.set_backend(backend);
+    risp.eval_all(SETUP).expect("setup");
+    let runs = 5;
+    let start = Instant::now();
+    for _ in 0..runs {
+        risp.eval_all(src).expect("bench");
+    }
+    start.elapsed() / runs
+}
+
+fn main() {
+    println!("{:<20}{:>12}{:>12}{:>10}", "case", "tree", "vm", "speedup");
+    for (name, src) in CASES {
+        let tree = bench(Backend::Tree, src);
+        let vm = bench(Backend::Vm, src);
+        println!(
+            "{:<20}{:>12.2?}{:>12.2?}{:>9.1}x",
+            name,
+            tree,
+            vm,
+            tree.as_secs_f64() / vm.as_secs_f64()
+        );
+    }
+}
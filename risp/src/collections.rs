@@ -0,0 +1,399 @@
+use std::{fmt, ops::Deref};
+
+use im_rc::{HashMap, OrdMap, Vector};
+
+use crate::{
+    gc::{Gc, Trace},
+    RispError, RispExp,
+};
+
+// 向量、映射和集合都不可变, 修改时返回新的值; 内容分配在托管堆中, clone时共享
+// 向量的内容是共享结构的持久化向量, push和assoc只复制改动的路径
+#[derive(Clone, Default)]
+struct Items(Vector<RispExp>);
+
+// 映射和集合的内容: 键到插入序号的索引, 以及按序号排列的键值对;
+// 两者都是共享结构的持久化映射, 修改时只复制改动的路径
+#[derive(Clone)]
+struct Ordered<T> {
+    index: HashMap<RispExp, u64>,
+    order: OrdMap<u64, (RispExp, T)>,
+    next: u64,
+}
+
+impl<T: Clone> Default for Ordered<T> {
+    fn default() -> Self {
+        Self { index: HashMap::new(), order: OrdMap::new(), next: 0 }
+    }
+}
+
+impl<T: Clone> Ordered<T> {
+    fn get(&self, key: &RispExp) -> Option<&(RispExp, T)> {
+        self.index.get(key).and_then(|seq| self.order.get(seq))
+    }
+
+    // 已有的键保持原来的键和位置, 只替换值
+    fn insert(&mut self, key: RispExp, value: T) {
+        if let Some(seq) = self.index.get(&key) {
+            if let Some(entry) = self.order.get_mut(seq) {
+                entry.1 = value;
+            }
+            return;
+        }
+        self.index.insert(key.clone(), self.next);
+        self.order.insert(self.next, (key, value));
+        self.next += 1;
+    }
+
+    fn remove(&mut self, key: &RispExp) {
+        if let Some(seq) = self.index.remove(key) {
+            self.order.remove(&seq);
+        }
+    }
+}
+
+impl<T: Clone> FromIterator<(RispExp, T)> for Ordered<T> {
+    fn from_iter<I: IntoIterator<Item = (RispExp, T)>>(iter: I) -> Self {
+        let mut ordered = Self::default();
+        for (k, v) in iter {
+            ordered.insert(k, v);
+        }
+        ordered
+    }
+}
+
+impl Trace for Items {
+    fn trace(&self, visit: &mut dyn FnMut(usize)) {
+        self.0.iter().for_each(|x| x.trace(visit));
+    }
+}
+
+impl Trace for Ordered<RispExp> {
+    fn trace(&self, visit: &mut dyn FnMut(usize)) {
+        for (k, v) in self.order.values() {
+            k.trace(visit);
+            v.trace(visit);
+        }
+    }
+}
+
+impl Trace for Ordered<()> {
+    fn trace(&self, visit: &mut dyn FnMut(usize)) {
+        self.order.values().for_each(|(k, _)| k.trace(visit));
+    }
+}
+
+impl Deref for Items {
+    type Target = Vector<RispExp>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+// [1 2 3], 按下标访问
+#[derive(Clone, Default)]
+pub struct RispVector(Gc<Items>);
+
+impl RispVector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&RispExp> {
+        self.0.get(idx)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> im_rc::vector::Iter<'_, RispExp> {
+        self.0.iter()
+    }
+
+    pub fn to_vec(&self) -> Vec<RispExp> {
+        self.iter().cloned().collect()
+    }
+
+    // 在末尾添加; 与原来的向量共享未修改的部分
+    pub fn push(&self, value: RispExp) -> Self {
+        let mut items = (*self.0).clone();
+        items.0.push_back(value);
+        Self(Gc::new(items))
+    }
+
+    // 替换下标处的值, 下标等于长度时添加到末尾, 超出时为None
+    pub fn assoc(&self, idx: usize, value: RispExp) -> Option<Self> {
+        let mut items = (*self.0).clone();
+        match idx.cmp(&items.len()) {
+            std::cmp::Ordering::Less => items.0[idx] = value,
+            std::cmp::Ordering::Equal => items.0.push_back(value),
+            std::cmp::Ordering::Greater => return None,
+        }
+        Some(Self(Gc::new(items)))
+    }
+
+    pub(crate) fn trace(&self, visit: &mut dyn FnMut(usize)) {
+        visit(self.0.id());
+    }
+}
+
+impl From<Vec<RispExp>> for RispVector {
+    fn from(items: Vec<RispExp>) -> Self {
+        items.into_iter().collect()
+    }
+}
+
+impl FromIterator<RispExp> for RispVector {
+    fn from_iter<I: IntoIterator<Item = RispExp>>(iter: I) -> Self {
+        Self(Gc::new(Items(iter.into_iter().collect())))
+    }
+}
+
+impl<'a> IntoIterator for &'a RispVector {
+    type Item = &'a RispExp;
+    type IntoIter = im_rc::vector::Iter<'a, RispExp>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl PartialEq for RispVector {
+    fn eq(&self, other: &Self) -> bool {
+        **self.0 == **other.0
+    }
+}
+
+impl fmt::Debug for RispVector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+// {:a 1 :b 2}, 按插入顺序保存, 输出顺序稳定; 键按=比较, 1和1.0是同一个键
+#[derive(Clone, Default)]
+pub struct RispMap(Gc<Ordered<RispExp>>);
+
+impl RispMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // (k1 v1 k2 v2) -> {k1 v1 k2 v2}
+    pub fn from_pairs(items: Vec<RispExp>) -> Result<Self, RispError> {
+        if !items.len().is_multiple_of(2) {
+            return Err(RispError::Reason(
+                "expected an even number of forms for a map".to_string(),
+            ));
+        }
+        Ok(items.chunks(2).map(|kv| (kv[0].clone(), kv[1].clone())).collect())
+    }
+
+    pub fn get(&self, key: &RispExp) -> Option<&RispExp> {
+        self.0.get(key).map(|(_, v)| v)
+    }
+
+    pub fn contains_key(&self, key: &RispExp) -> bool {
+        self.0.index.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.order.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&RispExp, &RispExp)> {
+        self.0.order.values().map(|(k, v)| (k, v))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &RispExp> {
+        self.0.order.values().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &RispExp> {
+        self.0.order.values().map(|(_, v)| v)
+    }
+
+    // 已有的键保持原来的位置; 与原来的映射共享未修改的部分
+    pub fn assoc(&self, key: RispExp, value: RispExp) -> Self {
+        let mut entries = (*self.0).clone();
+        entries.insert(key, value);
+        Self(Gc::new(entries))
+    }
+
+    // 其余的键保持原来的顺序
+    pub fn dissoc(&self, key: &RispExp) -> Self {
+        let mut entries = (*self.0).clone();
+        entries.remove(key);
+        Self(Gc::new(entries))
+    }
+
+    pub(crate) fn trace(&self, visit: &mut dyn FnMut(usize)) {
+        visit(self.0.id());
+    }
+}
+
+// 重复的键保留最后一个值
+impl FromIterator<(RispExp, RispExp)> for RispMap {
+    fn from_iter<I: IntoIterator<Item = (RispExp, RispExp)>>(iter: I) -> Self {
+        Self(Gc::new(iter.into_iter().collect()))
+    }
+}
+
+// 与顺序无关
+impl PartialEq for RispMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl fmt::Debug for RispMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// #{1 2 3}, 按插入顺序保存, 元素按=去重
+#[derive(Clone, Default)]
+pub struct RispSet(Gc<Ordered<()>>);
+
+impl RispSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contains(&self, value: &RispExp) -> bool {
+        self.0.index.contains_key(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.order.is_empty()
+    }
+
+    pub fn iter(&self) -> SetIter<'_> {
+        SetIter(self.0.order.values())
+    }
+
+    pub fn conj(&self, value: RispExp) -> Self {
+        if self.contains(&value) {
+            return self.clone();
+        }
+        let mut items = (*self.0).clone();
+        items.insert(value, ());
+        Self(Gc::new(items))
+    }
+
+    pub fn disj(&self, value: &RispExp) -> Self {
+        if !self.contains(value) {
+            return self.clone();
+        }
+        let mut items = (*self.0).clone();
+        items.remove(value);
+        Self(Gc::new(items))
+    }
+
+    pub(crate) fn trace(&self, visit: &mut dyn FnMut(usize)) {
+        visit(self.0.id());
+    }
+}
+
+impl FromIterator<RispExp> for RispSet {
+    fn from_iter<I: IntoIterator<Item = RispExp>>(iter: I) -> Self {
+        Self(Gc::new(iter.into_iter().map(|x| (x, ())).collect()))
+    }
+}
+
+// 按插入顺序访问集合的元素
+pub struct SetIter<'a>(im_rc::ordmap::Values<'a, u64, (RispExp, ())>);
+
+impl<'a> Iterator for SetIter<'a> {
+    type Item = &'a RispExp;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(x, _)| x)
+    }
+}
+
+impl<'a> IntoIterator for &'a RispSet {
+    type Item = &'a RispExp;
+    type IntoIter = SetIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl PartialEq for RispSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|x| other.contains(x))
+    }
+}
+
+impl fmt::Debug for RispSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyword(s: &str) -> RispExp {
+        RispExp::Keyword(s.to_string())
+    }
+
+    #[test]
+    fn map_should_keep_insertion_order() {
+        let m: RispMap = ["c", "a", "b"].iter().map(|k| (keyword(k), RispExp::Int(1))).collect();
+        let m = m.assoc(keyword("a"), RispExp::Int(2)).dissoc(&keyword("c")).assoc(keyword("c"), RispExp::Int(3));
+        let keys: Vec<String> = m.keys().map(|k| k.to_string()).collect();
+        assert_eq!(keys, [":a", ":b", ":c"]);
+        assert_eq!(m.get(&keyword("a")), Some(&RispExp::Int(2)));
+    }
+
+    #[test]
+    fn equal_values_should_be_the_same_key() {
+        let m = RispMap::new().assoc(RispExp::Int(1), keyword("int")).assoc(RispExp::Number(1.0), keyword("float"));
+        assert_eq!(m.len(), 1);
+        assert_eq!(m.get(&RispExp::Number(1.0)), Some(&keyword("float")));
+        let inner: RispSet = [RispExp::Int(1), RispExp::Int(2)].into_iter().collect();
+        let reversed: RispSet = [RispExp::Number(2.0), RispExp::Int(1)].into_iter().collect();
+        let s = RispSet::new().conj(RispExp::Set(inner)).conj(RispExp::Set(reversed.clone()));
+        assert_eq!(s.len(), 1);
+        assert!(s.contains(&RispExp::Set(reversed)));
+        assert!(!s.disj(&RispExp::Set(RispSet::new().conj(RispExp::Int(1)))).is_empty());
+    }
+
+    #[test]
+    fn assoc_should_not_change_shared_maps() {
+        let m = RispMap::new().assoc(keyword("a"), RispExp::Int(1));
+        let n = m.clone().assoc(keyword("b"), RispExp::Int(2)).dissoc(&keyword("a"));
+        assert_eq!(m.len(), 1);
+        assert_eq!(m.get(&keyword("a")), Some(&RispExp::Int(1)));
+        assert_eq!(n.keys().collect::<Vec<_>>(), [&keyword("b")]);
+    }
+
+    #[test]
+    fn push_and_assoc_should_not_change_shared_vectors() {
+        let v: RispVector = (0..100).map(RispExp::Int).collect();
+        let w = v.push(RispExp::Int(100)).assoc(0, keyword("a")).unwrap();
+        assert_eq!((v.len(), v.get(0)), (100, Some(&RispExp::Int(0))));
+        assert_eq!((w.len(), w.get(0), w.get(100)), (101, Some(&keyword("a")), Some(&RispExp::Int(100))));
+        assert_eq!(w.assoc(101, RispExp::Nil).map(|x| x.len()), Some(102));
+        assert!(w.assoc(102, RispExp::Nil).is_none());
+    }
+}
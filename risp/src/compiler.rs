@@ -0,0 +1,596 @@
+use std::rc::Rc;
+
+use crate::{eval::parse_try, RispError, RispExp, RispList};
+
+// 字节码指令, 参数为常量池/局部变量/跳转目标的下标
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Const(u32),
+    Nil,
+    // 当前函数的局部变量
+    Local(u32),
+    SetLocal(u32),
+    // 外层函数的局部变量, 第一个参数为向外的层数
+    Outer(u16, u32),
+    SetOuter(u16, u32),
+    // 全局变量, 参数为常量池中的符号
+    Global(u32),
+    DefGlobal(u32),
+    SetGlobal(u32),
+    // 用protos中的函数和当前作用域创建闭包
+    Closure(u32),
+    Call(u32),
+    TailCall(u32),
+    Jump(u32),
+    JumpIfFalse(u32),
+    Pop,
+    Return,
+    // 栈顶n个值组成list
+    List(u32),
+    // 栈顶n个list连接为一个list, 用于unquote-splicing
+    Concat(u32),
+    // 栈顶的list转为vector/map/set, 用于集合字面量
+    Vector,
+    Map,
+    Set,
+    // 栈顶依次为body、catch和finally的闭包(没有时为nil), 执行try
+    Try,
+    // 交给树遍历求值器执行常量池中的表达式, 用于顶层的defmacro、load等
+    Tree(u32),
+}
+
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<Op>,
+    pub consts: Vec<RispExp>,
+    pub protos: Vec<Rc<Proto>>,
+    // 每条指令对应的源表达式, 出错时用于定位
+    forms: Vec<RispExp>,
+    origins: Vec<u32>,
+    origin: u32,
+}
+
+impl Chunk {
+    fn emit(&mut self, op: Op) -> usize {
+        self.code.push(op);
+        self.origins.push(self.origin);
+        self.code.len() - 1
+    }
+
+    // 常量池去重, 类型不同的值(1和1.0)不合并
+    fn add_const(&mut self, exp: &RispExp) -> u32 {
+        let same = |x: &RispExp| std::mem::discriminant(x) == std::mem::discriminant(exp) && x == exp;
+        match self.consts.iter().position(same) {
+            Some(i) => i as u32,
+            None => {
+                self.consts.push(exp.clone());
+                self.consts.len() as u32 - 1
+            }
+        }
+    }
+
+    fn patch(&mut self, at: usize) {
+        let target = self.code.len() as u32;
+        match &mut self.code[at] {
+            Op::Jump(t) | Op::JumpIfFalse(t) => *t = target,
+            op => unreachable!("patch {:?}", op),
+        }
+    }
+
+    // 之后生成的指令来自exp, 返回之前的位置用于恢复
+    fn enter(&mut self, exp: &RispExp) -> u32 {
+        self.forms.push(exp.clone());
+        std::mem::replace(&mut self.origin, self.forms.len() as u32 - 1)
+    }
+
+    pub fn form_at(&self, ip: usize) -> Option<&RispExp> {
+        let origin = *self.origins.get(ip)?;
+        self.forms.get(origin as usize)
+    }
+}
+
+// 编译后的函数, 局部变量按下标存放在调用帧的slots中
+#[derive(Debug)]
+pub struct Proto {
+    pub name: Rc<String>,
+    pub params: usize,
+    // 最后一个参数收集剩余的实参
+    pub rest: bool,
+    pub slots: usize,
+    pub chunk: Chunk,
+}
+
+// 函数的局部变量名, 下标即slot
+#[derive(Debug, Default)]
+struct Scope {
+    names: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct Compiler {
+    // 由外到内的函数作用域, 为空时在顶层, def定义全局变量
+    scopes: Vec<Scope>,
+}
+
+// 编译顶层表达式, 结果作为没有参数的函数执行
+pub fn compile(exp: &RispExp) -> Result<Rc<Proto>, RispError> {
+    let mut compiler = Compiler::default();
+    let mut chunk = Chunk::default();
+    compiler.compile(exp, &mut chunk, false)?;
+    chunk.emit(Op::Return);
+    Ok(Rc::new(Proto {
+        name: Rc::new("<top>".to_string()),
+        params: 0,
+        rest: false,
+        slots: 0,
+        chunk,
+    }))
+}
+
+impl Compiler {
+    fn resolve(&self, name: &str) -> Option<(usize, usize)> {
+        self.scopes
+            .iter()
+            .rev()
+            .enumerate()
+            .find_map(|(depth, scope)| scope.names.iter().rposition(|n| n == name).map(|i| (depth, i)))
+    }
+
+    fn compile(&mut self, exp: &RispExp, chunk: &mut Chunk, tail: bool) -> Result<(), RispError> {
+        match exp {
+            RispExp::Nil => {
+                chunk.emit(Op::Nil);
+            }
+            RispExp::Symbol(name) => {
+                let prev = chunk.enter(exp);
+                match self.resolve(name) {
+                    Some((0, i)) => chunk.emit(Op::Local(i as u32)),
+                    Some((depth, i)) => chunk.emit(Op::Outer(depth as u16, i as u32)),
+                    None => {
+                        let idx = chunk.add_const(exp);
+                        chunk.emit(Op::Global(idx))
+                    }
+                };
+                chunk.origin = prev;
+            }
+            RispExp::List(list) => {
+                let prev = chunk.enter(exp);
+                self.compile_list(exp, &list.to_vec(), chunk, tail)?;
+                chunk.origin = prev;
+            }
+            RispExp::Vector(_) | RispExp::Map(_) | RispExp::Set(_) => {
+                let prev = chunk.enter(exp);
+                self.compile_collection(exp, chunk)?;
+                chunk.origin = prev;
+            }
+            _ => {
+                let idx = chunk.add_const(exp);
+                chunk.emit(Op::Const(idx));
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_list(
+        &mut self,
+        exp: &RispExp,
+        list: &[RispExp],
+        chunk: &mut Chunk,
+        tail: bool,
+    ) -> Result<(), RispError> {
+        let (first, args) = list
+            .split_first()
+            .ok_or(RispError::Reason("expected a non empty list".to_string()))?;
+        if let RispExp::Symbol(s) = first {
+            match s.as_str() {
+                "quote" => return self.compile_quote(args, chunk),
+                "if" => return self.compile_if(args, chunk, tail),
+                "begin" => return self.compile_begin(args, chunk, tail),
+                "def" => return self.compile_def(args, chunk),
+                "set!" => return self.compile_set(args, chunk),
+                "fn" => return self.compile_fn("fn", args, chunk),
+                "try" => return self.compile_try(args, chunk),
+                "quasiquote" => match args {
+                    [x] => return self.compile_quasiquote(x, chunk),
+                    _ => return Err(RispError::Reason("quasiquote expected one form".to_string())),
+                },
+                "defmacro" | "load" | "macroexpand" | "module" | "import" if self.scopes.is_empty() => {
+                    let idx = chunk.add_const(exp);
+                    chunk.emit(Op::Tree(idx));
+                    return Ok(());
+                }
+                "defmacro" | "load" | "macroexpand" | "module" | "import" => {
+                    return Err(RispError::Reason(format!(
+                        "{} is only supported at the top level by the vm",
+                        s
+                    )))
+                }
+                _ => {}
+            }
+        }
+        self.compile(first, chunk, false)?;
+        for arg in args {
+            self.compile(arg, chunk, false)?;
+        }
+        let argc = args.len() as u32;
+        if tail {
+            chunk.emit(Op::TailCall(argc));
+            chunk.emit(Op::Return);
+        } else {
+            chunk.emit(Op::Call(argc));
+        }
+        Ok(())
+    }
+
+    // [a (+ 1 2)], 元素依次求值后组成list, 再转为对应的类型
+    fn compile_collection(&mut self, exp: &RispExp, chunk: &mut Chunk) -> Result<(), RispError> {
+        let (items, op) = match exp {
+            RispExp::Vector(v) => (v.to_vec(), Op::Vector),
+            RispExp::Set(s) => (s.iter().cloned().collect(), Op::Set),
+            RispExp::Map(m) => (m.iter().flat_map(|(k, v)| [k.clone(), v.clone()]).collect(), Op::Map),
+            _ => unreachable!("compile_collection {}", exp),
+        };
+        for item in &items {
+            self.compile(item, chunk, false)?;
+        }
+        chunk.emit(Op::List(items.len() as u32));
+        chunk.emit(op);
+        Ok(())
+    }
+
+    fn compile_quote(&mut self, args: &[RispExp], chunk: &mut Chunk) -> Result<(), RispError> {
+        match args {
+            [exp] => {
+                let idx = chunk.add_const(exp);
+                chunk.emit(Op::Const(idx));
+                Ok(())
+            }
+            _ => Err(RispError::Reason("quote expected one form".to_string())),
+        }
+    }
+
+    // 没有else分支时为nil
+    fn compile_if(&mut self, args: &[RispExp], chunk: &mut Chunk, tail: bool) -> Result<(), RispError> {
+        let test = args
+            .first()
+            .ok_or(RispError::Reason("expected test form".to_string()))?;
+        let then = args
+            .get(1)
+            .ok_or(RispError::Reason("expected form idx=1".to_string()))?;
+        self.compile(test, chunk, false)?;
+        let jump_else = chunk.emit(Op::JumpIfFalse(0));
+        self.compile(then, chunk, tail)?;
+        let jump_end = chunk.emit(Op::Jump(0));
+        chunk.patch(jump_else);
+        match args.get(2) {
+            Some(els) => self.compile(els, chunk, tail)?,
+            None => {
+                chunk.emit(Op::Nil);
+            }
+        }
+        chunk.patch(jump_end);
+        Ok(())
+    }
+
+    fn compile_begin(&mut self, args: &[RispExp], chunk: &mut Chunk, tail: bool) -> Result<(), RispError> {
+        let (last, rest) = args
+            .split_last()
+            .ok_or(RispError::Reason("begin expected at least one form".to_string()))?;
+        for form in rest {
+            self.compile(form, chunk, false)?;
+            chunk.emit(Op::Pop);
+        }
+        self.compile(last, chunk, tail)
+    }
+
+    // 函数中的def定义当前函数的局部变量, slot在编译函数体之前已声明
+    fn compile_def(&mut self, args: &[RispExp], chunk: &mut Chunk) -> Result<(), RispError> {
+        let name = match args.first() {
+            Some(RispExp::Symbol(s)) => s,
+            Some(_) => return Err(RispError::Reason("expected first form to be a symbol".to_string())),
+            None => return Err(RispError::Reason("expected first form".to_string())),
+        };
+        let value = args
+            .get(1)
+            .ok_or(RispError::Reason("expected second form".to_string()))?;
+        if args.len() > 2 {
+            return Err(RispError::Reason("def can only have two forms ".to_string()));
+        }
+        let sym = chunk.add_const(&args[0]);
+        match self.scopes.last() {
+            Some(scope) => {
+                let slot = scope.names.iter().position(|n| n == name).expect("declared def");
+                self.compile_def_value(name, value, chunk)?;
+                chunk.emit(Op::SetLocal(slot as u32));
+                chunk.emit(Op::Pop);
+            }
+            None => {
+                self.compile_def_value(name, value, chunk)?;
+                chunk.emit(Op::DefGlobal(sym));
+            }
+        }
+        chunk.emit(Op::Const(sym));
+        Ok(())
+    }
+
+    // (def add (fn ...)) 以变量名作为函数名
+    fn compile_def_value(&mut self, name: &str, value: &RispExp, chunk: &mut Chunk) -> Result<(), RispError> {
+        if let RispExp::List(list) = value {
+            if let Some(RispExp::Symbol(s)) = list.first() {
+                if s == "fn" {
+                    let prev = chunk.enter(value);
+                    let res = self.compile_fn(name, &list.rest().to_vec(), chunk);
+                    chunk.origin = prev;
+                    return res;
+                }
+            }
+        }
+        self.compile(value, chunk, false)
+    }
+
+    // body、catch和finally分别编译为函数, 由虚拟机捕获body的错误
+    fn compile_try(&mut self, args: &[RispExp], chunk: &mut Chunk) -> Result<(), RispError> {
+        let form = parse_try(args)?;
+        let no_params = RispExp::List(RispList::new());
+        self.compile_fn("<try>", &[no_params.clone(), form.body], chunk)?;
+        match form.catch {
+            Some((name, handler)) => {
+                let params = RispExp::List(vec![RispExp::Symbol(name)].into());
+                self.compile_fn("<catch>", &[params, handler], chunk)?;
+            }
+            None => {
+                chunk.emit(Op::Nil);
+            }
+        }
+        match form.finally {
+            Some(finally) => self.compile_fn("<finally>", &[no_params, finally], chunk)?,
+            None => {
+                chunk.emit(Op::Nil);
+            }
+        }
+        chunk.emit(Op::Try);
+        Ok(())
+    }
+
+    fn compile_set(&mut self, args: &[RispExp], chunk: &mut Chunk) -> Result<(), RispError> {
+        let name = match args.first() {
+            Some(RispExp::Symbol(s)) => s,
+            _ => return Err(RispError::Reason("expected first form to be a symbol".to_string())),
+        };
+        if args.len() != 2 {
+            return Err(RispError::Reason("set! must have two forms".to_string()));
+        }
+        self.compile(&args[1], chunk, false)?;
+        match self.resolve(name) {
+            Some((0, i)) => chunk.emit(Op::SetLocal(i as u32)),
+            Some((depth, i)) => chunk.emit(Op::SetOuter(depth as u16, i as u32)),
+            None => {
+                let idx = chunk.add_const(&args[0]);
+                chunk.emit(Op::SetGlobal(idx))
+            }
+        };
+        Ok(())
+    }
+
+    // (fn (a & rest) body), 函数体在新的作用域中编译, 尾位置的调用不增加调用栈
+    fn compile_fn(&mut self, name: &str, args: &[RispExp], chunk: &mut Chunk) -> Result<(), RispError> {
+        let params = match args.first() {
+            Some(RispExp::List(params)) => params.to_vec(),
+            Some(RispExp::Vector(params)) => params.to_vec(),
+            Some(_) => return Err(RispError::Reason("expected args form to be a list or vector".to_string())),
+            None => return Err(RispError::Reason("expected args form".to_string())),
+        };
+        let body = args
+            .get(1)
+            .ok_or(RispError::Reason("expected second form".to_string()))?;
+        if args.len() > 2 {
+            return Err(RispError::Reason("fn definition can only have two forms ".to_string()));
+        }
+        let mut names = params
+            .iter()
+            .map(|x| match x {
+                RispExp::Symbol(s) => Ok(s.clone()),
+                _ => Err(RispError::Reason("expected symbols in the argument list".to_string())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let rest = match names.iter().position(|n| n == "&") {
+            Some(i) if i + 2 == names.len() => {
+                names.remove(i);
+                true
+            }
+            Some(_) => return Err(RispError::Reason("expected one symbol after &".to_string())),
+            None => false,
+        };
+        let params = names.len() - rest as usize;
+        declare_defs(body, &mut names);
+        self.scopes.push(Scope { names });
+        let mut body_chunk = Chunk::default();
+        body_chunk.enter(body);
+        let res = self.compile(body, &mut body_chunk, true);
+        let scope = self.scopes.pop().unwrap_or_default();
+        res?;
+        body_chunk.emit(Op::Return);
+        let proto = Proto {
+            name: Rc::new(name.to_string()),
+            params,
+            rest,
+            slots: scope.names.len(),
+            chunk: body_chunk,
+        };
+        chunk.protos.push(Rc::new(proto));
+        chunk.emit(Op::Closure(chunk.protos.len() as u32 - 1));
+        Ok(())
+    }
+
+    // `(a ~b ~@c) -> 连续的普通元素组成list, 与unquote-splicing的结果连接
+    fn compile_quasiquote(&mut self, exp: &RispExp, chunk: &mut Chunk) -> Result<(), RispError> {
+        if !has_unquote(exp) {
+            let idx = chunk.add_const(exp);
+            chunk.emit(Op::Const(idx));
+            return Ok(());
+        }
+        let op = match exp {
+            RispExp::List(list) => {
+                return match unquoted(list, "unquote") {
+                    Some(x) => self.compile(x, chunk, false),
+                    None => self.compile_quasiquote_items(list.iter(), chunk),
+                }
+            }
+            RispExp::Vector(v) => {
+                self.compile_quasiquote_items(v.iter(), chunk)?;
+                Op::Vector
+            }
+            RispExp::Set(s) => {
+                self.compile_quasiquote_items(s.iter(), chunk)?;
+                Op::Set
+            }
+            RispExp::Map(m) => {
+                self.compile_quasiquote_items(m.iter().flat_map(|(k, v)| [k, v]), chunk)?;
+                Op::Map
+            }
+            _ => unreachable!("compile_quasiquote {}", exp),
+        };
+        chunk.emit(op);
+        Ok(())
+    }
+
+    fn compile_quasiquote_items<'a>(
+        &mut self,
+        list: impl Iterator<Item = &'a RispExp>,
+        chunk: &mut Chunk,
+    ) -> Result<(), RispError> {
+        let (mut parts, mut items, mut spliced_any) = (0, 0, false);
+        for item in list {
+            let spliced = match item {
+                RispExp::List(l) => unquoted(l, "unquote-splicing"),
+                _ => None,
+            };
+            match spliced {
+                Some(x) => {
+                    if items > 0 {
+                        chunk.emit(Op::List(items));
+                        parts += 1;
+                        items = 0;
+                    }
+                    self.compile(x, chunk, false)?;
+                    parts += 1;
+                    spliced_any = true;
+                }
+                None => {
+                    self.compile_quasiquote(item, chunk)?;
+                    items += 1;
+                }
+            }
+        }
+        if items > 0 || parts == 0 {
+            chunk.emit(Op::List(items));
+            parts += 1;
+        }
+        if spliced_any {
+            chunk.emit(Op::Concat(parts));
+        }
+        Ok(())
+    }
+}
+
+// 声明函数体中所有def的变量, 与树遍历求值一样在整个函数中可见, 执行def之前未绑定;
+// fn和try的各部分在自己的作用域中编译, quote中的不是def; quasiquote中unquote的部分可能有def
+fn declare_defs(exp: &RispExp, names: &mut Vec<String>) {
+    let RispExp::List(list) = exp else {
+        return;
+    };
+    match list.first() {
+        Some(RispExp::Symbol(s)) if matches!(s.as_str(), "fn" | "try" | "quote") => return,
+        Some(RispExp::Symbol(s)) if s == "def" => {
+            if let Some(RispExp::Symbol(name)) = list.rest().first() {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+        }
+        _ => {}
+    }
+    list.iter().for_each(|x| declare_defs(x, names));
+}
+
+fn unquoted<'a>(list: &'a RispList, name: &str) -> Option<&'a RispExp> {
+    let mut items = list.iter();
+    match (items.next(), items.next(), items.next()) {
+        (Some(RispExp::Symbol(s)), Some(x), None) if s == name => Some(x),
+        _ => None,
+    }
+}
+
+fn has_unquote(exp: &RispExp) -> bool {
+    match exp {
+        RispExp::List(list) => {
+            unquoted(list, "unquote").is_some()
+                || unquoted(list, "unquote-splicing").is_some()
+                || list.iter().any(has_unquote)
+        }
+        RispExp::Vector(v) => v.iter().any(has_unquote),
+        RispExp::Set(s) => s.iter().any(has_unquote),
+        RispExp::Map(m) => m.iter().any(|(k, v)| has_unquote(k) || has_unquote(v)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read;
+
+    fn compile_str(src: &str) -> Result<Rc<Proto>, RispError> {
+        compile(&read(src).unwrap()[0].exp)
+    }
+
+    #[test]
+    fn fn_should_compile_locals_and_tail_calls() {
+        let top = compile_str("(fn (a & rest) (if a (g rest) (begin (set! a 1) (fn () a))))").unwrap();
+        assert_eq!(top.chunk.code, [Op::Closure(0), Op::Return]);
+        let f = &top.chunk.protos[0];
+        assert_eq!((f.params, f.rest, f.slots), (1, true, 2));
+        let code = [
+            Op::Local(0),
+            Op::JumpIfFalse(7),
+            Op::Global(0),
+            Op::Local(1),
+            // 尾位置的调用
+            Op::TailCall(1),
+            Op::Return,
+            Op::Jump(11),
+            Op::Const(1),
+            Op::SetLocal(0),
+            Op::Pop,
+            Op::Closure(0),
+            Op::Return,
+        ];
+        assert_eq!(f.chunk.code, code);
+        assert_eq!(f.chunk.consts, [RispExp::Symbol("g".to_string()), RispExp::Int(1)]);
+        // 内层函数访问外层的a
+        assert_eq!(f.chunk.protos[0].chunk.code, [Op::Outer(1, 0), Op::Return]);
+        // 出错的指令对应(g rest)
+        assert_eq!(f.chunk.form_at(4), Some(&read("(g rest)").unwrap()[0].exp));
+    }
+
+    #[test]
+    fn consts_should_not_merge_values_of_different_types() {
+        let top = compile_str("(list 1 1 1.0 'x)").unwrap();
+        assert_eq!(top.chunk.consts.len(), 4);
+        assert!(matches!(top.chunk.consts[2], RispExp::Number(_)));
+        assert_eq!(&top.chunk.code[1..5], [Op::Const(1), Op::Const(1), Op::Const(2), Op::Const(3)]);
+    }
+
+    #[test]
+    fn compile_should_reject_invalid_forms() {
+        let cases = [
+            ("(fn (a & b c) a)", "expected one symbol after &"),
+            ("(fn (1) 1)", "expected symbols in the argument list"),
+            ("(fn (a))", "expected second form"),
+            ("(if)", "expected test form"),
+        ];
+        for (src, msg) in cases {
+            assert_eq!(compile_str(src).unwrap_err().message(), msg, "{}", src);
+        }
+    }
+}
@@ -0,0 +1,559 @@
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::HashMap,
+    env, fmt, fs, process,
+    sync::atomic::{AtomicUsize, Ordering as AtomicOrdering},
+};
+
+use crate::{
+    gc::{self, Gc, Trace},
+    num::Num,
+    parser::parse_number,
+    Capabilities, RispError, RispExp, RispFunc, RispList, RispMap,
+};
+
+macro_rules! ensure_tonicity {
+    ($check_fn:expr) => {{
+        |args: &[RispExp]| -> Result<RispExp, RispError> {
+            let nums = Num::from_list(args)?;
+            if nums.is_empty() {
+                return Err(RispError::Reason("expected at least one number".to_string()));
+            }
+            let ok = nums.windows(2).all(|w| w[0].compare(&w[1]).is_some_and($check_fn));
+            Ok(RispExp::Boolean(ok))
+        }
+    }};
+}
+
+// 作用域链, clone后共享同一个frame, lambda通过它捕获定义时的作用域
+// frame由托管堆管理, lambda与作用域之间的循环引用由gc回收
+#[derive(Clone, Default)]
+pub struct RispEnv(Gc<RefCell<Frame>>);
+
+#[derive(Default)]
+struct Frame {
+    data: HashMap<String, RispExp>,
+    outer: Option<RispEnv>,
+    // (module name ...)定义的模块, 只在最外层作用域中使用
+    modules: HashMap<String, RispEnv>,
+}
+
+// 正在修改的frame无法访问, 它引用的对象会被当作根
+impl Trace for RefCell<Frame> {
+    fn trace(&self, visit: &mut dyn FnMut(usize)) {
+        if let Ok(frame) = self.try_borrow() {
+            frame.data.values().for_each(|v| v.trace(visit));
+            if let Some(o) = &frame.outer {
+                visit(o.id());
+            }
+            frame.modules.values().for_each(|m| visit(m.id()));
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut frame) = self.try_borrow_mut() {
+            frame.data.clear();
+            frame.outer = None;
+            frame.modules.clear();
+        }
+    }
+}
+
+impl RispEnv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn id(&self) -> usize {
+        self.0.id()
+    }
+
+    // 以当前作用域为外层创建新的作用域
+    pub fn extend(&self, data: HashMap<String, RispExp>) -> Self {
+        Self(Gc::new(RefCell::new(Frame { data, outer: Some(self.clone()), modules: HashMap::new() })))
+    }
+
+    // map get, 最外层作用域中也没有时按str/join查找模块中的定义
+    pub fn get(&self, k: &str) -> Option<RispExp> {
+        let frame = self.0.borrow();
+        match frame.data.get(k) {
+            Some(v) => Some(v.clone()),
+            None => match &frame.outer {
+                Some(o) => o.get(k),
+                None => {
+                    let (module, name) = k.rsplit_once('/')?;
+                    frame.modules.get(module)?.0.borrow().data.get(name).cloned()
+                }
+            },
+        }
+    }
+
+    // 最外层作用域
+    pub fn root(&self) -> RispEnv {
+        match &self.0.borrow().outer {
+            Some(o) => o.root(),
+            None => self.clone(),
+        }
+    }
+
+    // 模块的作用域, 其中的定义可以用name/x访问
+    pub fn module(&self, name: &str) -> Option<RispEnv> {
+        self.root().0.borrow().modules.get(name).cloned()
+    }
+
+    pub(crate) fn add_module(&self, name: &str, module: RispEnv) {
+        self.root().0.borrow_mut().modules.insert(name.to_string(), module);
+    }
+
+    pub(crate) fn remove_module(&self, name: &str) {
+        self.root().0.borrow_mut().modules.remove(name);
+    }
+
+    // 在当前作用域中定义
+    pub fn insert(&self, k: &str, v: RispExp) {
+        self.0.borrow_mut().data.insert(k.to_string(), v);
+    }
+
+    // 从当前作用域中删除
+    pub fn remove(&self, k: &str) -> Option<RispExp> {
+        self.0.borrow_mut().data.remove(k)
+    }
+
+    // 修改最近一层作用域中已定义的值
+    pub fn set(&self, k: &str, v: RispExp) -> Result<(), RispError> {
+        let mut frame = self.0.borrow_mut();
+        if let Some(slot) = frame.data.get_mut(k) {
+            *slot = v;
+            return Ok(());
+        }
+        match &frame.outer {
+            Some(o) => o.set(k, v),
+            None => Err(RispError::Reason(format!("set! undefined symbol k='{}'", k))),
+        }
+    }
+
+    // 当前作用域及外层中定义的所有名字, 包括模块中的str/join
+    pub fn keys(&self) -> Vec<String> {
+        let frame = self.0.borrow();
+        let mut keys: Vec<String> = frame.data.keys().cloned().collect();
+        if let Some(o) = &frame.outer {
+            keys.extend(o.keys());
+        }
+        for (name, module) in &frame.modules {
+            keys.extend(module.0.borrow().data.keys().map(|k| format!("{}/{}", name, k)));
+        }
+        keys
+    }
+}
+
+// lambda和作用域互相引用, 只输出当前frame中的名字
+impl fmt::Debug for RispEnv {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let frame = self.0.borrow();
+        let mut keys: Vec<&String> = frame.data.keys().collect();
+        keys.sort();
+        f.debug_struct("RispEnv")
+            .field("keys", &keys)
+            .field("outer", &frame.outer.is_some())
+            .finish()
+    }
+}
+
+fn string_arg(args: &[RispExp], idx: usize) -> Result<&str, RispError> {
+    match args.get(idx) {
+        Some(RispExp::Str(s)) => Ok(s),
+        Some(exp) => Err(RispError::Reason(format!("expected a string, got {}", exp))),
+        None => Err(RispError::Reason(format!("expected string argument idx={}", idx))),
+    }
+}
+
+fn first_arg(args: &[RispExp]) -> Result<&RispExp, RispError> {
+    args.first()
+        .ok_or(RispError::Reason("expected at least one argument".to_string()))
+}
+
+// 向量和集合按元素顺序转为list
+fn list_arg(args: &[RispExp], idx: usize) -> Result<RispList, RispError> {
+    match args.get(idx) {
+        Some(RispExp::List(list)) => Ok(list.clone()),
+        Some(RispExp::Vector(v)) => Ok(v.iter().cloned().collect()),
+        Some(RispExp::Set(s)) => Ok(s.iter().cloned().collect()),
+        Some(RispExp::Nil) => Ok(RispList::new()),
+        Some(exp) => Err(RispError::Reason(format!("expected a list, got {}", exp))),
+        None => Err(RispError::Reason(format!("expected list argument idx={}", idx))),
+    }
+}
+
+fn map_arg(args: &[RispExp], idx: usize) -> Result<RispMap, RispError> {
+    match args.get(idx) {
+        Some(RispExp::Map(m)) => Ok(m.clone()),
+        Some(RispExp::Nil) => Ok(RispMap::new()),
+        Some(exp) => Err(RispError::Reason(format!("expected a map, got {}", exp))),
+        None => Err(RispError::Reason(format!("expected map argument idx={}", idx))),
+    }
+}
+
+fn index_arg(args: &[RispExp], idx: usize) -> Result<Option<usize>, RispError> {
+    match args.get(idx) {
+        Some(RispExp::Int(n)) if *n >= 0 => Ok(Some(*n as usize)),
+        Some(exp) => Err(RispError::Reason(format!("expected a non negative integer, got {}", exp))),
+        None => Ok(None),
+    }
+}
+
+type Builtin = fn(&[RispExp]) -> Result<RispExp, RispError>;
+
+// 访问文件、进程和标准输出的内置函数, 按允许的宿主能力添加到环境中
+const HOST_BUILTINS: &[(Capabilities, &str, Builtin)] = &[
+    // (read-file "a.txt") -> "..."
+    (Capabilities::FILE, "read-file", |args| {
+        let path = string_arg(args, 0)?;
+        fs::read_to_string(path)
+            .map(RispExp::Str)
+            .map_err(|e| RispError::Reason(format!("failed to read {}: {}", path, e)))
+    }),
+    // (write-file "a.txt" "...") 覆盖文件内容, 返回nil
+    (Capabilities::FILE, "write-file", |args| {
+        let path = string_arg(args, 0)?;
+        fs::write(path, string_arg(args, 1)?)
+            .map(|_| RispExp::Nil)
+            .map_err(|e| RispError::Reason(format!("failed to write {}: {}", path, e)))
+    }),
+    // 未设置时返回nil
+    (Capabilities::PROCESS, "getenv", |args| {
+        Ok(env::var(string_arg(args, 0)?).map_or(RispExp::Nil, RispExp::Str))
+    }),
+    // (exit 1), 默认退出码为0
+    (Capabilities::PROCESS, "exit", |args| match args.first() {
+        Some(RispExp::Int(code)) => match i32::try_from(*code) {
+            Ok(code) => process::exit(code),
+            Err(_) => Err(RispError::Reason(format!("exit code out of range {}", code))),
+        },
+        Some(RispExp::BigInt(code)) => Err(RispError::Reason(format!("exit code out of range {}", code))),
+        Some(exp) => Err(RispError::Reason(format!("exit expected an integer, got {}", exp))),
+        None => process::exit(0),
+    }),
+    // (println "a" 1) 以空格分隔输出, 返回nil
+    (Capabilities::STDOUT, "println", |args| {
+        println!("{}", args.iter().map(RispExp::to_str_value).collect::<Vec<_>>().join(" "));
+        Ok(RispExp::Nil)
+    }),
+];
+
+// 所有内置函数, 包括访问文件、进程和标准输出的
+pub fn init_env() -> RispEnv {
+    init_env_with(Capabilities::ALL)
+}
+
+// 只添加允许的宿主内置函数; 在创建环境时决定, 之后不会有绑定到不允许的函数的别名
+pub fn init_env_with(capabilities: Capabilities) -> RispEnv {
+    let env = RispEnv::default();
+    let add = |name: &str, f: Builtin| {
+        env.insert(name, RispExp::Func(RispFunc::new(name, f)));
+    };
+    add("+", |args| {
+        let sum = Num::from_list(args)?.into_iter().fold(Num::Int(0), Num::add);
+        Ok(sum.into_exp())
+    });
+    // (- 10 1 2) -> 7, (- 5) -> -5
+    add("-", |args| {
+        let mut nums = Num::from_list(args)?.into_iter();
+        let first = nums
+            .next()
+            .ok_or(RispError::Reason("expected at least one number".to_string()))?;
+        if args.len() == 1 {
+            return Ok(Num::Int(0).sub(first).into_exp());
+        }
+        Ok(nums.fold(first, Num::sub).into_exp())
+    });
+    add("*", |args| {
+        let product = Num::from_list(args)?.into_iter().fold(Num::Int(1), Num::mul);
+        Ok(product.into_exp())
+    });
+    // (/ 6 3) -> 2, (/ 7 2) -> 3.5, (/ 2) -> 0.5
+    add("/", |args| {
+        let mut nums = Num::from_list(args)?.into_iter();
+        let first = nums
+            .next()
+            .ok_or(RispError::Reason("expected at least one number".to_string()))?;
+        if args.len() == 1 {
+            return Ok(Num::Int(1).div(first)?.into_exp());
+        }
+        let mut res = first;
+        for n in nums {
+            res = res.div(n)?;
+        }
+        Ok(res.into_exp())
+    });
+    add("mod", |args| match Num::from_list(args)?.as_slice() {
+        [a, b] => Ok(a.clone().modulo(b.clone())?.into_exp()),
+        _ => Err(RispError::Reason("mod expected two numbers".to_string())),
+    });
+    add("=", |args| {
+        if args.is_empty() {
+            return Err(RispError::Reason("expected at least one argument".to_string()));
+        }
+        Ok(RispExp::Boolean(args.windows(2).all(|w| w[0] == w[1])))
+    });
+    add(">", ensure_tonicity!(|o| o == Ordering::Greater));
+    add(">=", ensure_tonicity!(|o| o != Ordering::Less));
+    add("<", ensure_tonicity!(|o| o == Ordering::Less));
+    add("<=", ensure_tonicity!(|o| o != Ordering::Greater));
+
+    add("not", |args| Ok(RispExp::Boolean(!first_arg(args)?.is_truthy())));
+    add("nil?", |args| Ok(RispExp::Boolean(matches!(first_arg(args)?, RispExp::Nil))));
+
+    // list操作, nil按空list处理
+    add("list", |args| Ok(RispExp::List(args.iter().cloned().collect())));
+    add("car", |args| Ok(list_arg(args, 0)?.first().cloned().unwrap_or(RispExp::Nil)));
+    add("cdr", |args| Ok(RispExp::List(list_arg(args, 0)?.rest())));
+    // (cons 1 '(2 3)) -> (1 2 3), 与原list共享尾部
+    add("cons", |args| {
+        let head = first_arg(args)?.clone();
+        Ok(RispExp::List(RispList::cons(head, &list_arg(args, 1)?)))
+    });
+    add("reverse", |args| {
+        let list = list_arg(args, 0)?;
+        Ok(RispExp::List(list.iter().fold(RispList::new(), |acc, x| RispList::cons(x.clone(), &acc))))
+    });
+    add("empty?", |args| match first_arg(args)? {
+        RispExp::Str(s) => Ok(RispExp::Boolean(s.is_empty())),
+        RispExp::Vector(v) => Ok(RispExp::Boolean(v.is_empty())),
+        RispExp::Map(m) => Ok(RispExp::Boolean(m.is_empty())),
+        RispExp::Set(s) => Ok(RispExp::Boolean(s.is_empty())),
+        _ => Ok(RispExp::Boolean(list_arg(args, 0)?.is_empty())),
+    });
+    // 字符串按字符计算长度
+    add("length", |args| match first_arg(args)? {
+        RispExp::Str(s) => Ok(RispExp::from(s.chars().count() as i64)),
+        RispExp::Vector(v) => Ok(RispExp::from(v.len() as i64)),
+        RispExp::Map(m) => Ok(RispExp::from(m.len() as i64)),
+        RispExp::Set(s) => Ok(RispExp::from(s.len() as i64)),
+        _ => Ok(RispExp::from(list_arg(args, 0)?.len() as i64)),
+    });
+
+    // 向量、映射和集合, 修改时返回新的值
+    add("vector", |args| Ok(RispExp::Vector(args.to_vec().into())));
+    add("vec", |args| Ok(RispExp::Vector(list_arg(args, 0)?.iter().cloned().collect())));
+    add("hash-map", |args| Ok(RispExp::Map(RispMap::from_pairs(args.to_vec())?)));
+    add("hash-set", |args| Ok(RispExp::Set(args.iter().cloned().collect())));
+    // (get {:a 1} :a) -> 1, (get [1 2] 5 0) -> 0, 不存在时为默认值或nil
+    add("get", |args| {
+        let key = args
+            .get(1)
+            .ok_or(RispError::Reason("get expected a collection and a key".to_string()))?;
+        let found = match first_arg(args)? {
+            RispExp::Map(m) => m.get(key).cloned(),
+            RispExp::Vector(v) => match key {
+                RispExp::Int(i) if *i >= 0 => v.get(*i as usize).cloned(),
+                _ => None,
+            },
+            RispExp::Set(s) => s.contains(key).then(|| key.clone()),
+            RispExp::Nil => None,
+            exp => return Err(RispError::Reason(format!("get expected a collection, got {}", exp))),
+        };
+        Ok(found.or_else(|| args.get(2).cloned()).unwrap_or(RispExp::Nil))
+    });
+    add("contains?", |args| {
+        let key = args
+            .get(1)
+            .ok_or(RispError::Reason("contains? expected a collection and a key".to_string()))?;
+        let found = match first_arg(args)? {
+            RispExp::Map(m) => m.contains_key(key),
+            RispExp::Set(s) => s.contains(key),
+            RispExp::Vector(v) => matches!(key, RispExp::Int(i) if *i >= 0 && (*i as usize) < v.len()),
+            RispExp::Nil => false,
+            exp => return Err(RispError::Reason(format!("contains? expected a collection, got {}", exp))),
+        };
+        Ok(RispExp::Boolean(found))
+    });
+    // (assoc {:a 1} :b 2) -> {:a 1 :b 2}, (assoc [1 2] 0 3) -> [3 2]
+    add("assoc", |args| {
+        let pairs = args.get(1..).unwrap_or_default();
+        if pairs.is_empty() || !pairs.len().is_multiple_of(2) {
+            return Err(RispError::Reason("assoc expected a collection and key value pairs".to_string()));
+        }
+        match first_arg(args)? {
+            RispExp::Vector(v) => {
+                let mut v = v.clone();
+                for kv in pairs.chunks(2) {
+                    v = match &kv[0] {
+                        RispExp::Int(i) if *i >= 0 => v.assoc(*i as usize, kv[1].clone()),
+                        _ => None,
+                    }
+                    .ok_or(RispError::Reason(format!("assoc index out of range {}", kv[0])))?;
+                }
+                Ok(RispExp::Vector(v))
+            }
+            _ => {
+                let mut m = map_arg(args, 0)?;
+                for kv in pairs.chunks(2) {
+                    m = m.assoc(kv[0].clone(), kv[1].clone());
+                }
+                Ok(RispExp::Map(m))
+            }
+        }
+    });
+    add("dissoc", |args| {
+        let mut m = map_arg(args, 0)?;
+        for key in args.iter().skip(1) {
+            m = m.dissoc(key);
+        }
+        Ok(RispExp::Map(m))
+    });
+    add("keys", |args| Ok(RispExp::List(map_arg(args, 0)?.keys().cloned().collect())));
+    add("vals", |args| Ok(RispExp::List(map_arg(args, 0)?.values().cloned().collect())));
+    // (conj [1] 2) -> [1 2], (conj #{1} 2) -> #{1 2}, (conj '(1) 2) -> (2 1)
+    add("conj", |args| {
+        let items = args.iter().skip(1).cloned();
+        match first_arg(args)? {
+            RispExp::Vector(v) => Ok(RispExp::Vector(items.fold(v.clone(), |v, x| v.push(x)))),
+            RispExp::Set(s) => Ok(RispExp::Set(items.fold(s.clone(), |s, x| s.conj(x)))),
+            _ => Ok(RispExp::List(items.fold(list_arg(args, 0)?, |l, x| RispList::cons(x, &l)))),
+        }
+    });
+    add("disj", |args| match first_arg(args)? {
+        RispExp::Set(s) => Ok(RispExp::Set(args.iter().skip(1).fold(s.clone(), |s, x| s.disj(x)))),
+        exp => Err(RispError::Reason(format!("disj expected a set, got {}", exp))),
+    });
+    add("keyword", |args| Ok(RispExp::Keyword(string_arg(args, 0)?.to_string())));
+    add("keyword?", |args| Ok(RispExp::Boolean(matches!(first_arg(args)?, RispExp::Keyword(_)))));
+    add("vector?", |args| Ok(RispExp::Boolean(matches!(first_arg(args)?, RispExp::Vector(_)))));
+    add("map?", |args| Ok(RispExp::Boolean(matches!(first_arg(args)?, RispExp::Map(_)))));
+    add("set?", |args| Ok(RispExp::Boolean(matches!(first_arg(args)?, RispExp::Set(_)))));
+
+    // (str "a" 1 \b) -> "a1b"
+    add("str", |args| Ok(RispExp::Str(args.iter().map(RispExp::to_str_value).collect())));
+    // (concat "a" "b") -> "ab", (concat (list 1) (list 2)) -> (1 2)
+    add("concat", |args| {
+        if args.iter().all(|x| matches!(x, RispExp::List(_))) && !args.is_empty() {
+            let list = args
+                .iter()
+                .flat_map(|x| match x {
+                    RispExp::List(l) => l.to_vec(),
+                    _ => vec![],
+                })
+                .collect();
+            return Ok(RispExp::List(list));
+        }
+        let mut s = String::new();
+        for i in 0..args.len() {
+            s.push_str(string_arg(args, i)?);
+        }
+        Ok(RispExp::Str(s))
+    });
+    // (substring "hello" 1 3) -> "el", 按字符计算下标
+    add("substring", |args| {
+        let s = string_arg(args, 0)?;
+        let len = s.chars().count();
+        let start = index_arg(args, 1)?.unwrap_or(0);
+        let end = index_arg(args, 2)?.unwrap_or(len);
+        if start > end || end > len {
+            return Err(RispError::Reason(format!(
+                "substring index out of range start={} end={} len={}",
+                start, end, len
+            )));
+        }
+        Ok(RispExp::Str(s.chars().skip(start).take(end - start).collect()))
+    });
+    // (gensym "tmp") -> tmp__1, 宏中用于生成不会与用户代码冲突的符号
+    add("gensym", |args| {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let prefix = match args.first() {
+            Some(_) => string_arg(args, 0)?,
+            None => "G",
+        };
+        Ok(RispExp::Symbol(format!("{}__{}", prefix, COUNTER.fetch_add(1, AtomicOrdering::Relaxed) + 1)))
+    });
+    // (raise {:kind :not-found :message "no such key"}), 由try中的catch捕获
+    add("raise", |args| Err(RispError::raised(first_arg(args)?.clone())));
+    // ((allocations 10) (live 8) (collections 1) (freed 2))
+    add("gc-stats", |_| {
+        let stats = gc::gc_stats();
+        let pairs = [
+            ("allocations", stats.allocations),
+            ("live", stats.live),
+            ("collections", stats.collections),
+            ("freed", stats.freed),
+        ];
+        let list = pairs
+            .iter()
+            .map(|(k, v)| RispExp::List(vec![RispExp::Symbol(k.to_string()), RispExp::from(*v as i64)].into()))
+            .collect();
+        Ok(RispExp::List(list))
+    });
+    // 立即回收, 返回回收的对象数
+    add("gc", |_| Ok(RispExp::from(gc::collect() as i64)));
+    // 不是数字时返回nil
+    add("string->number", |args| {
+        let s = string_arg(args, 0)?;
+        Ok(parse_number(s.trim()).unwrap_or(RispExp::Nil))
+    });
+    for (capability, name, f) in HOST_BUILTINS {
+        if capabilities.contains(*capability) {
+            add(name, *f);
+        }
+    }
+    env
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eval, read, EvalContext};
+
+    fn eval_str(src: &str, env: &RispEnv) -> Result<RispExp, RispError> {
+        eval(&read(src)?[0].exp, env, &mut EvalContext::default())
+    }
+
+    #[test]
+    fn builtins_should_work_on_numbers_lists_collections_and_strings() {
+        let env = init_env();
+        let cases = [
+            ("(+ 1 2 3)", "6"),
+            ("(- 10 1 2)", "7"),
+            ("[(- 5) (- 1.5) (- -9223372036854775808)]", "[-5 -1.5 9223372036854775808]"),
+            ("(* 2 3.0)", "6.0"),
+            ("(/ 1 2)", "0.5"),
+            ("(mod 7 -3)", "-2"),
+            ("[(= 1 1.0 1) (< 1 2 2) (<= 1 2 2) (not nil) (nil? false)]", "[true false true true false]"),
+            ("[(car '(1 2)) (cdr '(1)) (car '()) (cons 0 '(1)) (reverse [1 2 3])]", "[1 () nil (0 1) (3 2 1)]"),
+            (r#"[(empty? []) (length "héllo") (length #{1 2}) (length {:a 1})]"#, "[true 5 2 1]"),
+            ("[(vector 1 2) (vec '(1 2)) (hash-map :a 1) (hash-set 1 1 2)]", "[[1 2] [1 2] {:a 1} #{1 2}]"),
+            ("[(get [1 2] 5) (get {:a 1} :b) (get {:a 1} :b 0) (vals {:a 1 :b 2})]", "[nil nil 0 (1 2)]"),
+            ("[(keyword? :a) (vector? []) (map? {}) (set? #{}) (keyword \"a\")]", "[true true true true :a]"),
+            (r#"[(concat "ab" "c") (substring "héllo" 1 3) (str "a" 'b :c 1.5 nil)]"#, r#"["abc" "él" "ab:c1.5"]"#),
+        ];
+        for (src, expected) in cases {
+            assert_eq!(eval_str(src, &env).map(|v| v.to_string()).as_deref(), Ok(expected), "{}", src);
+        }
+        let errors = [
+            (r#"(+ 1 "a")"#, r#"expected a number, got "a""#),
+            ("(/ 1 0)", "division by zero"),
+            (r#"(substring "abc" 2 1)"#, "substring index out of range start=2 end=1 len=3"),
+            ("(assoc [1] 5 2)", "assoc index out of range 5"),
+            ("(hash-map :a)", "expected an even number of forms for a map"),
+        ];
+        for (src, msg) in errors {
+            assert_eq!(eval_str(src, &env).unwrap_err().message(), msg, "{}", src);
+        }
+    }
+
+    #[test]
+    fn set_should_modify_the_nearest_definition() {
+        let root = RispEnv::new();
+        root.insert("a", RispExp::Int(1));
+        let outer = root.extend(HashMap::from([("b".to_string(), RispExp::Int(2))]));
+        let inner = outer.extend(HashMap::from([("a".to_string(), RispExp::Int(3))]));
+        inner.set("a", RispExp::Int(4)).unwrap();
+        inner.set("b", RispExp::Int(5)).unwrap();
+        assert_eq!(root.get("a"), Some(RispExp::Int(1)));
+        assert_eq!(inner.get("a"), Some(RispExp::Int(4)));
+        // clone共享同一个frame
+        assert_eq!(outer.clone().get("b"), Some(RispExp::Int(5)));
+        let err = inner.set("c", RispExp::Nil).unwrap_err();
+        assert_eq!(err.message(), "set! undefined symbol k='c'");
+        assert_eq!(inner.get("c"), None);
+    }
+}
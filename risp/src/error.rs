@@ -0,0 +1,132 @@
+use std::{
+    fmt::{self, Write as _},
+    rc::Rc,
+};
+
+use crate::{eval::is_anonymous, RispExp, RispList, RispMap, Span};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RispError {
+    Reason(String),
+    // 求值出错的表达式, 由Interpreter在源码中定位
+    InForm(String, Box<RispExp>),
+    // 源码中的位置, snippet为出错的行并用^标出位置, 来自文件时file为文件路径
+    Spanned { msg: String, span: Span, snippet: String, file: Option<String> },
+    // (raise value), msg为映射中的:message或值的文本
+    Raised(String, Box<RispExp>),
+    // 超出沙箱的资源限制, 不能被try捕获
+    Limit(String),
+}
+
+impl RispError {
+    pub fn spanned(msg: impl Into<String>, span: Span, src: &str) -> Self {
+        RispError::Spanned { msg: msg.into(), span, snippet: snippet(src, span), file: None }
+    }
+
+    pub fn raised(value: RispExp) -> Self {
+        let msg = match &value {
+            RispExp::Map(m) => match m.get(&keyword("message")) {
+                Some(RispExp::Str(s)) => s.clone(),
+                _ => value.to_string(),
+            },
+            _ => value.to_str_value(),
+        };
+        RispError::Raised(msg, Box::new(value))
+    }
+
+    // 标记出错的文件, 已经标记过的错误来自更内层load的文件, 保持不变
+    pub fn in_file(self, path: impl Into<String>) -> Self {
+        match self {
+            RispError::Spanned { msg, span, snippet, file: None } => {
+                RispError::Spanned { msg, span, snippet, file: Some(path.into()) }
+            }
+            e => e,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            RispError::Reason(msg)
+            | RispError::InForm(msg, _)
+            | RispError::Spanned { msg, .. }
+            | RispError::Raised(msg, _)
+            | RispError::Limit(msg) => msg,
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            RispError::Spanned { span, .. } => Some(*span),
+            _ => None,
+        }
+    }
+
+    // 记录最内层出错的表达式
+    pub(crate) fn in_form(self, exp: &RispExp) -> Self {
+        match self {
+            RispError::Reason(msg) => RispError::InForm(msg, Box::new(exp.clone())),
+            e => e,
+        }
+    }
+
+    // catch得到的错误对象 {:kind :error :message "..." :stack ("f" "g")};
+    // raise的值为映射时直接使用并补充调用栈, 其他值放在:value中
+    pub(crate) fn to_exp(&self, stack: &[Rc<String>]) -> RispExp {
+        let stack: RispList = stack
+            .iter()
+            .filter(|name| !is_anonymous(name))
+            .map(|name| RispExp::Str(name.to_string()))
+            .collect();
+        let map = match self {
+            RispError::Raised(_, value) => match value.as_ref() {
+                RispExp::Map(m) if m.contains_key(&keyword("stack")) => return RispExp::Map(m.clone()),
+                RispExp::Map(m) => m.clone(),
+                value => RispMap::new()
+                    .assoc(keyword("kind"), keyword("raise"))
+                    .assoc(keyword("message"), RispExp::Str(self.message().to_string()))
+                    .assoc(keyword("value"), value.clone()),
+            },
+            _ => RispMap::new()
+                .assoc(keyword("kind"), keyword("error"))
+                .assoc(keyword("message"), RispExp::Str(self.message().to_string())),
+        };
+        RispExp::Map(map.assoc(keyword("stack"), RispExp::List(stack)))
+    }
+}
+
+fn keyword(name: &str) -> RispExp {
+    RispExp::Keyword(name.to_string())
+}
+
+// 出错的行, 多行时只展示第一行
+fn snippet(src: &str, span: Span) -> String {
+    let line_start = src[..span.start.min(src.len())].rfind('\n').map_or(0, |i| i + 1);
+    let line = src[line_start..].lines().next().unwrap_or_default();
+    let line_end = line_start + line.len();
+    let width = src[span.start.min(line_end)..span.end.clamp(span.start, line_end)]
+        .chars()
+        .count()
+        .max(1);
+    let gutter = span.line.to_string().len();
+    let mut out = String::new();
+    let _ = writeln!(out, "{:>gutter$} |", "");
+    let _ = writeln!(out, "{} | {}", span.line, line);
+    let _ = write!(out, "{:>gutter$} | {}{}", "", " ".repeat(span.col - 1), "^".repeat(width));
+    out
+}
+
+impl fmt::Display for RispError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RispError::Reason(msg) | RispError::InForm(msg, _) | RispError::Raised(msg, _) | RispError::Limit(msg) => {
+                write!(f, "{}", msg)
+            }
+            RispError::Spanned { msg, span, snippet, file: None } => write!(f, "{}\n --> {}\n{}", msg, span, snippet),
+            RispError::Spanned { msg, span, snippet, file: Some(file) } => {
+                write!(f, "{}\n --> {}:{}\n{}", msg, file, span, snippet)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RispError {}
@@ -0,0 +1,948 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Instant,
+};
+
+use crate::{
+    live_objects,
+    parser::{nesting_error, MAX_NESTING},
+    read, vm, Capabilities, Closure, Limits, RispEnv, RispError, RispExp, RispFunc, RispLambda, RispList, RispMap,
+};
+
+// 默认的最大递归深度, 尾调用不计入
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+// 剩余的栈少于RED_ZONE时在堆上分配STACK_SEGMENT大小的新栈继续递归,
+// debug构建中每层递归约占用8KB栈, 经过map等内置函数时更多, 默认2MB的线程也能达到最大深度
+const RED_ZONE: usize = 256 * 1024;
+const STACK_SEGMENT: usize = 4 * 1024 * 1024;
+
+// 每消耗这么多fuel检查一次时间和存活对象数
+const CHECK_INTERVAL: u64 = 1024;
+
+// 特殊形式, 参数不预先求值
+pub const SPECIAL_FORMS: &[&str] = &[
+    "if",
+    "begin",
+    "def",
+    "set!",
+    "fn",
+    "load",
+    "quote",
+    "quasiquote",
+    "defmacro",
+    "macroexpand",
+    "try",
+    "module",
+    "import",
+];
+
+// 求值状态, 记录当前递归深度、调用栈和剩余的资源额度
+#[derive(Debug, Clone)]
+pub struct EvalContext {
+    pub max_depth: usize,
+    pub(crate) depth: usize,
+    // 调用中的函数名称, 由外到内
+    pub(crate) stack: Vec<Rc<String>>,
+    // 当前eval循环开始时调用栈的长度, 循环中的尾调用替换栈顶而不是增长
+    pub(crate) base: usize,
+    // 最近一次出错时的调用栈, 由try取出放入错误对象
+    pub(crate) error_stack: Option<Vec<Rc<String>>>,
+    limits: Limits,
+    // 剩余的求值步数, 不限制时为u64::MAX
+    fuel: u64,
+    // 设置限制时的存活对象数
+    object_base: usize,
+    capabilities: Capabilities,
+    // import查找模块文件的目录
+    module_path: Vec<PathBuf>,
+}
+
+impl Default for EvalContext {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_DEPTH)
+    }
+}
+
+impl EvalContext {
+    pub fn new(max_depth: usize) -> Self {
+        Self {
+            max_depth,
+            depth: 0,
+            stack: vec![],
+            base: 0,
+            error_stack: None,
+            limits: Limits::default(),
+            fuel: u64::MAX,
+            object_base: 0,
+            capabilities: Capabilities::default(),
+            module_path: vec![PathBuf::from(".")],
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    // fuel和对象数从现在开始计算
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.fuel = limits.fuel.unwrap_or(u64::MAX);
+        self.object_base = live_objects();
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    pub fn remaining_fuel(&self) -> Option<u64> {
+        self.limits.fuel.map(|_| self.fuel)
+    }
+
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.capabilities = capabilities;
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    // 按添加的顺序查找, 默认只有当前目录
+    pub fn add_module_path(&mut self, dir: impl Into<PathBuf>) {
+        self.module_path.push(dir.into());
+    }
+
+    pub fn module_path(&self) -> &[PathBuf] {
+        &self.module_path
+    }
+
+    // 使用宿主能力的特殊形式在执行前检查
+    pub(crate) fn require(&self, capability: Capabilities, name: &str) -> Result<(), RispError> {
+        if self.capabilities.contains(capability) {
+            Ok(())
+        } else {
+            Err(RispError::Reason(format!("{} is not allowed in the sandbox", name)))
+        }
+    }
+
+    // 消耗一步fuel, 每CHECK_INTERVAL步检查一次截止时间和存活对象数, 超出限制时返回false;
+    // 不返回Result, 避免增大eval的栈帧
+    pub(crate) fn tick(&mut self) -> bool {
+        if self.fuel == 0 {
+            return false;
+        }
+        self.fuel -= 1;
+        !self.fuel.is_multiple_of(CHECK_INTERVAL) || self.check_limits().is_ok()
+    }
+
+    pub(crate) fn depth_error(&self) -> RispError {
+        RispError::Reason(format!("maximum recursion depth {} exceeded", self.max_depth))
+    }
+
+    // 内置函数的结果超过单个值的大小限制时返回错误
+    pub(crate) fn check_value(&self, exp: &RispExp) -> Result<(), RispError> {
+        let Some(max) = self.limits.max_value_bytes else {
+            return Ok(());
+        };
+        let size = match exp {
+            RispExp::Str(s) => s.len(),
+            RispExp::BigInt(n) => n.bits().div_ceil(8) as usize,
+            _ => 0,
+        };
+        if size > max {
+            return Err(RispError::Limit(format!("value of {} bytes exceeds the limit of {} bytes", size, max)));
+        }
+        Ok(())
+    }
+
+    // tick返回false时的错误
+    pub(crate) fn limit_error(&self) -> RispError {
+        match self.check_limits() {
+            Err(e) => e,
+            Ok(_) => RispError::Limit("fuel exhausted".to_string()),
+        }
+    }
+
+    fn check_limits(&self) -> Result<(), RispError> {
+        if self.limits.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(RispError::Limit("deadline exceeded".to_string()));
+        }
+        match self.limits.max_objects {
+            Some(max) if live_objects().saturating_sub(self.object_base) > max => {
+                Err(RispError::Limit(format!("allocation limit of {} objects exceeded", max)))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// 尾位置的表达式交给eval的循环继续计算, 不增加rust调用栈
+enum Tail {
+    Value(RispExp),
+    Eval(RispExp, RispEnv),
+}
+
+// (if false 1 2) -> 2, nil和false为假
+fn eval_if_args(arg_forms: &RispList, env: &RispEnv, ctx: &mut EvalContext) -> Result<Tail, RispError> {
+    let test_form = arg_forms
+        .first()
+        .ok_or(RispError::Reason("expected test form".to_string()))?;
+    let test_eval = eval(test_form, env, ctx)?;
+    let form_idx = if test_eval.is_truthy() { 1 } else { 2 };
+    match arg_forms.get(form_idx) {
+        Some(res_form) => Ok(Tail::Eval(res_form.clone(), env.clone())),
+        // 没有else分支时为nil
+        None if form_idx == 2 => Ok(Tail::Value(RispExp::Nil)),
+        None => Err(RispError::Reason(format!("expected form idx={}", form_idx))),
+    }
+}
+
+// (begin (def a 1) (+ a 1)) -> 2, 依次执行并返回最后一个
+fn eval_begin_args(arg_forms: &RispList, env: &RispEnv, ctx: &mut EvalContext) -> Result<Tail, RispError> {
+    let mut forms = arg_forms.iter();
+    let mut last = forms
+        .next()
+        .ok_or(RispError::Reason("begin expected at least one form".to_string()))?;
+    for form in forms {
+        eval(last, env, ctx)?;
+        last = form;
+    }
+    Ok(Tail::Eval(last.clone(), env.clone()))
+}
+
+// def a 1 => (+ a 1)
+fn eval_def_args(arg_forms: &RispList, env: &RispEnv, ctx: &mut EvalContext) -> Result<RispExp, RispError> {
+    let first_form = arg_forms
+        .first()
+        .ok_or(RispError::Reason("expected first form".to_string()))?;
+    let first_str = match first_form {
+        RispExp::Symbol(s) => Ok(s.clone()),
+        _ => Err(RispError::Reason("expected first form to be a symbol".to_string())),
+    }?;
+    let second_form = arg_forms
+        .get(1)
+        .ok_or(RispError::Reason("expected second form".to_string()))?;
+    if arg_forms.len() > 2 {
+        return Err(RispError::Reason("def can only have two forms ".to_string()));
+    }
+    let second_eval = match eval(second_form, env, ctx)? {
+        // (def add (fn ...)) 为匿名函数命名
+        RispExp::Lambda(mut lambda) if lambda.name.as_str() == "fn" => {
+            lambda.name = Rc::new(first_str.clone());
+            RispExp::Lambda(lambda)
+        }
+        exp => exp,
+    };
+    env.insert(&first_str, second_eval);
+    Ok(first_form.clone())
+}
+
+// (set! a 2) 修改外层作用域中已定义的值
+fn eval_set_args(arg_forms: &RispList, env: &RispEnv, ctx: &mut EvalContext) -> Result<RispExp, RispError> {
+    let name = match arg_forms.first() {
+        Some(RispExp::Symbol(s)) => s.clone(),
+        _ => return Err(RispError::Reason("expected first form to be a symbol".to_string())),
+    };
+    let value_form = match (arg_forms.len(), arg_forms.get(1)) {
+        (2, Some(form)) => form,
+        _ => return Err(RispError::Reason("set! must have two forms".to_string())),
+    };
+    let value = eval(value_form, env, ctx)?;
+    env.set(&name, value.clone())?;
+    Ok(value)
+}
+
+fn eval_lambda_args(arg_forms: &RispList, env: &RispEnv) -> Result<RispExp, RispError> {
+    new_lambda("fn", arg_forms, env).map(RispExp::Lambda)
+}
+
+// (fn (a & rest) body), &之后的参数收集为list
+fn new_lambda(name: &str, arg_forms: &RispList, env: &RispEnv) -> Result<RispLambda, RispError> {
+    let params_exp = arg_forms
+        .first()
+        .ok_or(RispError::Reason("expected args form".to_string()))?;
+    let body_exp = arg_forms
+        .get(1)
+        .ok_or(RispError::Reason("expected second form".to_string()))?;
+    if arg_forms.len() > 2 {
+        return Err(RispError::Reason(format!("{} definition can only have two forms ", name)));
+    }
+    parse_list_of_symbol_strings(Rc::new(params_exp.clone()))?;
+    Ok(RispLambda {
+        name: Rc::new(name.to_string()),
+        body_exp: Rc::new(body_exp.clone()),
+        params_exp: Rc::new(params_exp.clone()),
+        env: env.clone(),
+    })
+}
+
+// (defmacro unless (test & body) `(if ~test nil (begin ~@body)))
+fn eval_defmacro_args(arg_forms: &RispList, env: &RispEnv) -> Result<RispExp, RispError> {
+    let name = match arg_forms.first() {
+        Some(RispExp::Symbol(s)) => s.clone(),
+        _ => return Err(RispError::Reason("expected macro name to be a symbol".to_string())),
+    };
+    let mut mac = new_lambda("defmacro", &arg_forms.rest(), env)?;
+    mac.name = Rc::new(name.clone());
+    env.insert(&name, RispExp::Macro(mac));
+    Ok(RispExp::Symbol(name))
+}
+
+// try的各个部分, 多个body组成begin
+pub(crate) struct TryForm {
+    pub(crate) body: RispExp,
+    pub(crate) catch: Option<(String, RispExp)>,
+    pub(crate) finally: Option<RispExp>,
+}
+
+// (try body... (catch e handler...) (finally cleanup...)), catch和finally都可以省略
+pub(crate) fn parse_try<'a>(arg_forms: impl IntoIterator<Item = &'a RispExp>) -> Result<TryForm, RispError> {
+    let mut body = vec![RispExp::Symbol("begin".to_string())];
+    let (mut catch, mut finally) = (None, None);
+    for form in arg_forms {
+        let clause = match form {
+            RispExp::List(list) => match list.first() {
+                Some(RispExp::Symbol(s)) if s == "catch" || s == "finally" => Some((s.as_str(), list.rest())),
+                _ => None,
+            },
+            _ => None,
+        };
+        let Some((name, rest)) = clause else {
+            if catch.is_some() || finally.is_some() {
+                return Err(RispError::Reason("try expected catch and finally after the body".to_string()));
+            }
+            body.push(form.clone());
+            continue;
+        };
+        let mut forms = vec![RispExp::Symbol("begin".to_string())];
+        match (name, rest.first()) {
+            // (catch e handler...)
+            ("catch", Some(RispExp::Symbol(e))) if catch.is_none() && finally.is_none() && rest.len() > 1 => {
+                forms.extend(rest.iter().skip(1).cloned());
+                catch = Some((e.clone(), RispExp::List(forms.into())));
+            }
+            ("finally", _) if finally.is_none() && !rest.is_empty() => {
+                forms.extend(rest.iter().cloned());
+                finally = Some(RispExp::List(forms.into()));
+            }
+            _ => return Err(RispError::Reason(format!("invalid {} clause in try", name))),
+        }
+    }
+    if body.len() == 1 {
+        return Err(RispError::Reason("try expected a body".to_string()));
+    }
+    Ok(TryForm { body: RispExp::List(body.into()), catch, finally })
+}
+
+// body出错时恢复递归深度和调用栈, 用错误对象调用handler; finally总会执行, 它的错误优先;
+// 超出资源限制时直接返回, 不执行handler和finally
+pub(crate) fn run_try<B, H, F>(
+    ctx: &mut EvalContext,
+    body: B,
+    handler: Option<H>,
+    finally: Option<F>,
+) -> Result<RispExp, RispError>
+where
+    B: FnOnce(&mut EvalContext) -> Result<RispExp, RispError>,
+    H: FnOnce(RispExp, &mut EvalContext) -> Result<RispExp, RispError>,
+    F: FnOnce(&mut EvalContext) -> Result<RispExp, RispError>,
+{
+    let (depth, len, base) = (ctx.depth, ctx.stack.len(), ctx.base);
+    let res = match (body(ctx), handler) {
+        (Err(e @ RispError::Limit(_)), _) => return Err(e),
+        (Err(e), Some(handler)) => {
+            ctx.depth = depth;
+            ctx.stack.truncate(len);
+            ctx.base = base;
+            let stack = ctx.error_stack.take().unwrap_or_default();
+            handler(e.to_exp(&stack), ctx)
+        }
+        (res, _) => res,
+    };
+    if let Some(finally) = finally {
+        let pending = ctx.error_stack.take();
+        finally(ctx)?;
+        ctx.error_stack = pending;
+    }
+    res
+}
+
+// body、handler和finally都在新的作用域中执行, 与虚拟机中编译为函数的行为一致
+fn eval_try_args(arg_forms: &RispList, env: &RispEnv, ctx: &mut EvalContext) -> Result<RispExp, RispError> {
+    let form = parse_try(arg_forms)?;
+    let handler = form.catch.map(|(name, handler)| {
+        move |err, ctx: &mut EvalContext| eval(&handler, &env.extend(HashMap::from([(name, err)])), ctx)
+    });
+    let finally = form.finally.map(|f| move |ctx: &mut EvalContext| eval(&f, &env.extend(HashMap::new()), ctx));
+    run_try(ctx, |ctx| eval(&form.body, &env.extend(HashMap::new()), ctx), handler, finally)
+}
+
+// 'x -> x, 参数作为数据返回
+fn eval_quote_args(arg_forms: &RispList) -> Result<RispExp, RispError> {
+    match only_form(arg_forms) {
+        Some(exp) => Ok(exp.clone()),
+        _ => Err(RispError::Reason("quote expected one form".to_string())),
+    }
+}
+
+// `(a ~b ~@c) 只计算unquote的部分, unquote-splicing的结果展开到外层list中
+fn eval_quasiquote_args(arg_forms: &RispList, env: &RispEnv, ctx: &mut EvalContext) -> Result<RispExp, RispError> {
+    match only_form(arg_forms) {
+        Some(exp) => quasiquote(exp, env, ctx),
+        _ => Err(RispError::Reason("quasiquote expected one form".to_string())),
+    }
+}
+
+// 向量、映射和集合中也可以使用unquote-splicing
+fn quasiquote(exp: &RispExp, env: &RispEnv, ctx: &mut EvalContext) -> Result<RispExp, RispError> {
+    match exp {
+        RispExp::List(list) => match unquoted(list, "unquote") {
+            Some(x) => eval(x, env, ctx),
+            None => quasiquote_items(list.iter(), env, ctx).map(|items| RispExp::List(items.into())),
+        },
+        RispExp::Vector(v) => quasiquote_items(v.iter(), env, ctx).map(|items| RispExp::Vector(items.into())),
+        RispExp::Set(s) => quasiquote_items(s.iter(), env, ctx).map(|items| RispExp::Set(items.into_iter().collect())),
+        RispExp::Map(m) => {
+            let items = quasiquote_items(m.iter().flat_map(|(k, v)| [k, v]), env, ctx)?;
+            RispMap::from_pairs(items).map(RispExp::Map)
+        }
+        _ => Ok(exp.clone()),
+    }
+}
+
+fn quasiquote_items<'a>(
+    items: impl Iterator<Item = &'a RispExp>,
+    env: &RispEnv,
+    ctx: &mut EvalContext,
+) -> Result<Vec<RispExp>, RispError> {
+    let mut ret = vec![];
+    for item in items {
+        let spliced = match item {
+            RispExp::List(l) => unquoted(l, "unquote-splicing"),
+            _ => None,
+        };
+        match spliced {
+            Some(x) => match eval(x, env, ctx)? {
+                RispExp::List(items) => ret.extend(items.iter().cloned()),
+                RispExp::Nil => {}
+                exp => return Err(RispError::Reason(format!("unquote-splicing expected a list, got {}", exp))),
+            },
+            None => ret.push(quasiquote(item, env, ctx)?),
+        }
+    }
+    Ok(ret)
+}
+
+// (unquote x) -> x
+fn unquoted<'a>(list: &'a RispList, name: &str) -> Option<&'a RispExp> {
+    let mut items = list.iter();
+    match (items.next(), items.next(), items.next()) {
+        (Some(RispExp::Symbol(s)), Some(x), None) if s == name => Some(x),
+        _ => None,
+    }
+}
+
+// (quote x)等只接受一个参数的形式
+fn only_form(arg_forms: &RispList) -> Option<&RispExp> {
+    let mut forms = arg_forms.iter();
+    match (forms.next(), forms.next()) {
+        (Some(x), None) => Some(x),
+        _ => None,
+    }
+}
+
+// (macroexpand '(unless false 1)) 展开但不执行
+fn eval_macroexpand_args(arg_forms: &RispList, env: &RispEnv, ctx: &mut EvalContext) -> Result<RispExp, RispError> {
+    match only_form(arg_forms) {
+        Some(form) => {
+            let exp = eval(form, env, ctx)?;
+            macroexpand(&exp, env, ctx)
+        }
+        _ => Err(RispError::Reason("macroexpand expected one form".to_string())),
+    }
+}
+
+// 宏的参数不求值, 宏体在定义宏的作用域中求值, 结果为展开后的表达式
+fn expand_macro(mac: &RispLambda, arg_forms: &RispList, ctx: &mut EvalContext) -> Result<RispExp, RispError> {
+    let env = mac.env.extend(bind_params(mac, arg_forms.to_vec())?);
+    eval(&mac.body_exp, &env, ctx)
+}
+
+fn macro_call(exp: &RispExp, env: &RispEnv) -> Option<(RispLambda, RispList)> {
+    let RispExp::List(list) = exp else {
+        return None;
+    };
+    match list.first() {
+        Some(RispExp::Symbol(s)) => match env.get(s) {
+            Some(RispExp::Macro(mac)) => Some((mac, list.rest())),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// 表头是宏时反复展开, 直到不再是宏调用
+pub fn macroexpand(exp: &RispExp, env: &RispEnv, ctx: &mut EvalContext) -> Result<RispExp, RispError> {
+    let mut exp = exp.clone();
+    while let Some((mac, args)) = macro_call(&exp, env) {
+        exp = expand_macro(&mac, &args, ctx)?;
+    }
+    Ok(exp)
+}
+
+// 求值前展开表达式中所有已定义的宏, quote和quasiquote中的内容不展开;
+// 执行过程中才定义的宏在调用时展开
+pub fn macroexpand_all(exp: &RispExp, env: &RispEnv, ctx: &mut EvalContext) -> Result<RispExp, RispError> {
+    expand_nested(exp, env, ctx, 0)
+}
+
+// depth为外层表达式的层数, 宏展开的结果也可能嵌套过深
+fn expand_nested(exp: &RispExp, env: &RispEnv, ctx: &mut EvalContext, depth: usize) -> Result<RispExp, RispError> {
+    if depth >= MAX_NESTING {
+        return Err(nesting_error());
+    }
+    let exp = macroexpand(exp, env, ctx)?;
+    let depth = depth + 1;
+    match &exp {
+        RispExp::List(list) => match list.first() {
+            Some(RispExp::Symbol(s)) if s == "quote" || s == "quasiquote" => Ok(exp),
+            _ => list
+                .iter()
+                .map(|x| expand_nested(x, env, ctx, depth))
+                .collect::<Result<_, _>>()
+                .map(RispExp::List),
+        },
+        RispExp::Vector(v) => v
+            .iter()
+            .map(|x| expand_nested(x, env, ctx, depth))
+            .collect::<Result<_, _>>()
+            .map(RispExp::Vector),
+        RispExp::Set(s) => s
+            .iter()
+            .map(|x| expand_nested(x, env, ctx, depth))
+            .collect::<Result<_, _>>()
+            .map(RispExp::Set),
+        RispExp::Map(m) => m
+            .iter()
+            .map(|(k, v)| Ok((expand_nested(k, env, ctx, depth)?, expand_nested(v, env, ctx, depth)?)))
+            .collect::<Result<_, _>>()
+            .map(RispExp::Map),
+        _ => Ok(exp),
+    }
+}
+
+// (load "lib.risp") 在当前作用域依次执行文件中的表达式, 返回最后一个的值
+fn eval_load_args(arg_forms: &RispList, env: &RispEnv, ctx: &mut EvalContext) -> Result<RispExp, RispError> {
+    ctx.require(Capabilities::FILE, "load")?;
+    let Some(path_form) = only_form(arg_forms) else {
+        return Err(RispError::Reason("load expected one file path".to_string()));
+    };
+    match eval(path_form, env, ctx)? {
+        RispExp::Str(path) => load_file(Path::new(&path), env, ctx),
+        exp => Err(RispError::Reason(format!("load expected a string, got {}", exp))),
+    }
+}
+
+fn load_file(path: &Path, env: &RispEnv, ctx: &mut EvalContext) -> Result<RispExp, RispError> {
+    load_file_with(path, |exp| {
+        let exp = macroexpand_all(exp, env, ctx)?;
+        eval(&exp, env, ctx)
+    })
+}
+
+// 用eval_fn依次执行文件中的表达式, 文件中的错误按文件内容定位
+pub(crate) fn load_file_with<F>(path: &Path, mut eval_fn: F) -> Result<RispExp, RispError>
+where
+    F: FnMut(&RispExp) -> Result<RispExp, RispError>,
+{
+    let src = fs::read_to_string(path)
+        .map_err(|e| RispError::Reason(format!("failed to read {}: {}", path.display(), e)))?;
+    let file = path.display().to_string();
+    let mut res = RispExp::Nil;
+    for form in read(&src).map_err(|e| e.in_file(&file))? {
+        res = eval_fn(&form.exp).map_err(|e| form.locate(e, &src).in_file(&file))?;
+    }
+    Ok(res)
+}
+
+// (module str (defn join ...)) 在新的作用域中依次执行, 外层为最外层作用域, 返回模块名;
+// 模块中的定义用str/join访问, 不同模块中的同名定义互不影响
+fn eval_module_args(arg_forms: &RispList, env: &RispEnv, ctx: &mut EvalContext) -> Result<RispExp, RispError> {
+    let name = match arg_forms.first() {
+        Some(RispExp::Symbol(name)) => name,
+        _ => return Err(RispError::Reason("module expected a name".to_string())),
+    };
+    let module = env.root().extend(HashMap::new());
+    // 先注册, 循环import时可以访问已经执行的部分
+    env.add_module(name, module.clone());
+    for form in arg_forms.iter().skip(1) {
+        if let Err(e) = eval(form, &module, ctx) {
+            env.remove_module(name);
+            return Err(e);
+        }
+    }
+    Ok(RispExp::Symbol(name.clone()))
+}
+
+// (import str) 未定义的模块在搜索路径中查找str.risp并执行, 已定义的不重复加载;
+// (import str [join]) 同时在当前作用域中定义join
+fn eval_import_args(arg_forms: &RispList, env: &RispEnv, ctx: &mut EvalContext) -> Result<RispExp, RispError> {
+    let mut forms = arg_forms.iter();
+    let (name, names) = match (forms.next(), forms.next(), forms.next()) {
+        (Some(RispExp::Symbol(name)), None, None) => (name, vec![]),
+        (Some(RispExp::Symbol(name)), Some(RispExp::Vector(v)), None) => (name, v.to_vec()),
+        (Some(RispExp::Symbol(name)), Some(RispExp::List(l)), None) => (name, l.to_vec()),
+        _ => {
+            return Err(RispError::Reason(
+                "import expected a module name and an optional list of names".to_string(),
+            ))
+        }
+    };
+    let module = match env.module(name) {
+        Some(module) => module,
+        None => load_module(name, env, ctx)?,
+    };
+    for exp in names {
+        let RispExp::Symbol(k) = exp else {
+            return Err(RispError::Reason(format!("import expected symbols, got {}", exp)));
+        };
+        let value = module
+            .get(&format!("{}/{}", name, k))
+            .ok_or_else(|| RispError::Reason(format!("module {} has no definition {}", name, k)))?;
+        env.insert(&k, value);
+    }
+    Ok(RispExp::Symbol(name.clone()))
+}
+
+// util/str -> util/str.risp, 文件应当定义同名的模块
+fn load_module(name: &str, env: &RispEnv, ctx: &mut EvalContext) -> Result<RispEnv, RispError> {
+    ctx.require(Capabilities::FILE, "import")?;
+    let file = format!("{}.risp", name);
+    let path = ctx.module_path.iter().map(|dir| dir.join(&file)).find(|p| p.is_file());
+    let Some(path) = path else {
+        let dirs: Vec<String> = ctx.module_path.iter().map(|dir| dir.display().to_string()).collect();
+        return Err(RispError::Reason(format!("module {} not found in {}", name, dirs.join(", "))));
+    };
+    load_file(&path, &env.root(), ctx)?;
+    env.module(name)
+        .ok_or_else(|| RispError::Reason(format!("{} did not define module {}", path.display(), name)))
+}
+
+fn parse_list_of_symbol_strings(form: Rc<RispExp>) -> Result<Vec<String>, RispError> {
+    let list = match form.as_ref() {
+        RispExp::List(s) => Ok(s.to_vec()),
+        RispExp::Vector(v) => Ok(v.to_vec()),
+        _ => Err(RispError::Reason("expected args form to be a list or vector".to_string())),
+    }?;
+    list.iter()
+        .map(|x| match x {
+            RispExp::Symbol(s) => Ok(s.clone()),
+            _ => Err(RispError::Reason("expected symbols in the argument list".to_string())),
+        })
+        .collect()
+}
+
+// 参数在调用处求值, 新作用域的外层是lambda定义时的作用域
+fn env_for_lambda(
+    lambda: &RispLambda,
+    arg_forms: &RispList,
+    env: &RispEnv,
+    ctx: &mut EvalContext,
+) -> Result<RispEnv, RispError> {
+    let vs = eval_forms(arg_forms, env, ctx)?;
+    Ok(lambda.env.extend(bind_params(lambda, vs)?))
+}
+
+// 以已求值的实参调用lambda, 用于虚拟机调用树遍历求值器中定义的函数
+pub(crate) fn apply_lambda(
+    lambda: &RispLambda,
+    args: Vec<RispExp>,
+    ctx: &mut EvalContext,
+) -> Result<RispExp, RispError> {
+    let env = lambda.env.extend(bind_params(lambda, args)?);
+    ctx.stack.push(lambda.name.clone());
+    let res = eval(&lambda.body_exp, &env, ctx);
+    ctx.stack.pop();
+    res
+}
+
+// 按参数名绑定实参, &之后的名称绑定剩余参数组成的list
+fn bind_params(lambda: &RispLambda, args: Vec<RispExp>) -> Result<HashMap<String, RispExp>, RispError> {
+    let mut ks = parse_list_of_symbol_strings(lambda.params_exp.clone())?;
+    let rest = match ks.iter().position(|k| k == "&") {
+        Some(i) if i + 2 == ks.len() => ks.pop().map(|k| (i, k)),
+        Some(_) => return Err(RispError::Reason("expected one symbol after &".to_string())),
+        None => None,
+    };
+    let mut args = args;
+    let mut data = HashMap::new();
+    if let Some((i, name)) = rest {
+        if args.len() < i {
+            return Err(RispError::Reason(format!(
+                "expected at least {} arguments, got {}",
+                i,
+                args.len()
+            )));
+        }
+        data.insert(name, RispExp::List(args.split_off(i).into()));
+        ks.truncate(i);
+    } else if ks.len() != args.len() {
+        return Err(RispError::Reason(format!(
+            "expected {} arguments, got {}",
+            ks.len(),
+            args.len()
+        )));
+    }
+    data.extend(ks.into_iter().zip(args));
+    Ok(data)
+}
+
+type SpecialForm = fn(&RispList, &RispEnv, &mut EvalContext) -> Result<Tail, RispError>;
+
+// if begin def set! fn load quote quasiquote defmacro macroexpand
+// 返回函数指针而不是直接调用, 递归求值时每层只有对应特殊形式的栈帧
+fn special_form(exp: &RispExp) -> Option<SpecialForm> {
+    let RispExp::Symbol(s) = exp else {
+        return None;
+    };
+    let f: SpecialForm = match s.as_ref() {
+        "if" => eval_if_args,
+        "begin" => eval_begin_args,
+        "def" => |args, env, ctx| eval_def_args(args, env, ctx).map(Tail::Value),
+        "set!" => |args, env, ctx| eval_set_args(args, env, ctx).map(Tail::Value),
+        "fn" => |args, env, _| eval_lambda_args(args, env).map(Tail::Value),
+        "load" => |args, env, ctx| eval_load_args(args, env, ctx).map(Tail::Value),
+        "quote" => |args, _, _| eval_quote_args(args).map(Tail::Value),
+        "quasiquote" => |args, env, ctx| eval_quasiquote_args(args, env, ctx).map(Tail::Value),
+        "defmacro" => |args, env, _| eval_defmacro_args(args, env).map(Tail::Value),
+        "macroexpand" => |args, env, ctx| eval_macroexpand_args(args, env, ctx).map(Tail::Value),
+        "try" => |args, env, ctx| eval_try_args(args, env, ctx).map(Tail::Value),
+        "module" => |args, env, ctx| eval_module_args(args, env, ctx).map(Tail::Value),
+        "import" => |args, env, ctx| eval_import_args(args, env, ctx).map(Tail::Value),
+        _ => return None,
+    };
+    Some(f)
+}
+
+// 执行list中所有计算
+// + 1 2 3
+fn eval_forms(arg_forms: &RispList, env: &RispEnv, ctx: &mut EvalContext) -> Result<Vec<RispExp>, RispError> {
+    arg_forms.iter().map(|x| eval(x, env, ctx)).collect()
+}
+
+// [a (+ 1 2)] -> [1 3], 字面量中的元素依次求值
+fn eval_collection(exp: &RispExp, env: &RispEnv, ctx: &mut EvalContext) -> Result<RispExp, RispError> {
+    match exp {
+        RispExp::Vector(v) => v.iter().map(|x| eval(x, env, ctx)).collect::<Result<_, _>>().map(RispExp::Vector),
+        RispExp::Set(s) => s.iter().map(|x| eval(x, env, ctx)).collect::<Result<_, _>>().map(RispExp::Set),
+        RispExp::Map(m) => m
+            .iter()
+            .map(|(k, v)| Ok((eval(k, env, ctx)?, eval(v, env, ctx)?)))
+            .collect::<Result<_, _>>()
+            .map(RispExp::Map),
+        _ => Ok(exp.clone()),
+    }
+}
+
+// 执行计算, 超过最大递归深度时返回错误
+pub fn eval(exp: &RispExp, env: &RispEnv, ctx: &mut EvalContext) -> Result<RispExp, RispError> {
+    if ctx.depth >= ctx.max_depth {
+        return Err(ctx.depth_error());
+    }
+    if !ctx.tick() {
+        return Err(ctx.limit_error());
+    }
+    ctx.depth += 1;
+    let base = std::mem::replace(&mut ctx.base, ctx.stack.len());
+    let res = grow_stack(|| eval_loop(exp, env, ctx));
+    if res.is_err() {
+        record_error_stack(ctx);
+    }
+    ctx.stack.truncate(ctx.base);
+    ctx.base = base;
+    ctx.depth -= 1;
+    res
+}
+
+// 每次进入会占用rust调用栈的递归都经过这里, 栈不够时切换到新的栈段
+pub(crate) fn grow_stack<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(RED_ZONE, STACK_SEGMENT, f)
+}
+
+// 只记录最内层出错时的调用栈, 错误向外传递时不覆盖
+pub(crate) fn record_error_stack(ctx: &mut EvalContext) {
+    if ctx.error_stack.is_none() {
+        ctx.error_stack = Some(ctx.stack.clone());
+    }
+}
+
+// 匿名函数(包括let展开的函数)和虚拟机中try的各部分不出现在调用栈中
+pub(crate) fn is_anonymous(name: &str) -> bool {
+    name == "fn" || name.starts_with('<')
+}
+
+// 尾调用替换当前eval循环中已调用的函数
+fn enter_call(ctx: &mut EvalContext, name: &Rc<String>) {
+    if is_anonymous(name) {
+        return;
+    }
+    if ctx.stack.len() > ctx.base {
+        ctx.stack.pop();
+    }
+    ctx.stack.push(name.clone());
+}
+
+fn eval_loop(exp: &RispExp, env: &RispEnv, ctx: &mut EvalContext) -> Result<RispExp, RispError> {
+    let mut next = eval_step(exp, env, ctx)?;
+    loop {
+        match next {
+            Tail::Value(v) => return Ok(v),
+            Tail::Eval(exp, env) => {
+                next = eval_step(&exp, &env, ctx)?
+            }
+        }
+    }
+}
+
+fn eval_step(exp: &RispExp, env: &RispEnv, ctx: &mut EvalContext) -> Result<Tail, RispError> {
+    match exp {
+        RispExp::Nil
+        | RispExp::Boolean(_)
+        | RispExp::Int(_)
+        | RispExp::BigInt(_)
+        | RispExp::Number(_)
+        | RispExp::Str(_)
+        | RispExp::Char(_)
+        | RispExp::Keyword(_) => Ok(Tail::Value(exp.clone())),
+        RispExp::Vector(_) | RispExp::Map(_) | RispExp::Set(_) => eval_collection(exp, env, ctx).map(Tail::Value),
+        RispExp::Symbol(op) => env
+            .get(op)
+            .map(Tail::Value)
+            .ok_or_else(|| RispError::Reason(format!("unexpected symbol k='{}'", op)).in_form(exp)),
+        RispExp::Func(_) => Err(RispError::Reason("unexpected function".to_string())),
+        RispExp::Lambda(_) | RispExp::Macro(_) | RispExp::Closure(_) => {
+            Err(RispError::Reason("unexpected form".to_string()))
+        }
+        RispExp::List(list) => eval_list(list, env, ctx).map_err(|e| e.in_form(exp)),
+    }
+}
+
+// 调用参数的求值分别放在单独的函数中, 避免eval_list的栈帧过大
+fn call_func(f: &RispFunc, arg_forms: &RispList, env: &RispEnv, ctx: &mut EvalContext) -> Result<Tail, RispError> {
+    let args = eval_forms(arg_forms, env, ctx)?;
+    let res = f.call(&args)?;
+    ctx.check_value(&res)?;
+    Ok(Tail::Value(res))
+}
+
+fn call_closure(c: &Closure, arg_forms: &RispList, env: &RispEnv, ctx: &mut EvalContext) -> Result<Tail, RispError> {
+    let args = eval_forms(arg_forms, env, ctx)?;
+    vm::call_closure(c, args, ctx).map(Tail::Value)
+}
+
+fn eval_list(list: &RispList, env: &RispEnv, ctx: &mut EvalContext) -> Result<Tail, RispError> {
+    // + 1 2
+    let first_form = list
+        .first()
+        .ok_or(RispError::Reason("expected a non empty list".to_string()))?;
+    let arg_forms = &list.rest();
+    match special_form(first_form) {
+        Some(f) => f(arg_forms, env, ctx),
+        None => {
+            let first_eval = eval(first_form, env, ctx)?;
+            match first_eval {
+                RispExp::Func(f) => call_func(&f, arg_forms, env, ctx),
+                RispExp::Lambda(lambda) => {
+                    let new_env = env_for_lambda(&lambda, arg_forms, env, ctx)?;
+                    enter_call(ctx, &lambda.name);
+                    Ok(Tail::Eval(lambda.body_exp.as_ref().clone(), new_env))
+                }
+                RispExp::Closure(c) => call_closure(&c, arg_forms, env, ctx),
+                // 执行中定义的宏在这里展开, 展开的结果在当前作用域中求值
+                RispExp::Macro(mac) => Ok(Tail::Eval(expand_macro(&mac, arg_forms, ctx)?, env.clone())),
+                _ => Err(RispError::Reason("first form must be a function".to_string())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::init_env;
+
+    // 不加载prelude, 依次展开并求值src中的表达式
+    fn run(src: &str, ctx: &mut EvalContext) -> Result<RispExp, RispError> {
+        let env = init_env();
+        let mut res = RispExp::Nil;
+        for form in read(src)? {
+            res = eval(&macroexpand_all(&form.exp, &env, ctx)?, &env, ctx)?;
+        }
+        Ok(res)
+    }
+
+    #[test]
+    fn closures_should_share_captured_scopes() {
+        let src = "
+            (def counter (fn () (begin (def n 0) (fn () (set! n (+ n 1))))))
+            (def a (counter))
+            (def b (counter))
+            (a) (a) (b)
+            (list (a) (b) (begin (def n 10) (a)))";
+        let res = run(src, &mut EvalContext::default()).unwrap();
+        assert_eq!(res.to_string(), "(3 2 4)");
+        // set!不能定义新的名字
+        let err = run("((fn () (set! undefined 1)))", &mut EvalContext::default()).unwrap_err();
+        assert_eq!(err.message(), "set! undefined symbol k='undefined'");
+    }
+
+    #[test]
+    fn tail_calls_should_not_count_towards_max_depth() {
+        let mut ctx = EvalContext::new(100);
+        let src = "
+            (def even? (fn (n) (if (= n 0) true (odd? (- n 1)))))
+            (def odd? (fn (n) (if (= n 0) false (begin nil (even? (- n 1))))))
+            (even? 10001)";
+        assert_eq!(run(src, &mut ctx).unwrap(), RispExp::Boolean(false));
+        let err = run("(def sum (fn (n) (if (= n 0) 0 (+ n (sum (- n 1)))))) (sum 1000)", &mut ctx).unwrap_err();
+        assert_eq!(err.message(), "maximum recursion depth 100 exceeded");
+        assert_eq!(ctx.depth(), 0);
+    }
+
+    #[test]
+    fn quasiquote_should_unquote_and_splice_into_collections() {
+        let mut ctx = EvalContext::default();
+        let src = "(def x 1) (def xs '(2 3)) `(a ~x ~@xs [~@xs ~x] {:k ~x} #{~@xs} ~@nil (b ~@(cdr xs)))";
+        assert_eq!(run(src, &mut ctx).unwrap().to_string(), "(a 1 2 3 [2 3 1] {:k 1} #{2 3} (b 3))");
+        let err = run("`(~@1)", &mut ctx).unwrap_err();
+        assert_eq!(err.message(), "unquote-splicing expected a list, got 1");
+    }
+
+    #[test]
+    fn macros_should_receive_unevaluated_forms() {
+        let mut ctx = EvalContext::default();
+        let src = "
+            (defmacro unless (test & body) `(if ~test nil (begin ~@body)))
+            (def n 0)
+            (unless true (set! n 1))
+            (unless false (set! n (+ n 2)))
+            (defmacro twice (x) `(unless false ~x ~x))
+            (list n (macroexpand '(unless (= n 2) :a)) (macroexpand '(twice (f))))";
+        // 展开结果的表头仍是宏时继续展开
+        let expected = "(2 (if (= n 2) nil (begin :a)) (if false nil (begin (f) (f))))";
+        assert_eq!(run(src, &mut ctx).unwrap().to_string(), expected);
+    }
+
+    #[test]
+    fn macroexpand_all_should_limit_nesting() {
+        let mut exp = RispExp::Int(1);
+        for _ in 0..MAX_NESTING {
+            exp = RispExp::Vector(vec![exp].into());
+        }
+        let (env, mut ctx) = (init_env(), EvalContext::default());
+        assert_eq!(macroexpand_all(&exp, &env, &mut ctx).unwrap_err(), nesting_error());
+    }
+}
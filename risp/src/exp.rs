@@ -0,0 +1,426 @@
+use std::{
+    fmt,
+    hash::{DefaultHasher, Hash, Hasher},
+    rc::Rc,
+};
+
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+
+use crate::{gc::Gc, num::Num, vm::Closure, RispEnv, RispError, RispList, RispMap, RispSet, RispVector};
+
+#[derive(Debug, Clone)]
+pub enum RispExp {
+    Nil,
+    Boolean(bool),
+    Int(i64),
+    BigInt(BigInt),
+    // 浮点数
+    Number(f64),
+    Str(String),
+    Char(char),
+    Symbol(String),
+    // :a, 求值结果为自身, 常用作映射的键
+    Keyword(String),
+    List(RispList),
+    Vector(RispVector),
+    Map(RispMap),
+    Set(RispSet),
+    Func(RispFunc),
+    Lambda(RispLambda),
+    // 宏, 参数不求值, 调用时展开为新的表达式
+    Macro(RispLambda),
+    // 字节码虚拟机中的闭包
+    Closure(Gc<Closure>),
+}
+
+// 闭包, env为定义时的作用域
+#[derive(Debug, Clone)]
+pub struct RispLambda {
+    // 错误对象的调用栈中显示的名称, 匿名函数为fn; 使用Rc<String>使RispExp不变大
+    pub name: Rc<String>,
+    pub params_exp: Rc<RispExp>,
+    pub body_exp: Rc<RispExp>,
+    pub env: RispEnv,
+}
+
+type NativeFn = dyn Fn(&[RispExp]) -> Result<RispExp, RispError>;
+
+// 内置函数或宿主程序注册的rust函数
+#[derive(Clone)]
+pub struct RispFunc {
+    name: String,
+    f: Rc<NativeFn>,
+}
+
+impl RispFunc {
+    pub fn new<F>(name: &str, f: F) -> Self
+    where
+        F: Fn(&[RispExp]) -> Result<RispExp, RispError> + 'static,
+    {
+        Self { name: name.to_string(), f: Rc::new(f) }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn call(&self, args: &[RispExp]) -> Result<RispExp, RispError> {
+        (self.f)(args)
+    }
+}
+
+impl fmt::Debug for RispFunc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RispFunc({})", self.name)
+    }
+}
+
+impl RispExp {
+    // nil和false为假, 其他都为真
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, RispExp::Nil | RispExp::Boolean(false))
+    }
+
+    // str使用的文本, 字符串和字符不带引号, nil为空
+    pub fn to_str_value(&self) -> String {
+        match self {
+            RispExp::Nil => String::new(),
+            RispExp::Str(s) => s.clone(),
+            RispExp::Char(c) => c.to_string(),
+            _ => self.to_string(),
+        }
+    }
+
+    // 访问直接引用的托管对象, lambda的参数和函数体是不会形成循环的代码, 不需要访问
+    pub(crate) fn trace(&self, visit: &mut dyn FnMut(usize)) {
+        match self {
+            RispExp::List(list) => list.trace(visit),
+            RispExp::Vector(v) => v.trace(visit),
+            RispExp::Map(m) => m.trace(visit),
+            RispExp::Set(s) => s.trace(visit),
+            RispExp::Lambda(lambda) | RispExp::Macro(lambda) => visit(lambda.env.id()),
+            RispExp::Closure(c) => visit(c.id()),
+            _ => {}
+        }
+    }
+}
+
+// 数值按值比较(1与1.0相等), 函数按引用比较
+impl PartialEq for RispExp {
+    fn eq(&self, other: &Self) -> bool {
+        use RispExp::*;
+        match (self, other) {
+            (Int(_) | BigInt(_) | Number(_), Int(_) | BigInt(_) | Number(_)) => {
+                matches!(
+                    (Num::from_exp(self), Num::from_exp(other)),
+                    (Ok(a), Ok(b)) if a.compare(&b) == Some(std::cmp::Ordering::Equal)
+                )
+            }
+            (Nil, Nil) => true,
+            (Boolean(a), Boolean(b)) => a == b,
+            (Str(a), Str(b)) => a == b,
+            (Char(a), Char(b)) => a == b,
+            (Symbol(a), Symbol(b)) => a == b,
+            (Keyword(a), Keyword(b)) => a == b,
+            (List(a), List(b)) => a == b,
+            (Vector(a), Vector(b)) => a == b,
+            (Map(a), Map(b)) => a == b,
+            (Set(a), Set(b)) => a == b,
+            (Func(a), Func(b)) => Rc::ptr_eq(&a.f, &b.f),
+            (Closure(a), Closure(b)) => Gc::ptr_eq(a, b),
+            (Lambda(a), Lambda(b)) | (Macro(a), Macro(b)) => {
+                Rc::ptr_eq(&a.body_exp, &b.body_exp) && Rc::ptr_eq(&a.params_exp, &b.params_exp)
+            }
+            _ => false,
+        }
+    }
+}
+
+// 用作映射的键和集合的元素; NaN与自身不相等, 作为键时查找不到
+impl Eq for RispExp {}
+
+// 与=一致: 数值按f64的值哈希, 映射和集合与顺序无关, 函数只按类型
+impl Hash for RispExp {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        use RispExp::*;
+        match self {
+            Int(n) => hash_number(*n as f64, state),
+            BigInt(n) => hash_number(n.to_f64().unwrap_or(f64::NAN), state),
+            Number(n) => hash_number(*n, state),
+            Nil => 1u8.hash(state),
+            Boolean(b) => (2u8, b).hash(state),
+            Str(s) => (3u8, s).hash(state),
+            Char(c) => (4u8, c).hash(state),
+            Symbol(s) => (5u8, s).hash(state),
+            Keyword(s) => (6u8, s).hash(state),
+            List(list) => {
+                7u8.hash(state);
+                list.iter().for_each(|x| x.hash(state));
+            }
+            Vector(v) => {
+                8u8.hash(state);
+                v.iter().for_each(|x| x.hash(state));
+            }
+            Map(m) => (9u8, hash_unordered(m.iter())).hash(state),
+            Set(s) => (10u8, hash_unordered(s.iter())).hash(state),
+            Func(_) | Lambda(_) | Macro(_) | Closure(_) => 11u8.hash(state),
+        }
+    }
+}
+
+fn hash_number<H: Hasher>(n: f64, state: &mut H) {
+    // -0.0 == 0.0
+    let n = if n == 0.0 { 0.0 } else { n };
+    (0u8, n.to_bits()).hash(state);
+}
+
+// 各元素的哈希相加, 与顺序无关
+fn hash_unordered<T: Hash>(items: impl Iterator<Item = T>) -> u64 {
+    items.fold(0u64, |sum, x| {
+        let mut hasher = DefaultHasher::new();
+        x.hash(&mut hasher);
+        sum.wrapping_add(hasher.finish())
+    })
+}
+
+// 字符名称, 与读取时一致
+pub(crate) const CHAR_NAMES: [(&str, char); 4] = [("space", ' '), ("newline", '\n'), ("tab", '\t'), ("return", '\r')];
+
+fn escape_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn join(items: impl Iterator<Item = String>) -> String {
+    items.collect::<Vec<_>>().join(" ")
+}
+
+// 输出的文本可以被读取为相等的值, 函数和宏除外
+impl fmt::Display for RispExp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let str = match self {
+            RispExp::Nil => "nil".to_string(),
+            RispExp::Boolean(a) => a.to_string(),
+            RispExp::Int(n) => n.to_string(),
+            RispExp::BigInt(n) => n.to_string(),
+            // 整数值的浮点数保留小数点, 与整数区分
+            RispExp::Number(n) if n.is_finite() && n.fract() == 0.0 => format!("{:.1}", n),
+            RispExp::Number(n) if n.is_nan() => "##NaN".to_string(),
+            RispExp::Number(n) if n.is_infinite() => if *n > 0.0 { "##Inf" } else { "##-Inf" }.to_string(),
+            RispExp::Number(n) => n.to_string(),
+            RispExp::Str(s) => escape_str(s),
+            RispExp::Char(c) => match CHAR_NAMES.iter().find(|(_, v)| v == c) {
+                Some((name, _)) => format!("\\{}", name),
+                None => format!("\\{}", c),
+            },
+            RispExp::Symbol(s) => s.clone(),
+            RispExp::Keyword(s) => format!(":{}", s),
+            RispExp::List(list) => format!("({})", join(list.iter().map(|x| x.to_string()))),
+            RispExp::Vector(v) => format!("[{}]", join(v.iter().map(|x| x.to_string()))),
+            RispExp::Map(m) => format!("{{{}}}", join(m.iter().map(|(k, v)| format!("{} {}", k, v)))),
+            RispExp::Set(s) => format!("#{{{}}}", join(s.iter().map(|x| x.to_string()))),
+            RispExp::Func(_) => "Function {}".to_string(),
+            RispExp::Lambda(_) => "Lambda {}".to_string(),
+            RispExp::Macro(_) => "Macro {}".to_string(),
+            RispExp::Closure(_) => "Closure {}".to_string(),
+        };
+        write!(f, "{}", str)
+    }
+}
+
+// rust类型与RispExp互相转换, 用于向环境中注册值和读取计算结果
+impl From<bool> for RispExp {
+    fn from(b: bool) -> Self {
+        RispExp::Boolean(b)
+    }
+}
+
+impl From<f64> for RispExp {
+    fn from(n: f64) -> Self {
+        RispExp::Number(n)
+    }
+}
+
+impl From<i32> for RispExp {
+    fn from(n: i32) -> Self {
+        RispExp::Int(n as i64)
+    }
+}
+
+impl From<i64> for RispExp {
+    fn from(n: i64) -> Self {
+        RispExp::Int(n)
+    }
+}
+
+impl From<BigInt> for RispExp {
+    fn from(n: BigInt) -> Self {
+        Num::Big(n).into_exp()
+    }
+}
+
+impl From<&str> for RispExp {
+    fn from(s: &str) -> Self {
+        RispExp::Str(s.to_string())
+    }
+}
+
+impl From<String> for RispExp {
+    fn from(s: String) -> Self {
+        RispExp::Str(s)
+    }
+}
+
+impl From<char> for RispExp {
+    fn from(c: char) -> Self {
+        RispExp::Char(c)
+    }
+}
+
+// None转为nil
+impl<T: Into<RispExp>> From<Option<T>> for RispExp {
+    fn from(v: Option<T>) -> Self {
+        v.map_or(RispExp::Nil, Into::into)
+    }
+}
+
+impl<T: Into<RispExp>> From<Vec<T>> for RispExp {
+    fn from(list: Vec<T>) -> Self {
+        RispExp::List(list.into_iter().map(Into::into).collect())
+    }
+}
+
+impl From<RispFunc> for RispExp {
+    fn from(f: RispFunc) -> Self {
+        RispExp::Func(f)
+    }
+}
+
+impl TryFrom<RispExp> for bool {
+    type Error = RispError;
+
+    fn try_from(exp: RispExp) -> Result<Self, Self::Error> {
+        match exp {
+            RispExp::Boolean(b) => Ok(b),
+            _ => Err(RispError::Reason(format!("expected a boolean, got {}", exp))),
+        }
+    }
+}
+
+// 整数也可以转为浮点数
+impl TryFrom<RispExp> for f64 {
+    type Error = RispError;
+
+    fn try_from(exp: RispExp) -> Result<Self, Self::Error> {
+        match exp {
+            RispExp::Number(_) | RispExp::Int(_) | RispExp::BigInt(_) => Ok(Num::from_exp(&exp)?.to_f64()),
+            _ => Err(RispError::Reason(format!("expected a number, got {}", exp))),
+        }
+    }
+}
+
+impl TryFrom<RispExp> for i64 {
+    type Error = RispError;
+
+    fn try_from(exp: RispExp) -> Result<Self, Self::Error> {
+        match exp {
+            RispExp::Int(n) => Ok(n),
+            _ => Err(RispError::Reason(format!("expected an integer, got {}", exp))),
+        }
+    }
+}
+
+impl TryFrom<RispExp> for BigInt {
+    type Error = RispError;
+
+    fn try_from(exp: RispExp) -> Result<Self, Self::Error> {
+        match exp {
+            RispExp::Int(n) => Ok(BigInt::from(n)),
+            RispExp::BigInt(n) => Ok(n),
+            _ => Err(RispError::Reason(format!("expected an integer, got {}", exp))),
+        }
+    }
+}
+
+impl TryFrom<RispExp> for String {
+    type Error = RispError;
+
+    fn try_from(exp: RispExp) -> Result<Self, Self::Error> {
+        match exp {
+            RispExp::Str(s) => Ok(s),
+            _ => Err(RispError::Reason(format!("expected a string, got {}", exp))),
+        }
+    }
+}
+
+impl TryFrom<RispExp> for char {
+    type Error = RispError;
+
+    fn try_from(exp: RispExp) -> Result<Self, Self::Error> {
+        match exp {
+            RispExp::Char(c) => Ok(c),
+            _ => Err(RispError::Reason(format!("expected a char, got {}", exp))),
+        }
+    }
+}
+
+impl<T> TryFrom<RispExp> for Vec<T>
+where
+    T: TryFrom<RispExp, Error = RispError>,
+{
+    type Error = RispError;
+
+    fn try_from(exp: RispExp) -> Result<Self, Self::Error> {
+        match exp {
+            RispExp::List(list) => list.iter().cloned().map(T::try_from).collect(),
+            _ => Err(RispError::Reason(format!("expected a list, got {}", exp))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::{BuildHasher, RandomState};
+
+    use super::*;
+    use crate::read;
+
+    #[test]
+    fn equal_values_should_hash_the_same() {
+        let state = RandomState::new();
+        let pairs = [
+            ("1", "1.0"),
+            ("0.0", "-0.0"),
+            ("9223372036854775808", "9223372036854775808.0"),
+            ("{:a 1 :b #{1 2}}", "{:b #{2 1} :a 1.0}"),
+            ("(1 [2])", "(1.0 [2.0])"),
+        ];
+        for (a, b) in pairs {
+            let (a, b) = (&read(a).unwrap()[0].exp, &read(b).unwrap()[0].exp);
+            assert_eq!(a, b);
+            assert_eq!(state.hash_one(a), state.hash_one(b), "{} {}", a, b);
+        }
+        assert_ne!(RispExp::Str("a".to_string()), RispExp::Symbol("a".to_string()));
+    }
+
+    #[test]
+    fn printed_values_should_read_back() {
+        let src = r#"[nil true -1 9223372036854775808 1.0 0.25 ##-Inf "a\"\n\\b" \a \space \newline :k sym (1 [2]) {"k" #{}}]"#;
+        let exp = &read(src).unwrap()[0].exp;
+        assert_eq!(exp.to_string(), src);
+        assert_eq!(&read(&exp.to_string()).unwrap()[0].exp, exp);
+    }
+}
@@ -0,0 +1,296 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    ops::Deref,
+    rc::{Rc, Weak},
+};
+
+// 自动回收的最小分配次数间隔
+const MIN_COLLECT_INTERVAL: usize = 100_000;
+
+// 托管对象, trace访问直接引用的托管对象的id
+pub trait Trace {
+    fn trace(&self, visit: &mut dyn FnMut(usize));
+
+    // 不可达时清空内容, 打断循环引用后由Rc释放
+    fn clear(&self) {}
+}
+
+// 托管堆中的对象: cons单元、向量/映射/集合、作用域、虚拟机的调用帧和闭包
+pub struct Gc<T: Trace + 'static>(Rc<GcBox<T>>);
+
+// 释放时减少存活对象计数
+struct GcBox<T> {
+    value: T,
+    _live: Live,
+}
+
+struct Live;
+
+impl Live {
+    fn new() -> Self {
+        LIVE.with(|live| live.set(live.get() + 1));
+        Live
+    }
+}
+
+impl Drop for Live {
+    fn drop(&mut self) {
+        LIVE.with(|live| live.set(live.get() - 1));
+    }
+}
+
+impl<T: Trace> Trace for GcBox<T> {
+    fn trace(&self, visit: &mut dyn FnMut(usize)) {
+        self.value.trace(visit);
+    }
+
+    fn clear(&self) {
+        self.value.clear();
+    }
+}
+
+impl<T: Trace + 'static> Gc<T> {
+    pub fn new(value: T) -> Self {
+        let rc = Rc::new(GcBox { value, _live: Live::new() });
+        let weak: Weak<dyn Trace> = Rc::downgrade(&rc) as Weak<dyn Trace>;
+        HEAP.with(|heap| heap.borrow_mut().register(weak));
+        maybe_collect();
+        Self(rc)
+    }
+
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        Rc::ptr_eq(&a.0, &b.0)
+    }
+
+    // 对象地址, 用于trace
+    pub fn id(&self) -> usize {
+        Rc::as_ptr(&self.0) as *const () as usize
+    }
+
+    // 只有这一个引用时取出内容, 用于逐个释放长list
+    pub(crate) fn try_unwrap(this: Self) -> Result<T, Self> {
+        Rc::try_unwrap(this.0).map(|b| b.value).map_err(Self)
+    }
+}
+
+impl<T: Trace + 'static> Clone for Gc<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Trace + Default + 'static> Default for Gc<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: Trace + 'static> Deref for Gc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0.value
+    }
+}
+
+impl<T: Trace + fmt::Debug + 'static> fmt::Debug for Gc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.value.fmt(f)
+    }
+}
+
+// 分配和回收的统计
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GcStats {
+    // 累计分配的对象数
+    pub allocations: usize,
+    // 当前存活的对象数
+    pub live: usize,
+    pub collections: usize,
+    // 累计回收的循环引用对象数
+    pub freed: usize,
+}
+
+#[derive(Default)]
+struct Heap {
+    objects: Vec<Weak<dyn Trace>>,
+    stats: GcStats,
+    since_collect: usize,
+    // 上次回收后存活的对象数
+    survivors: usize,
+}
+
+impl Heap {
+    fn register(&mut self, weak: Weak<dyn Trace>) {
+        self.objects.push(weak);
+        self.stats.allocations += 1;
+        self.since_collect += 1;
+    }
+}
+
+thread_local! {
+    static HEAP: RefCell<Heap> = RefCell::new(Heap::default());
+    static LIVE: Cell<usize> = const { Cell::new(0) };
+}
+
+fn maybe_collect() {
+    let due = HEAP.with(|heap| {
+        let heap = heap.borrow();
+        heap.since_collect >= MIN_COLLECT_INTERVAL.max(heap.survivors)
+    });
+    if due {
+        collect();
+    }
+}
+
+pub fn gc_stats() -> GcStats {
+    let stats = HEAP.with(|heap| heap.borrow().stats);
+    GcStats { live: live_objects(), ..stats }
+}
+
+// 当前存活的托管对象数, 对象释放时立即更新
+pub fn live_objects() -> usize {
+    LIVE.with(Cell::get)
+}
+
+// 标记清除:
+// 1. 统计每个对象被其他托管对象引用的次数, Rc引用数更多的对象还被栈上或宿主程序引用, 作为根
+// 2. 从根开始标记可达的对象
+// 3. 不可达的对象只被循环引用持有, 清空它们的内容后由Rc释放
+// 栈上的值都计入Rc引用数, 所以可以在求值过程中的任意时刻回收; 无法trace的对象(正在修改的作用域)
+// 也只会让它引用的对象被当作根, 不会误回收
+pub fn collect() -> usize {
+    let weaks = HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.since_collect = 0;
+        std::mem::take(&mut heap.objects)
+    });
+    let objects: Vec<Rc<dyn Trace>> = weaks.iter().filter_map(Weak::upgrade).collect();
+    drop(weaks);
+    let index: HashMap<usize, usize> = objects
+        .iter()
+        .enumerate()
+        .map(|(i, obj)| (Rc::as_ptr(obj) as *const () as usize, i))
+        .collect();
+
+    let mut internal = vec![0usize; objects.len()];
+    for obj in &objects {
+        obj.trace(&mut |id| {
+            if let Some(&i) = index.get(&id) {
+                internal[i] += 1;
+            }
+        });
+    }
+    // upgrade时多了一个引用
+    let mut marked: Vec<bool> = objects
+        .iter()
+        .zip(&internal)
+        .map(|(obj, n)| Rc::strong_count(obj) - 1 > *n)
+        .collect();
+    let mut pending: Vec<usize> = (0..objects.len()).filter(|&i| marked[i]).collect();
+    while let Some(i) = pending.pop() {
+        objects[i].trace(&mut |id| {
+            if let Some(&j) = index.get(&id) {
+                if !marked[j] {
+                    marked[j] = true;
+                    pending.push(j);
+                }
+            }
+        });
+    }
+
+    let garbage: Vec<&Rc<dyn Trace>> = objects
+        .iter()
+        .zip(&marked)
+        .filter(|(_, m)| !**m)
+        .map(|(o, _)| o)
+        .collect();
+    let freed = garbage.len();
+    for obj in garbage {
+        obj.clear();
+    }
+    let live: Vec<Weak<dyn Trace>> = objects
+        .iter()
+        .zip(&marked)
+        .filter(|(_, m)| **m)
+        .map(|(o, _)| Rc::downgrade(o))
+        .collect();
+    drop(objects);
+
+    HEAP.with(|heap| {
+        let mut heap = heap.borrow_mut();
+        heap.stats.collections += 1;
+        heap.stats.freed += freed;
+        // 回收过程中新分配的对象在heap.objects中
+        heap.objects.extend(live);
+        heap.survivors = heap.objects.len();
+    });
+    freed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{eval, init_env, read, EvalContext};
+
+    // 可以指向另一个节点的托管对象
+    #[derive(Default)]
+    struct Node(RefCell<Option<Gc<Node>>>);
+
+    impl Node {
+        fn link(&self, next: &Gc<Node>) {
+            *self.0.borrow_mut() = Some(next.clone());
+        }
+    }
+
+    impl Trace for Node {
+        fn trace(&self, visit: &mut dyn FnMut(usize)) {
+            if let Some(next) = &*self.0.borrow() {
+                visit(next.id());
+            }
+        }
+
+        fn clear(&self) {
+            self.0.borrow_mut().take();
+        }
+    }
+
+    #[test]
+    fn collect_should_free_unreachable_cycles() {
+        let base = live_objects();
+        let (a, b) = (Gc::<Node>::default(), Gc::<Node>::default());
+        a.link(&b);
+        b.link(&a);
+        let root = Gc::<Node>::default();
+        root.link(&a);
+        drop((a, b));
+        // 仍然被root引用
+        assert_eq!(collect(), 0);
+        assert_eq!(live_objects(), base + 3);
+        root.clear();
+        assert_eq!(collect(), 2);
+        assert_eq!(live_objects(), base + 1);
+        drop(root);
+        assert_eq!(live_objects(), base);
+        assert_eq!(gc_stats().freed, 2);
+    }
+
+    #[test]
+    fn collect_should_free_scopes_that_hold_their_own_closures() {
+        let root = init_env();
+        let base = live_objects();
+        let env = root.extend(HashMap::new());
+        // f的作用域中有f自身
+        let f = eval(&read("(fn () f)").unwrap()[0].exp, &env, &mut EvalContext::default()).unwrap();
+        env.insert("f", f);
+        // 经过映射的循环引用: 作用域 -> 映射 -> 闭包 -> 作用域
+        let m = eval(&read("{:g (fn () m)}").unwrap()[0].exp, &env, &mut EvalContext::default()).unwrap();
+        env.insert("m", m);
+        drop(env);
+        assert_eq!(live_objects(), base + 2);
+        assert_eq!(collect(), 2);
+        assert_eq!(live_objects(), base);
+    }
+}
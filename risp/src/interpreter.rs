@@ -0,0 +1,407 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    eval::{eval, load_file_with, macroexpand_all},
+    init_env_with, parse, read, tokenize, vm, Capabilities, EvalContext, Form, Limits, RispEnv, RispError, RispExp,
+    RispFunc,
+};
+
+const PRELUDE: &str = include_str!("prelude.risp");
+
+/// 求值方式, 默认为树遍历求值, Vm先编译为字节码再执行
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    #[default]
+    Tree,
+    Vm,
+}
+
+/// 嵌入使用的解释器, 持有全局环境, 多次求值之间共享定义
+///
+/// ```
+/// use risp::{Interpreter, RispExp};
+///
+/// let mut risp = Interpreter::new();
+/// risp.set("rate", 0.5);
+/// risp.register_fn("double", |args: &[RispExp]| {
+///     let n = f64::try_from(args[0].clone())?;
+///     Ok(RispExp::from(n * 2.0))
+/// });
+/// let res = risp.eval_str("(double (+ rate 1))").unwrap();
+/// assert_eq!(f64::try_from(res).unwrap(), 3.0);
+/// ```
+#[derive(Debug)]
+pub struct Interpreter {
+    env: RispEnv,
+    ctx: EvalContext,
+    backend: Backend,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    // 内置函数和prelude中用risp实现的标准库
+    pub fn new() -> Self {
+        Self::with_capabilities(Capabilities::ALL)
+    }
+
+    // 不可信的代码使用Capabilities::NONE, 环境中没有访问文件、进程和标准输出的内置函数, 也不能load和import文件;
+    // 能力在创建时决定, 之后不能再修改
+    pub fn with_capabilities(capabilities: Capabilities) -> Self {
+        let mut risp = Self::with_env(init_env_with(capabilities));
+        risp.ctx.set_capabilities(capabilities);
+        if let Err(e) = risp.eval_all(PRELUDE) {
+            panic!("failed to load prelude: {}", e);
+        }
+        risp
+    }
+
+    pub fn with_env(env: RispEnv) -> Self {
+        Self { env, ctx: EvalContext::default(), backend: Backend::default() }
+    }
+
+    // 解析并执行一个表达式, 错误带有在src中的位置; src中有多个表达式时返回错误, 应使用eval_all
+    pub fn eval_str(&mut self, src: &str) -> Result<RispExp, RispError> {
+        let tokens = tokenize(src)?;
+        let (form, rest) = parse(&tokens, src)?;
+        if let Some(extra) = rest.first() {
+            return Err(RispError::spanned(
+                format!("unexpected token {} after the expression, use eval_all for multiple forms", extra.text),
+                extra.span,
+                src,
+            ));
+        }
+        self.eval_form(&form, src)
+    }
+
+    // 依次执行src中所有表达式, 返回最后一个的值, 没有表达式时为nil
+    pub fn eval_all(&mut self, src: &str) -> Result<RispExp, RispError> {
+        let mut res = RispExp::Nil;
+        for form in read(src)? {
+            res = self.eval_form(&form, src)?;
+        }
+        Ok(res)
+    }
+
+    // 执行文件中的所有表达式, 与(load "file")相同
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<RispExp, RispError> {
+        load_file_with(path.as_ref(), |exp| self.eval(exp))
+    }
+
+    pub fn eval_form(&mut self, form: &Form, src: &str) -> Result<RispExp, RispError> {
+        self.eval(&form.exp).map_err(|e| form.locate(e, src))
+    }
+
+    // 先展开宏再求值
+    pub fn eval(&mut self, exp: &RispExp) -> Result<RispExp, RispError> {
+        // 上一个未被捕获的错误的调用栈
+        self.ctx.error_stack = None;
+        let exp = macroexpand_all(exp, &self.env, &mut self.ctx)?;
+        match self.backend {
+            Backend::Tree => eval(&exp, &self.env, &mut self.ctx),
+            Backend::Vm => vm::run(&exp, &self.env, &mut self.ctx),
+        }
+    }
+
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    // 非尾调用的最大递归深度, 嵌套的eval、try和进入虚拟机执行闭包都计入;
+    // debug构建中每层约占用8KB栈, 在栈较小的线程中使用时需要相应调小
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.ctx.max_depth = max_depth;
+    }
+
+    // 限制之后求值的步数、时间和新增的存活对象数, 超出时返回RispError::Limit;
+    // 额度在多次求值之间累计, 再次调用时重新开始计算
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.ctx.set_limits(limits);
+    }
+
+    // 设置了fuel时剩余的步数
+    pub fn remaining_fuel(&self) -> Option<u64> {
+        self.ctx.remaining_fuel()
+    }
+
+    pub fn capabilities(&self) -> Capabilities {
+        self.ctx.capabilities()
+    }
+
+    // (import name)查找name.risp的目录, 默认只有当前目录
+    pub fn add_module_path(&mut self, dir: impl Into<PathBuf>) {
+        self.ctx.add_module_path(dir);
+    }
+
+    pub fn module_path(&self) -> &[PathBuf] {
+        self.ctx.module_path()
+    }
+
+    // 注册rust函数, 参数为已求值的实参
+    pub fn register_fn<F>(&mut self, name: &str, f: F)
+    where
+        F: Fn(&[RispExp]) -> Result<RispExp, RispError> + 'static,
+    {
+        self.env.insert(name, RispExp::Func(RispFunc::new(name, f)));
+    }
+
+    pub fn set(&mut self, name: &str, value: impl Into<RispExp>) {
+        self.env.insert(name, value.into());
+    }
+
+    pub fn get(&self, name: &str) -> Option<RispExp> {
+        self.env.get(name)
+    }
+
+    pub fn env(&self) -> &RispEnv {
+        &self.env
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    fn interpreter(backend: Backend) -> Interpreter {
+        let mut risp = Interpreter::new();
+        risp.set_backend(backend);
+        risp.set_max_depth(100);
+        risp
+    }
+
+    #[test]
+    fn host_values_and_functions_should_be_shared_with_scripts() {
+        let mut risp = Interpreter::new();
+        risp.set("rate", 0.5);
+        risp.register_fn("checked", |args: &[RispExp]| match args {
+            [RispExp::Int(n)] if *n >= 0 => Ok(RispExp::Int(*n)),
+            _ => Err(RispError::Reason("expected a non negative int".to_string())),
+        });
+        assert_eq!(risp.eval_all("").unwrap(), RispExp::Nil);
+        assert_eq!(risp.eval_all("(def x (checked 2)) (* x rate)").unwrap(), RispExp::Number(1.0));
+        assert_eq!(risp.get("x"), Some(RispExp::Int(2)));
+        let err = risp.eval_str("(+ 1\n   (checked -1))").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "expected a non negative int\n --> 2:4\n  |\n2 |    (checked -1))\n  |    ^^^^^^^^^^^^"
+        );
+        // eval_str只接受一个表达式, 其余的不会被静默丢弃
+        let err = risp.eval_str("(def y 1) (def z 2)").unwrap_err();
+        assert_eq!(err.span().map(|s| s.start), Some(10));
+        assert_eq!(risp.get("y"), None);
+        // with_env不加载prelude
+        let mut bare = Interpreter::with_env(crate::init_env());
+        assert!(bare.eval_str("(defn f () 1)").is_err());
+    }
+
+    #[test]
+    fn load_file_should_locate_errors_in_the_file() {
+        let dir = std::env::temp_dir().join(format!("risp-load-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (lib, main, bad) = (dir.join("lib.risp"), dir.join("main.risp"), dir.join("bad.risp"));
+        std::fs::write(&lib, "(defn twice (x) (* 2 x))\n(defn broken () (car 1))").unwrap();
+        std::fs::write(&main, format!("(load {:?})\n(twice 21)", lib.display().to_string())).unwrap();
+        std::fs::write(&bad, "(twice 1)\n(broken)").unwrap();
+        for backend in [Backend::Tree, Backend::Vm] {
+            let mut risp = interpreter(backend);
+            assert_eq!(risp.load_file(&main).unwrap(), RispExp::Int(42), "{:?}", backend);
+            let err = risp.load_file(&bad).unwrap_err();
+            let expected = format!("expected a list, got 1\n --> {}:2:1", bad.display());
+            assert!(err.to_string().starts_with(&expected), "{:?}: {}", backend, err);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn try_should_count_towards_max_depth() {
+        for backend in [Backend::Tree, Backend::Vm] {
+            let mut risp = interpreter(backend);
+            risp.eval_str("(defn f (n) (if (= n 0) 0 (+ 1 (try (f (- n 1)) (catch e 0)))))").unwrap();
+            // try和body各占一层, 两种方式在同一层超出深度, 错误被最内层的catch捕获
+            assert_eq!(risp.eval_str("(f 1000)").unwrap(), RispExp::Int(49), "{:?}", backend);
+        }
+    }
+
+    #[test]
+    fn map_should_count_towards_max_depth() {
+        for backend in [Backend::Tree, Backend::Vm] {
+            let mut risp = interpreter(backend);
+            // 虚拟机中map为树遍历求值的lambda, g为虚拟机的闭包, 每层都重新进入对方
+            risp.eval_str("(defn g (n) (if (= n 0) 0 (+ 1 (car (map g (list (- n 1)))))))").unwrap();
+            assert_eq!(risp.eval_str("(g 10)").unwrap(), RispExp::Int(10));
+            let err = risp.eval_str("(g 1000)").unwrap_err();
+            assert!(err.to_string().starts_with("maximum recursion depth 100 exceeded"), "{:?}", backend);
+            // 出错后深度恢复
+            assert_eq!(risp.ctx.depth(), 0);
+        }
+    }
+
+    #[test]
+    fn default_max_depth_should_fit_default_thread_stack() {
+        // std::thread::spawn使用默认的2MB栈, 递归时按需扩展, 超过默认深度时返回错误而不是栈溢出
+        std::thread::spawn(|| {
+            for backend in [Backend::Tree, Backend::Vm] {
+                let mut risp = Interpreter::new();
+                risp.set_backend(backend);
+                risp.eval_all(
+                    "(defn deep (n) (if (= n 0) 0 (+ 1 (deep (- n 1)))))
+                     (defn g (n) (if (= n 0) 0 (+ 1 (car (map g (list (- n 1)))))))",
+                )
+                .unwrap();
+                assert_eq!(risp.eval_str("(deep 900)").unwrap(), RispExp::Int(900), "{:?}", backend);
+                assert_eq!(risp.eval_str("(g 150)").unwrap(), RispExp::Int(150), "{:?}", backend);
+                let err = risp.eval_str("(deep 5000)").unwrap_err();
+                assert!(err.to_string().starts_with("maximum recursion depth 1000 exceeded"), "{:?}", backend);
+            }
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn try_should_catch_errors_and_always_run_finally() {
+        for backend in [Backend::Tree, Backend::Vm] {
+            let mut risp = interpreter(backend);
+            risp.eval_all(
+                r#"
+                (def log '())
+                (defn note (x) (set! log (cons x log)))
+                (defn check (n) (if (< n 0) (raise {:kind :negative :message "negative"}) n))
+                (defn f (n) (+ 1 (check n)))"#,
+            )
+            .unwrap();
+            let cases = [
+                (
+                    "(try (f -1) (catch e [(get e :kind) (get e :message) (get e :stack)]) (finally (note 1)))",
+                    r#"[:negative "negative" ("f" "check")]"#,
+                ),
+                ("(try (/ 1 0) (catch e [(get e :kind) (get e :message)]))", r#"[:error "division by zero"]"#),
+                ("(try (raise 42) (catch e [(get e :kind) (get e :value)]))", "[:raise 42]"),
+                ("(try (f 1) (f 2) (catch e :caught) (finally (note 2)))", "3"),
+                ("(try (try (raise 1) (catch e (raise (+ (get e :value) 1)))) (catch e (get e :value)))", "2"),
+            ];
+            for (src, expected) in cases {
+                let res = risp.eval_str(src).map(|v| v.to_string());
+                assert_eq!(res.as_deref(), Ok(expected), "{:?}: {}", backend, src);
+            }
+            // 没有catch时错误在finally执行后继续传递, finally中的错误优先
+            let err = risp.eval_str("(try (raise 1) (finally (note 3)))").unwrap_err();
+            assert_eq!(err.message(), "1", "{:?}", backend);
+            let err = risp.eval_str("(try (raise 1) (catch e (raise 2)) (finally (raise 3)))").unwrap_err();
+            assert_eq!(err.message(), "3", "{:?}", backend);
+            assert_eq!(risp.eval_str("log").unwrap().to_string(), "(3 2 1)", "{:?}", backend);
+        }
+    }
+
+    #[test]
+    fn limits_should_stop_evaluation_and_skip_try() {
+        for backend in [Backend::Tree, Backend::Vm] {
+            let mut risp = interpreter(backend);
+            risp.eval_all("(def done false) (defn spin (n) (spin (+ n 1))) (defn grow (xs) (grow (cons 1 xs)))")
+                .unwrap();
+            let guarded = |body: &str| format!("(try {} (catch e :caught) (finally (set! done true)))", body);
+            let cases = [
+                (Limits { fuel: Some(10_000), ..Limits::default() }, "(spin 0)", "fuel exhausted"),
+                (Limits { deadline: Some(Instant::now()), ..Limits::default() }, "(spin 0)", "deadline exceeded"),
+                (
+                    Limits { max_objects: Some(1000), ..Limits::default() },
+                    "(grow '())",
+                    "allocation limit of 1000 objects exceeded",
+                ),
+            ];
+            for (limits, body, msg) in cases {
+                risp.set_limits(limits);
+                let err = risp.eval_str(&guarded(body)).unwrap_err();
+                assert_eq!(err, RispError::Limit(msg.to_string()), "{:?}", backend);
+            }
+            risp.set_limits(Limits::default());
+            assert_eq!(risp.eval_str("done").unwrap(), RispExp::Boolean(false), "{:?}", backend);
+
+            // 多次求值共用同一份fuel
+            risp.set_limits(Limits { fuel: Some(1000), ..Limits::default() });
+            risp.eval_str("(+ 1 2)").unwrap();
+            let left = risp.remaining_fuel().unwrap();
+            assert!(left < 1000, "{:?}", backend);
+            risp.eval_str("(+ 1 2)").unwrap();
+            assert!(risp.remaining_fuel().unwrap() < left, "{:?}", backend);
+        }
+    }
+
+    #[test]
+    fn max_value_bytes_should_limit_strings_and_bigints() {
+        for backend in [Backend::Tree, Backend::Vm] {
+            let mut risp = interpreter(backend);
+            risp.set_limits(Limits { max_value_bytes: Some(1024), ..Limits::default() });
+            risp.eval_str("(defn grow (f x) (grow f (try (f x x) (catch e x))))").unwrap();
+            // 超出限制的错误不能被try捕获, 尾调用不计入递归深度
+            for src in [r#"(grow str "ab")"#, "(grow * 3)"] {
+                let err = risp.eval_str(src).unwrap_err();
+                assert!(matches!(err, RispError::Limit(_)), "{:?}: {}", backend, err);
+            }
+            assert_eq!(risp.eval_str(r#"(str "a" 1)"#).unwrap(), RispExp::Str("a1".to_string()));
+        }
+    }
+
+    #[test]
+    fn modules_should_keep_definitions_apart_and_load_once() {
+        let dir = std::env::temp_dir().join(format!("risp-modules-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("text.risp"),
+            r#"(set! loads (+ loads 1))
+            (module text (defn helper (s) (str "<" s ">")) (defn wrap (xs) (map helper xs)))"#,
+        )
+        .unwrap();
+        for backend in [Backend::Tree, Backend::Vm] {
+            let mut risp = interpreter(backend);
+            risp.add_module_path(&dir);
+            risp.eval_all("(def loads 0) (import text [wrap]) (import text) (defn helper (x) (* x 2))").unwrap();
+            let res = risp.eval_str("[(text/wrap '(1 2)) (wrap '(3)) (helper 2) loads]").unwrap();
+            assert_eq!(res.to_string(), r#"[("<1>" "<2>") ("<3>") 4 1]"#, "{:?}", backend);
+
+            let err = risp.eval_str("(import missing)").unwrap_err();
+            assert!(err.message().starts_with("module missing not found in ."), "{:?}: {}", backend, err);
+            let err = risp.eval_str("(import text [nope])").unwrap_err();
+            assert_eq!(err.message(), "module text has no definition nope", "{:?}", backend);
+            // 出错的模块不保留
+            assert!(risp.eval_str("(module broken (def a 1) (raise 1))").is_err());
+            assert!(risp.eval_str("broken/a").is_err(), "{:?}", backend);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut risp = Interpreter::with_capabilities(Capabilities::NONE);
+        let err = risp.eval_str("(import text)").unwrap_err();
+        assert_eq!(err.message(), "import is not allowed in the sandbox");
+    }
+
+    #[test]
+    fn capabilities_should_be_removed_when_the_env_is_built() {
+        let mut risp = Interpreter::with_capabilities(Capabilities::NONE);
+        for src in [r#"(read-file "Cargo.toml")"#, r#"(getenv "HOME")"#, r#"(println "x")"#] {
+            let err = risp.eval_str(src).unwrap_err();
+            assert!(err.to_string().starts_with("unexpected symbol"), "{}", err);
+        }
+        let err = risp.eval_str(r#"(load "Cargo.toml")"#).unwrap_err();
+        assert!(err.to_string().starts_with("load is not allowed in the sandbox"), "{}", err);
+        assert_eq!(risp.capabilities(), Capabilities::NONE);
+
+        let risp = Interpreter::with_capabilities(Capabilities::PROCESS);
+        assert!(risp.get("getenv").is_some() && risp.get("read-file").is_none());
+        // 退出码超出i32时返回错误, 不截断
+        let mut risp = Interpreter::with_capabilities(Capabilities::PROCESS);
+        for src in ["(exit 4294967296)", "(exit 99999999999999999999)"] {
+            let err = risp.eval_str(src).unwrap_err();
+            assert!(err.to_string().starts_with("exit code out of range"), "{}", err);
+        }
+    }
+}
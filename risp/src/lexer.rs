@@ -0,0 +1,236 @@
+use std::{fmt, iter::Peekable, str::CharIndices};
+
+use crate::RispError;
+
+// 源码中的位置, start/end为字节偏移, line/col从1开始(col按字符计算)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    // #{
+    HashBrace,
+    // 'x
+    Quote,
+    // `x
+    Quasiquote,
+    // ~x
+    Unquote,
+    // ~@x
+    UnquoteSplicing,
+    Str,
+    Atom,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    pub span: Span,
+}
+
+struct Lexer<'a> {
+    src: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    line: usize,
+    col: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(src: &'a str) -> Self {
+        Self { src, chars: src.char_indices().peekable(), line: 1, col: 1 }
+    }
+
+    fn offset(&mut self) -> usize {
+        self.chars.peek().map_or(self.src.len(), |(i, _)| *i)
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|(_, c)| *c)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let (_, c) = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+        Some(c)
+    }
+
+    fn span_from(&mut self, start: usize, line: usize, col: usize) -> Span {
+        Span { start, end: self.offset(), line, col }
+    }
+
+    fn next_token(&mut self) -> Option<Result<Token, RispError>> {
+        // 跳过空白和注释
+        loop {
+            match self.peek()? {
+                c if c.is_whitespace() => {
+                    self.bump();
+                }
+                ';' => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.bump();
+                    }
+                }
+                _ => break,
+            }
+        }
+        let (start, line, col) = (self.offset(), self.line, self.col);
+        let c = self.bump()?;
+        let kind = match c {
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            '[' => TokenKind::LBracket,
+            ']' => TokenKind::RBracket,
+            '{' => TokenKind::LBrace,
+            '}' => TokenKind::RBrace,
+            '#' if self.peek() == Some('{') => {
+                self.bump();
+                TokenKind::HashBrace
+            }
+            '\'' => TokenKind::Quote,
+            '`' => TokenKind::Quasiquote,
+            '~' if self.peek() == Some('@') => {
+                self.bump();
+                TokenKind::UnquoteSplicing
+            }
+            '~' => TokenKind::Unquote,
+            '"' => {
+                let mut terminated = false;
+                while let Some(c) = self.bump() {
+                    match c {
+                        '\\' => {
+                            self.bump();
+                        }
+                        '"' => {
+                            terminated = true;
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                let span = self.span_from(start, line, col);
+                if !terminated {
+                    return Some(Err(RispError::spanned("unterminated string", span, self.src)));
+                }
+                TokenKind::Str
+            }
+            _ => {
+                // 字符字面量\(的第二个字符可以是括号或空白
+                if c == '\\' {
+                    self.bump();
+                }
+                while self.peek().is_some_and(|c| !is_delimiter(c)) {
+                    self.bump();
+                }
+                TokenKind::Atom
+            }
+        };
+        let span = self.span_from(start, line, col);
+        Some(Ok(Token { kind, text: self.src[span.start..span.end].to_string(), span }))
+    }
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '(' | ')' | '[' | ']' | '{' | '}' | '"' | ';' | '\'' | '`' | '~')
+}
+
+// 按行列记录每个token的位置, `;`到行尾为注释
+pub fn tokenize(src: &str) -> Result<Vec<Token>, RispError> {
+    let mut lexer = Lexer::new(src);
+    let mut tokens = vec![];
+    while let Some(token) = lexer.next_token() {
+        tokens.push(token?);
+    }
+    Ok(tokens)
+}
+
+// 括号未闭合、字符串未结束或以引号结尾时输入不完整, REPL需要继续读取下一行
+pub fn is_complete(src: &str) -> bool {
+    let mut lexer = Lexer::new(src);
+    let mut depth = 0i32;
+    let mut last = None;
+    while let Some(token) = lexer.next_token() {
+        let Ok(token) = token else {
+            return false;
+        };
+        match token.kind {
+            TokenKind::LParen | TokenKind::LBracket | TokenKind::LBrace | TokenKind::HashBrace => depth += 1,
+            TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace => depth -= 1,
+            _ => {}
+        }
+        last = Some(token.kind);
+    }
+    let quoted = matches!(
+        last,
+        Some(TokenKind::Quote | TokenKind::Quasiquote | TokenKind::Unquote | TokenKind::UnquoteSplicing)
+    );
+    depth <= 0 && !quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_should_record_spans() {
+        let tokens = tokenize("(print \"é\"\n  'ab) ; x\n#{1}").unwrap();
+        let spans: Vec<(TokenKind, &str, usize, usize)> =
+            tokens.iter().map(|t| (t.kind, t.text.as_str(), t.span.line, t.span.col)).collect();
+        assert_eq!(
+            spans,
+            [
+                (TokenKind::LParen, "(", 1, 1),
+                (TokenKind::Atom, "print", 1, 2),
+                (TokenKind::Str, "\"é\"", 1, 8),
+                (TokenKind::Quote, "'", 2, 3),
+                (TokenKind::Atom, "ab", 2, 4),
+                (TokenKind::RParen, ")", 2, 6),
+                (TokenKind::HashBrace, "#{", 3, 1),
+                (TokenKind::Atom, "1", 3, 3),
+                (TokenKind::RBrace, "}", 3, 4),
+            ]
+        );
+        // 字节偏移与列号不同
+        assert_eq!((tokens[3].span.start, tokens[3].span.end), (14, 15));
+    }
+
+    #[test]
+    fn tokenize_should_report_unterminated_strings() {
+        let err = tokenize("(a\n \"bc").unwrap_err();
+        assert_eq!(err.message(), "unterminated string");
+        assert_eq!(err.span(), Some(Span { start: 4, end: 7, line: 2, col: 2 }));
+        assert_eq!(err.to_string(), "unterminated string\n --> 2:2\n  |\n2 |  \"bc\n  |  ^^^");
+    }
+
+    #[test]
+    fn is_complete_should_wait_for_closing_brackets_and_quoted_forms() {
+        for src in ["(+ 1", "[1 {:a", "\"abc", "'", "(a) `"] {
+            assert!(!is_complete(src), "{}", src);
+        }
+        for src in ["(+ 1 2)", "[1 {:a 2}] ; (", "\\(", ")"] {
+            assert!(is_complete(src), "{}", src);
+        }
+    }
+}
@@ -0,0 +1,30 @@
+mod collections;
+mod compiler;
+mod env;
+mod error;
+mod eval;
+mod exp;
+mod gc;
+mod interpreter;
+mod lexer;
+mod list;
+mod num;
+mod parser;
+mod printer;
+mod sandbox;
+mod vm;
+
+pub use collections::{RispMap, RispSet, RispVector, SetIter};
+pub use compiler::{compile, Chunk, Op, Proto};
+pub use env::{init_env, init_env_with, RispEnv};
+pub use error::RispError;
+pub use eval::{eval, macroexpand, macroexpand_all, EvalContext, DEFAULT_MAX_DEPTH, SPECIAL_FORMS};
+pub use exp::{RispExp, RispFunc, RispLambda};
+pub use gc::{collect, gc_stats, live_objects, Gc, GcStats, Trace};
+pub use interpreter::{Backend, Interpreter};
+pub use lexer::{is_complete, tokenize, Span, Token, TokenKind};
+pub use list::RispList;
+pub use parser::{parse, read, Form, SpanTree, MAX_NESTING};
+pub use printer::pretty;
+pub use sandbox::{Capabilities, Limits};
+pub use vm::Closure;
@@ -0,0 +1,172 @@
+use std::fmt;
+
+use crate::{
+    gc::{Gc, Trace},
+    RispExp,
+};
+
+// 不可变的单链表, cons和cdr共享尾部, 不复制元素
+#[derive(Clone, Default)]
+pub struct RispList(Option<Gc<Cons>>);
+
+pub struct Cons {
+    car: RispExp,
+    cdr: RispList,
+}
+
+impl Trace for Cons {
+    fn trace(&self, visit: &mut dyn FnMut(usize)) {
+        self.car.trace(visit);
+        self.cdr.trace(visit);
+    }
+}
+
+// 长list逐个释放, 避免递归drop导致栈溢出
+impl Drop for Cons {
+    fn drop(&mut self) {
+        let mut next = self.cdr.0.take();
+        while let Some(cell) = next {
+            match Gc::try_unwrap(cell) {
+                Ok(mut cons) => next = cons.cdr.0.take(),
+                Err(_) => break,
+            }
+        }
+    }
+}
+
+impl RispList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cons(car: RispExp, cdr: &RispList) -> Self {
+        Self(Some(Gc::new(Cons { car, cdr: cdr.clone() })))
+    }
+
+    pub fn first(&self) -> Option<&RispExp> {
+        self.0.as_ref().map(|c| &c.car)
+    }
+
+    // 空list的rest也为空
+    pub fn rest(&self) -> RispList {
+        self.0.as_ref().map(|c| c.cdr.clone()).unwrap_or_default()
+    }
+
+    // 去掉前n个元素
+    pub fn skip(&self, n: usize) -> RispList {
+        let mut cur = self;
+        for _ in 0..n {
+            match &cur.0 {
+                Some(c) => cur = &c.cdr,
+                None => break,
+            }
+        }
+        cur.clone()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_none()
+    }
+
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&RispExp> {
+        self.iter().nth(idx)
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(self.0.as_deref())
+    }
+
+    pub fn to_vec(&self) -> Vec<RispExp> {
+        self.iter().cloned().collect()
+    }
+
+    pub(crate) fn trace(&self, visit: &mut dyn FnMut(usize)) {
+        if let Some(c) = &self.0 {
+            visit(c.id());
+        }
+    }
+}
+
+pub struct Iter<'a>(Option<&'a Cons>);
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a RispExp;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cons = self.0?;
+        self.0 = cons.cdr.0.as_deref();
+        Some(&cons.car)
+    }
+}
+
+impl<'a> IntoIterator for &'a RispList {
+    type Item = &'a RispExp;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// 从后向前创建cons单元
+impl FromIterator<RispExp> for RispList {
+    fn from_iter<I: IntoIterator<Item = RispExp>>(iter: I) -> Self {
+        let items: Vec<RispExp> = iter.into_iter().collect();
+        items
+            .into_iter()
+            .rev()
+            .fold(RispList::new(), |cdr, car| RispList::cons(car, &cdr))
+    }
+}
+
+impl From<Vec<RispExp>> for RispList {
+    fn from(items: Vec<RispExp>) -> Self {
+        items.into_iter().collect()
+    }
+}
+
+impl PartialEq for RispList {
+    fn eq(&self, other: &Self) -> bool {
+        let (mut a, mut b) = (self.iter(), other.iter());
+        loop {
+            match (a.next(), b.next()) {
+                (None, None) => return true,
+                (Some(x), Some(y)) if x == y => {}
+                _ => return false,
+            }
+        }
+    }
+}
+
+impl fmt::Debug for RispList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cons_should_share_the_tail() {
+        let tail: RispList = vec![RispExp::Int(2), RispExp::Int(3)].into();
+        let list = RispList::cons(RispExp::Int(1), &tail);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.get(2), Some(&RispExp::Int(3)));
+        assert_eq!(list.rest(), tail);
+        assert!(Gc::ptr_eq(list.skip(1).0.as_ref().unwrap(), tail.0.as_ref().unwrap()));
+        assert!(list.skip(5).is_empty() && RispList::new().rest().is_empty());
+    }
+
+    #[test]
+    fn long_lists_should_drop_without_recursion() {
+        let list: RispList = (0..200_000).map(RispExp::Int).collect();
+        assert_eq!(list.len(), 200_000);
+        drop(list);
+    }
+}
@@ -1,371 +1,245 @@
-use std::{io::{self, Write}, fmt, collections::HashMap, rc::Rc};
-
-// Env
-macro_rules! ensure_tonicity {
-    ($check_fn:expr) => {{
-        |args: &[RispExp]| -> Result<RispExp, RispError> {
-            let floats = parse_list_of_floats(args)?;
-            let first = floats.first().ok_or(RispError::Reason("expected at least one number".to_string()))?;
-            let rest = &floats[1..];
-            fn f(pre: &f64, rest: &[f64]) -> bool {
-                match rest.first() {
-                    Some(x) => $check_fn(pre, x) && f(x, &rest[1..]),
-                    None => true,
-                }
-            }
-            Ok(RispExp::Boolean(f(first, rest)))
+use std::{
+    env,
+    path::PathBuf,
+    process::ExitCode,
+    time::{Duration, Instant},
+};
+
+use clap::Parser;
+use rustyline::{
+    completion::Completer, error::ReadlineError, highlight::Highlighter, hint::Hinter, history::DefaultHistory,
+    validate::Validator, Context, Editor, Helper,
+};
+
+use risp::{
+    is_complete, pretty, read, tokenize, Backend, Capabilities, Interpreter, Limits, RispEnv, RispError, RispExp,
+    SPECIAL_FORMS,
+};
+
+// 结果超过这个宽度时换行输出
+const PRINT_WIDTH: usize = 80;
+
+const HELP: &str = ":help         显示帮助
+:env          列出环境中的符号和值
+:load <file>  执行文件中的所有表达式
+Ctrl-C        清空当前输入, Ctrl-D退出";
+
+/// risp解释器, 执行脚本文件或表达式, 都没有时进入REPL
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// 脚本文件, 依次执行其中所有表达式
+    file: Option<PathBuf>,
+    /// 执行表达式并输出最后一个的值, e: `-e "(+ 1 2)"`
+    #[clap(short, long, conflicts_with = "file")]
+    eval: Option<String>,
+    /// 编译为字节码在虚拟机中执行
+    #[clap(long)]
+    vm: bool,
+    /// 不输出tokens和exp调试信息
+    #[clap(short, long)]
+    quiet: bool,
+    /// 禁止load和访问文件、进程的内置函数
+    #[clap(long)]
+    sandbox: bool,
+    /// 最多执行的求值步数, REPL中每次输入重新计算
+    #[clap(long)]
+    fuel: Option<u64>,
+    /// 求值的时间限制(毫秒)
+    #[clap(long)]
+    timeout: Option<u64>,
+    /// 最多新增的存活对象数
+    #[clap(long)]
+    max_objects: Option<usize>,
+    /// 内置函数返回的单个字符串或大整数的最大字节数
+    #[clap(long)]
+    max_value_bytes: Option<usize>,
+    /// import查找模块的目录, 在脚本所在目录和RISP_PATH之前查找
+    #[clap(short = 'I', long = "module-path")]
+    module_path: Vec<PathBuf>,
+}
+
+impl Args {
+    fn limits(&self) -> Limits {
+        Limits {
+            fuel: self.fuel,
+            deadline: self.timeout.map(|ms| Instant::now() + Duration::from_millis(ms)),
+            max_objects: self.max_objects,
+            max_value_bytes: self.max_value_bytes,
         }
-    }};
-}
-
-#[derive(Debug, Clone)]
-enum RispExp {
-    Boolean(bool),
-    Number(f64),
-    Symbol(String),
-    List(Vec<RispExp>),
-    Func(fn(&[RispExp]) -> Result<RispExp, RispError>),
-    Lambda(RispLambda),
-}
-
-#[derive(Debug, Clone)]
-struct RispLambda {
-  params_exp: Rc<RispExp>,
-  body_exp: Rc<RispExp>,
-}
-
-impl fmt::Display for RispExp {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let str = match self {
-            RispExp::Boolean(a) => a.to_string(),
-            RispExp::Number(n) => n.to_string(),
-            RispExp::Symbol(s) => s.clone(),
-            RispExp::List(list) => {
-                let s: Vec<String> = list.iter().map(|x|x.to_string()).collect();
-                format!("({})", s.join(","))
-            },
-            RispExp::Func(_) => "Function {}".to_string(),
-            RispExp::Lambda(_) => "Lambda {}".to_string(),
-        };
-        write!(f, "{}", str)
     }
 }
 
-#[derive(Debug)]
-enum RispError {
-    Reason(String),
+// 按环境中的符号和特殊形式补全
+struct RispHelper {
+    env: RispEnv,
 }
 
-#[derive(Debug, Clone)]
-struct RispEnv {
-    data: HashMap<String, RispExp>,
-    outer: Option<Box<RispEnv>>,
-}
-    
-fn tokenize(input: &str) -> Vec<String> {
-    input
-        .replace("(", " ( ")
-        .replace(")", " ) ")
-        .split_whitespace()
-        .map(|s| s.to_string())
-        .collect()
-}
+impl Completer for RispHelper {
+    type Candidate = String;
 
-// (+ 1 2 3)
-fn parse(tokens: &[String]) -> Result<(RispExp, &[String]), RispError> {
-    let (first, rest) = tokens.split_first().ok_or(RispError::Reason("could not get token".to_string()))?;
-    match &first[..] {
-        "(" => parse_expression(rest),
-        ")" => Err(RispError::Reason("unexpected token)".to_string())),
-        _ => Ok((parse_atom(first), rest)),
-    }
-}
-
-// + 1 2 3)
-fn parse_expression(tokens: &[String]) -> Result<(RispExp, &[String]), RispError> {
-    let mut ret = Vec::new();
-    let mut cur = tokens;
-    loop {
-        let peek = cur.get(0);
-        if peek.is_some() {
-            if peek.unwrap() == ")" {
-                return Ok((RispExp::List(ret), &cur[1..]));
-            }
-        } else {
-            return Err(RispError::Reason("can not found)".to_string()));
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos]
+            .rfind(|c: char| c.is_whitespace() || "()[]{}'`~\"".contains(c))
+            .map_or(0, |i| i + 1);
+        let prefix = &line[start..pos];
+        if prefix.is_empty() {
+            return Ok((start, vec![]));
         }
-        let (exp, rest) = parse(cur)?;
-        ret.push(exp);
-        cur = rest;
+        let mut names: Vec<String> = self
+            .env
+            .keys()
+            .into_iter()
+            .chain(SPECIAL_FORMS.iter().map(|s| s.to_string()))
+            .filter(|k| k.starts_with(prefix))
+            .collect();
+        names.sort();
+        names.dedup();
+        Ok((start, names))
     }
 }
 
-// + 1 2 3
-fn parse_atom(token: &str) -> RispExp {
-    match token.as_ref() {
-        "true" => RispExp::Boolean(true),
-        "false" => RispExp::Boolean(false),
-        _ => {
-            match token.parse::<f64>() {
-                Ok(n) => RispExp::Number(n),
-                Err(_) => RispExp::Symbol(token.to_string()),
-            }
-        },
-    }
+impl Hinter for RispHelper {
+    type Hint = String;
 }
 
-fn parse_single_float(exp: &RispExp) -> Result<f64, RispError> {
-    match exp {
-        RispExp::Number(n) => Ok(*n),
-        RispExp::Boolean(b) => Ok(*b as i32 as f64),
-        _ => Err(RispError::Reason("expected a number".to_string())),
-    }
-}
+impl Highlighter for RispHelper {}
 
-fn parse_list_of_floats(args: &[RispExp]) -> Result<Vec<f64>, RispError> {
-    args.iter().map(|x| parse_single_float(x)).collect()
-}
+impl Validator for RispHelper {}
 
-fn init_env() -> RispEnv {
-    let mut data: HashMap<String, RispExp> = HashMap::new();
-    data.insert("+".to_string(), RispExp::Func(|args: &[RispExp]| -> Result<RispExp, RispError> {
-        let sum = parse_list_of_floats(args)?.iter().sum();
-        Ok(RispExp::Number(sum))
-    }));
-    data.insert("-".to_string(), RispExp::Func(|args: &[RispExp]| -> Result<RispExp, RispError> {
-        let floats = parse_list_of_floats(args)?;
-        let first = floats.first().ok_or(RispError::Reason("expected at least one number".to_string()))?;
-        let rest_sum: f64 = floats[1..].iter().sum();
-        Ok(RispExp::Number(first - rest_sum))
-    }));
-    data.insert(
-      "=".to_string(), 
-      RispExp::Func(ensure_tonicity!(|a, b| a == b))
-    );
-    data.insert(
-      ">".to_string(), 
-      RispExp::Func(ensure_tonicity!(|a, b| a > b))
-    );
-    data.insert(
-      ">=".to_string(), 
-      RispExp::Func(ensure_tonicity!(|a, b| a >= b))
-    );
-    data.insert(
-      "<".to_string(), 
-      RispExp::Func(ensure_tonicity!(|a, b| a < b))
-    );
-    data.insert(
-      "<=".to_string(), 
-      RispExp::Func(ensure_tonicity!(|a, b| a <= b))
-    );
-    RispEnv { data, outer: None }
-}
+impl Helper for RispHelper {}
 
-// map get
-fn env_get(k: &str, env: &RispEnv) -> Option<RispExp> {
-    match env.data.get(k) {
-        Some(v) => Some(v.clone()),
-        None => {
-            match &env.outer {
-                Some(o) => env_get(k, o),
-                None => None
-            }
-        },
-    }
+fn history_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|dir| dir.join(".risp_history"))
 }
 
-// (if false 1 2) -> 2
-fn eval_if_args(arg_forms: &[RispExp], env: &mut RispEnv) -> Result<RispExp, RispError> {
-    let test_form = arg_forms.first().ok_or(RispError::Reason("expected test form".to_string()))?;
-    let test_eval = eval(test_form, env)?;
-    match test_eval {
-        RispExp::Boolean(b) => {
-            let form_idx = if b { 1 } else { 2 };
-            let res_form = arg_forms.get(form_idx)
-              .ok_or(RispError::Reason(
-                format!("expected form idx={}", form_idx)
-              ))?;
-            let res_eval = eval(res_form, env);
-            res_eval
-        },
-        _ => Err(RispError::Reason(format!("unexpected test form='{}'", test_form.to_string())))
+// 读取一个完整的输入, 括号未闭合时用..提示继续输入
+fn read_input(rl: &mut Editor<RispHelper, DefaultHistory>) -> Result<String, ReadlineError> {
+    let mut input = rl.readline("> ")?;
+    while !input.trim_start().starts_with(':') && !is_complete(&input) {
+        let line = rl.readline(".. ")?;
+        input.push('\n');
+        input.push_str(&line);
     }
+    Ok(input)
 }
 
-// def a 1 => (+ a 1)
-fn eval_def_args(arg_forms: &[RispExp], env: &mut RispEnv) -> Result<RispExp, RispError> {
-    let first_form = arg_forms.first().ok_or(RispError::Reason("expected first form".to_string()))?;
-    let first_str = match first_form {
-        RispExp::Symbol(s) => Ok(s.clone()),
-        _ => Err(RispError::Reason(
-          "expected first form to be a symbol".to_string(),
-        ))
-    }?;
-    let second_form = arg_forms.get(1).ok_or(
-      RispError::Reason(
-        "expected second form".to_string(),
-      )
-    )?;
-    if arg_forms.len() > 2 {
-      return Err(
-        RispError::Reason(
-          "def can only have two forms ".to_string(),
-        )
-      )
+fn parse_eval(src: &str, risp: &mut Interpreter, quiet: bool) -> Result<RispExp, RispError> {
+    if !quiet {
+        let tokens = tokenize(src)?;
+        println!(
+            "tokens: {:?}",
+            tokens.iter().map(|t| t.text.as_str()).collect::<Vec<_>>()
+        );
     }
-    let second_eval = eval(second_form, env)?;
-    env.data.insert(first_str, second_eval);
-    Ok(first_form.clone())
+    let mut res = RispExp::Nil;
+    for form in read(src)? {
+        if !quiet {
+            println!("exp: {:?}", form.exp);
+        }
+        res = risp.eval_form(&form, src)?;
+    }
+    Ok(res)
 }
 
-fn eval_lambda_args(arg_forms: &[RispExp]) -> Result<RispExp, RispError> {
-    let params_exp = arg_forms.first().ok_or(
-      RispError::Reason(
-        "expected args form".to_string(),
-      )
-    )?;
-    let body_exp = arg_forms.get(1).ok_or(
-      RispError::Reason(
-        "expected second form".to_string(),
-      )
-    )?;
-    if arg_forms.len() > 2 {
-      return Err(
-        RispError::Reason(
-          "fn definition can only have two forms ".to_string(),
-        )
-      )
-    }
-    Ok(
-      RispExp::Lambda(
-        RispLambda {
-          body_exp: Rc::new(body_exp.clone()),
-          params_exp: Rc::new(params_exp.clone()),
+fn print_env(risp: &Interpreter) {
+    let mut keys = risp.env().keys();
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        if let Some(value) = risp.get(&key) {
+            println!("{} = {}", key, pretty(&value, PRINT_WIDTH));
         }
-      )
-    )
+    }
 }
 
-fn parse_list_of_symbol_strings(form: Rc<RispExp>) -> Result<Vec<String>, RispError> {
-    let list = match form.as_ref() {
-        RispExp::List(s) => Ok(s.clone()),
-        _ => Err(RispError::Reason(
-          "expected args form to be a list".to_string(),
-        ))
-    }?;
-    list.iter().map(|x| {
-        match x {
-            RispExp::Symbol(s) => Ok(s.clone()),
-            _ => Err(RispError::Reason(
-              "expected symbols in the argument list".to_string(),
-            ))
+fn run_command(cmd: &str, risp: &mut Interpreter) -> Result<Option<RispExp>, RispError> {
+    let (name, arg) = cmd.split_once(char::is_whitespace).unwrap_or((cmd, ""));
+    match (name, arg.trim()) {
+        (":help", _) => println!("{}", HELP),
+        (":env", _) => print_env(risp),
+        (":load", "") => return Err(RispError::Reason(":load expected a file".to_string())),
+        (":load", _) if !risp.capabilities().contains(Capabilities::FILE) => {
+            return Err(RispError::Reason("load is not allowed in the sandbox".to_string()))
         }
-    }).collect()
+        (":load", path) => return risp.load_file(path).map(Some),
+        _ => return Err(RispError::Reason(format!("unknown command {}, try :help", name))),
+    }
+    Ok(None)
 }
 
-fn env_for_lambda(params: Rc<RispExp>, arg_forms: &[RispExp], outer_env: &mut RispEnv) -> Result<RispEnv, RispError> {
-    let ks = parse_list_of_symbol_strings(params)?;
-    if ks.len() != arg_forms.len() {
-      return Err(
-        RispError::Reason(
-          format!("expected {} arguments, got {}", ks.len(), arg_forms.len())
-        )
-      );
+// 出错时输出到stderr, 返回非0退出码
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let capabilities = if args.sandbox { Capabilities::NONE } else { Capabilities::ALL };
+    let mut risp = Interpreter::with_capabilities(capabilities);
+    if args.vm {
+        risp.set_backend(Backend::Vm);
     }
-    let vs = eval_forms(arg_forms, outer_env)?;
-    let mut data: HashMap<String, RispExp> = HashMap::new();
-    for (k, v) in ks.iter().zip(vs.iter()) {
-        data.insert(k.clone(), v.clone());
+    for dir in &args.module_path {
+        risp.add_module_path(dir);
     }
-    Ok(
-      RispEnv {
-        data,
-        outer: Some(Box::new(outer_env.clone())),
-      }
-    )
-}
-
-// if def fn
-fn eval_built_in_form(exp: &RispExp, arg_forms: &[RispExp], env: &mut RispEnv) -> Option<Result<RispExp, RispError>> {
-    match exp {
-        RispExp::Symbol(s) => {
-            match s.as_ref() {
-                "if" => Some(eval_if_args(arg_forms, env)),
-                "def" => Some(eval_def_args(arg_forms, env)),
-                "fn" => Some(eval_lambda_args(arg_forms)),
-                _ => None,
-            }
-        },
-        _ => None,
+    if let Some(dir) = args.file.as_ref().and_then(|path| path.parent()) {
+        risp.add_module_path(dir);
     }
-}
-
-// 执行list中所有计算
-// + 1 2 3
-fn eval_forms(arg_forms: &[RispExp], env: &mut RispEnv) -> Result<Vec<RispExp>, RispError> {
-    arg_forms.iter().map(|x| eval(x, env)).collect()
-}
-
-// 执行计算
-fn eval(exp: &RispExp, env: &mut RispEnv) -> Result<RispExp, RispError> {
-    match exp {
-        RispExp::Boolean(_) => Ok(exp.clone()),
-        RispExp::Number(_) => Ok(exp.clone()),
-        RispExp::Symbol(op) => {
-            env_get(op, env).ok_or(RispError::Reason(format!("unexpected symbol k='{}'", op)))
-        },
-        RispExp::Func(_) => Err(RispError::Reason("unexpected function".to_string())),
-        RispExp::Lambda(_) => Err(RispError::Reason("unexpected form".to_string())),
-        RispExp::List(list) => {
-            // + 1 2
-            let first_form = list.first().ok_or(RispError::Reason("expected a non empty list".to_string()))?;
-            let arg_forms = &list[1..];
-            match eval_built_in_form(first_form, arg_forms, env) {
-                Some(res) => res,
-                None => {
-                    let first_eval = eval(first_form, env)?;
-                    match first_eval {
-                        RispExp::Func(f) => {
-                            f(&eval_forms(arg_forms, env)?)
-                        },
-                        RispExp::Lambda(lambda) => {
-                            let new_env = &mut env_for_lambda(lambda.params_exp, arg_forms, env)?;
-                            eval(&lambda.body_exp, new_env)
-                        }
-                        _ => Err(RispError::Reason("first form must be a function".to_string()))
-                    }
-                },
-            }
-        },
+    if let Some(paths) = env::var_os("RISP_PATH") {
+        env::split_paths(&paths).for_each(|dir| risp.add_module_path(dir));
+    }
+    risp.set_limits(args.limits());
+    let res = if let Some(path) = &args.file {
+        risp.load_file(path).map(|_| ()).map_err(|e| e.to_string())
+    } else if let Some(src) = &args.eval {
+        risp.eval_all(src)
+            .map(|res| println!("{}", pretty(&res, PRINT_WIDTH)))
+            .map_err(|e| e.to_string())
+    } else {
+        repl(&mut risp, &args).map_err(|e| e.to_string())
+    };
+    match res {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
     }
 }
 
-fn parse_eval(expr: &str, env: &mut RispEnv) -> Result<RispExp, RispError> {
-    let tokens: Vec<String> = tokenize(expr);
-    println!("tokens: {:?}", tokens);
-    let (exp, _) = parse(&tokens)?;
-    println!("exp: {:?}", exp);
-    eval(&exp, env)
-}
-
-fn main() {
-    let mut env = init_env();
-    let mut input = String::new();
+fn repl(risp: &mut Interpreter, args: &Args) -> rustyline::Result<()> {
+    let mut rl: Editor<RispHelper, DefaultHistory> = Editor::new()?;
+    rl.set_helper(Some(RispHelper {
+        env: risp.env().clone(),
+    }));
+    let history = history_path();
+    if let Some(path) = &history {
+        let _ = rl.load_history(path);
+    }
     loop {
-        print!("> ");
-        let _ = io::stdout().flush();
-        io::stdin()
-            .read_line(&mut input)
-            .expect("Failed to read line");
-        if input.len() == 0 {
-            break;
+        let input = match read_input(&mut rl) {
+            Ok(input) => input,
+            // Ctrl-C丢弃当前输入
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e),
+        };
+        let src = input.trim();
+        if src.is_empty() {
+            continue;
         }
-        match parse_eval(&input, &mut env) {
-            Ok(res) => println!("// 🔥 => {}", res),
-            Err(e) => match e {
-                RispError::Reason(msg) => println!("// 🙀 => {}", msg),
-            },
+        let _ = rl.add_history_entry(src);
+        risp.set_limits(args.limits());
+        let res = if src.starts_with(':') {
+            run_command(src, risp)
+        } else {
+            parse_eval(src, risp, args.quiet).map(Some)
+        };
+        match res {
+            Ok(Some(res)) => println!("// 🔥 => {}", pretty(&res, PRINT_WIDTH)),
+            Ok(None) => {}
+            Err(e) => println!("// 🙀 => {}", e),
         }
-        input.clear();
     }
-}
\ No newline at end of file
+    if let Some(path) = &history {
+        let _ = rl.save_history(path);
+    }
+    Ok(())
+}
@@ -0,0 +1,204 @@
+use std::cmp::Ordering;
+
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+
+use crate::{RispError, RispExp};
+
+// 数值运算: 整数精确计算, 溢出时转为大整数, 有浮点数参与时按浮点数计算
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Num {
+    Int(i64),
+    Big(BigInt),
+    Float(f64),
+}
+
+impl Num {
+    // 布尔值按0和1计算
+    pub fn from_exp(exp: &RispExp) -> Result<Num, RispError> {
+        match exp {
+            RispExp::Int(n) => Ok(Num::Int(*n)),
+            RispExp::BigInt(n) => Ok(Num::Big(n.clone())),
+            RispExp::Number(n) => Ok(Num::Float(*n)),
+            RispExp::Boolean(b) => Ok(Num::Int(*b as i64)),
+            _ => Err(RispError::Reason(format!("expected a number, got {}", exp))),
+        }
+    }
+
+    pub fn from_list(args: &[RispExp]) -> Result<Vec<Num>, RispError> {
+        args.iter().map(Num::from_exp).collect()
+    }
+
+    // 能放进i64的大整数转回普通整数
+    pub fn into_exp(self) -> RispExp {
+        match self {
+            Num::Int(n) => RispExp::Int(n),
+            Num::Big(n) => match n.to_i64() {
+                Some(n) => RispExp::Int(n),
+                None => RispExp::BigInt(n),
+            },
+            Num::Float(n) => RispExp::Number(n),
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Num::Int(n) => *n as f64,
+            Num::Big(n) => n.to_f64().unwrap_or(f64::NAN),
+            Num::Float(n) => *n,
+        }
+    }
+
+    fn to_big(&self) -> BigInt {
+        match self {
+            Num::Int(n) => BigInt::from(*n),
+            Num::Big(n) => n.clone(),
+            Num::Float(n) => BigInt::from(*n as i64),
+        }
+    }
+
+    fn arith(
+        self,
+        other: Num,
+        int_op: fn(i64, i64) -> Option<i64>,
+        big_op: fn(BigInt, BigInt) -> BigInt,
+        float_op: fn(f64, f64) -> f64,
+    ) -> Num {
+        match (self, other) {
+            (Num::Float(a), b) => Num::Float(float_op(a, b.to_f64())),
+            (a, Num::Float(b)) => Num::Float(float_op(a.to_f64(), b)),
+            (Num::Int(a), Num::Int(b)) => match int_op(a, b) {
+                Some(n) => Num::Int(n),
+                None => Num::Big(big_op(BigInt::from(a), BigInt::from(b))),
+            },
+            (a, b) => Num::Big(big_op(a.to_big(), b.to_big())),
+        }
+    }
+
+    pub fn add(self, other: Num) -> Num {
+        self.arith(other, i64::checked_add, |a, b| a + b, |a, b| a + b)
+    }
+
+    pub fn sub(self, other: Num) -> Num {
+        self.arith(other, i64::checked_sub, |a, b| a - b, |a, b| a - b)
+    }
+
+    pub fn mul(self, other: Num) -> Num {
+        self.arith(other, i64::checked_mul, |a, b| a * b, |a, b| a * b)
+    }
+
+    // 整数能整除时结果为整数, 否则按浮点数计算
+    pub fn div(self, other: Num) -> Result<Num, RispError> {
+        match (&self, &other) {
+            (Num::Float(_), _) | (_, Num::Float(_)) => Ok(Num::Float(self.to_f64() / other.to_f64())),
+            (Num::Int(_), Num::Int(0)) => Err(division_by_zero()),
+            // i64::MIN / -1 溢出, 余数按wrapping计算
+            (Num::Int(a), Num::Int(b)) if a.wrapping_rem(*b) == 0 => Ok(match a.checked_div(*b) {
+                Some(n) => Num::Int(n),
+                None => Num::Big(BigInt::from(*a) / BigInt::from(*b)),
+            }),
+            (a, b) => {
+                let (a, b) = (a.to_big(), b.to_big());
+                if b.is_zero() {
+                    return Err(division_by_zero());
+                }
+                if (&a % &b).is_zero() {
+                    return Ok(Num::Big(a / b));
+                }
+                Ok(Num::Float(self.to_f64() / other.to_f64()))
+            }
+        }
+    }
+
+    // 结果的符号与除数相同, (mod -7 3) -> 2
+    pub fn modulo(self, other: Num) -> Result<Num, RispError> {
+        match (self, other) {
+            (Num::Float(a), b) => Ok(Num::Float(floor_mod(a % b.to_f64(), b.to_f64(), 0.0))),
+            (a, Num::Float(b)) => Ok(Num::Float(floor_mod(a.to_f64() % b, b, 0.0))),
+            (_, Num::Int(0)) => Err(division_by_zero()),
+            (Num::Int(a), Num::Int(b)) => match a.checked_rem(b) {
+                Some(r) => Ok(Num::Int(floor_mod(r, b, 0))),
+                // i64::MIN % -1
+                None => Ok(Num::Int(0)),
+            },
+            (a, b) => {
+                let (a, b) = (a.to_big(), b.to_big());
+                if b.is_zero() {
+                    return Err(division_by_zero());
+                }
+                Ok(Num::Big(floor_mod(&a % &b, b, BigInt::zero())))
+            }
+        }
+    }
+
+    pub fn compare(&self, other: &Num) -> Option<Ordering> {
+        match (self, other) {
+            (Num::Int(a), Num::Int(b)) => Some(a.cmp(b)),
+            (Num::Float(_), _) | (_, Num::Float(_)) => self.to_f64().partial_cmp(&other.to_f64()),
+            (a, b) => Some(a.to_big().cmp(&b.to_big())),
+        }
+    }
+}
+
+// 余数与除数符号不同时加上除数
+fn floor_mod<T>(r: T, b: T, zero: T) -> T
+where
+    T: PartialOrd + std::ops::Add<Output = T>,
+{
+    if r != zero && (r < zero) != (b < zero) {
+        r + b
+    } else {
+        r
+    }
+}
+
+fn division_by_zero() -> RispError {
+    RispError::Reason("division by zero".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(s: &str) -> Num {
+        Num::Big(s.parse().unwrap())
+    }
+
+    #[test]
+    fn overflow_should_promote_to_bigint() {
+        let max = Num::Int(i64::MAX);
+        assert_eq!(max.clone().add(Num::Int(1)), big("9223372036854775808"));
+        assert_eq!(Num::Int(i64::MIN).sub(Num::Int(1)), big("-9223372036854775809"));
+        assert_eq!(max.clone().mul(max.clone()), big("85070591730234615847396907784232501249"));
+        assert_eq!(Num::Int(i64::MIN).div(Num::Int(-1)).unwrap(), big("9223372036854775808"));
+        assert_eq!(max.add(Num::Float(1.0)), Num::Float(i64::MAX as f64 + 1.0));
+    }
+
+    #[test]
+    fn bigints_should_demote_when_they_fit() {
+        let n = big("9223372036854775808").sub(Num::Int(1));
+        assert_eq!(n.into_exp(), RispExp::Int(i64::MAX));
+        let n = big("18446744073709551616").div(big("9223372036854775808")).unwrap();
+        assert_eq!(n.into_exp(), RispExp::Int(2));
+        assert_eq!(big("18446744073709551616").modulo(Num::Int(-3)).unwrap().into_exp(), RispExp::Int(-2));
+    }
+
+    #[test]
+    fn division_should_stay_exact_when_possible() {
+        assert_eq!(Num::Int(6).div(Num::Int(3)).unwrap(), Num::Int(2));
+        assert_eq!(Num::Int(7).div(Num::Int(2)).unwrap(), Num::Float(3.5));
+        assert_eq!(big("18446744073709551617").div(Num::Int(2)).unwrap(), Num::Float(9223372036854775808.5));
+        for b in [Num::Int(0), big("0")] {
+            assert_eq!(Num::Int(1).div(b.clone()).unwrap_err().message(), "division by zero");
+            assert_eq!(big("18446744073709551616").modulo(b).unwrap_err().message(), "division by zero");
+        }
+        assert_eq!(Num::Int(-7).modulo(Num::Int(3)).unwrap(), Num::Int(2));
+    }
+
+    #[test]
+    fn compare_should_mix_integers_bigints_and_floats() {
+        assert_eq!(big("9223372036854775808").compare(&Num::Int(i64::MAX)), Some(Ordering::Greater));
+        assert_eq!(Num::Int(1).compare(&Num::Float(1.0)), Some(Ordering::Equal));
+        assert_eq!(Num::Int(1).compare(&Num::Float(f64::NAN)), None);
+    }
+}
@@ -0,0 +1,313 @@
+use num_bigint::BigInt;
+
+use crate::{
+    exp::CHAR_NAMES,
+    lexer::{tokenize, Token, TokenKind},
+    RispError, RispExp, RispMap, Span,
+};
+
+// 解析结果, spans与exp的结构一致, 用于定位求值错误
+#[derive(Debug, Clone)]
+pub struct Form {
+    pub exp: RispExp,
+    pub spans: SpanTree,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SpanTree {
+    pub span: Span,
+    pub children: Vec<SpanTree>,
+}
+
+impl SpanTree {
+    // 按先序查找与target相同的子表达式的位置
+    pub fn find(&self, exp: &RispExp, target: &RispExp) -> Option<Span> {
+        if exp == target {
+            return Some(self.span);
+        }
+        match exp {
+            RispExp::List(list) => self.find_children(list.iter(), target),
+            RispExp::Vector(v) => self.find_children(v.iter(), target),
+            RispExp::Map(m) => self.find_children(m.iter().flat_map(|(k, v)| [k, v]), target),
+            RispExp::Set(s) => self.find_children(s.iter(), target),
+            _ => None,
+        }
+    }
+
+    fn find_children<'a>(&self, items: impl Iterator<Item = &'a RispExp>, target: &RispExp) -> Option<Span> {
+        items.zip(&self.children).find_map(|(exp, tree)| tree.find(exp, target))
+    }
+}
+
+impl Form {
+    // 为求值错误补充位置, 找不到出错的表达式时使用整个form的位置; 超出资源限制的错误保持不变
+    pub fn locate(&self, err: RispError, src: &str) -> RispError {
+        let span = match &err {
+            RispError::Spanned { .. } | RispError::Limit(_) => return err,
+            RispError::InForm(_, target) => self.spans.find(&self.exp, target).unwrap_or(self.spans.span),
+            RispError::Reason(_) | RispError::Raised(..) => self.spans.span,
+        };
+        RispError::spanned(err.message(), span, src)
+    }
+}
+
+// 表达式最多嵌套的层数, 解析和展开宏时超过则返回RispError::Limit, 避免递归导致栈溢出
+pub const MAX_NESTING: usize = 256;
+
+pub(crate) fn nesting_error() -> RispError {
+    RispError::Limit(format!("maximum nesting depth {} exceeded", MAX_NESTING))
+}
+
+// (+ 1 2 3), 'x -> (quote x)
+pub fn parse<'a>(tokens: &'a [Token], src: &str) -> Result<(Form, &'a [Token]), RispError> {
+    parse_nested(tokens, src, 0)
+}
+
+// depth为外层括号和引号的层数
+fn parse_nested<'a>(tokens: &'a [Token], src: &str, depth: usize) -> Result<(Form, &'a [Token]), RispError> {
+    let (first, rest) = tokens
+        .split_first()
+        .ok_or(RispError::Reason("could not get token".to_string()))?;
+    if depth >= MAX_NESTING && first.kind != TokenKind::Str && first.kind != TokenKind::Atom {
+        return Err(nesting_error());
+    }
+    let depth = depth + 1;
+    match first.kind {
+        TokenKind::LParen => parse_expression(first, rest, TokenKind::RParen, src, depth),
+        TokenKind::LBracket | TokenKind::LBrace | TokenKind::HashBrace => parse_collection(first, rest, src, depth),
+        TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace => Err(RispError::spanned(
+            format!("unexpected token {}", first.text),
+            first.span,
+            src,
+        )),
+        TokenKind::Quote => parse_quoted("quote", first, rest, src, depth),
+        TokenKind::Quasiquote => parse_quoted("quasiquote", first, rest, src, depth),
+        TokenKind::Unquote => parse_quoted("unquote", first, rest, src, depth),
+        TokenKind::UnquoteSplicing => parse_quoted("unquote-splicing", first, rest, src, depth),
+        TokenKind::Str | TokenKind::Atom => {
+            let exp = parse_atom(&first.text).map_err(|e| RispError::spanned(e.message(), first.span, src))?;
+            let spans = SpanTree { span: first.span, children: vec![] };
+            Ok((Form { exp, spans }, rest))
+        }
+    }
+}
+
+// 解析src中所有的表达式
+pub fn read(src: &str) -> Result<Vec<Form>, RispError> {
+    let tokens = tokenize(src)?;
+    let mut forms = vec![];
+    let mut rest = tokens.as_slice();
+    while !rest.is_empty() {
+        let (form, next) = parse(rest, src)?;
+        forms.push(form);
+        rest = next;
+    }
+    Ok(forms)
+}
+
+// [1 2], {:a 1}, #{1 2}, 元素与list一样解析, 再转为对应的类型
+fn parse_collection<'a>(
+    open: &Token,
+    tokens: &'a [Token],
+    src: &str,
+    depth: usize,
+) -> Result<(Form, &'a [Token]), RispError> {
+    let close = match open.kind {
+        TokenKind::LBracket => TokenKind::RBracket,
+        _ => TokenKind::RBrace,
+    };
+    let (form, rest) = parse_expression(open, tokens, close, src, depth)?;
+    let RispExp::List(list) = &form.exp else {
+        unreachable!("parse_expression returns a list");
+    };
+    let exp = match open.kind {
+        TokenKind::LBracket => RispExp::Vector(list.iter().cloned().collect()),
+        TokenKind::LBrace => RispMap::from_pairs(list.to_vec())
+            .map(RispExp::Map)
+            .map_err(|e| RispError::spanned(e.message(), form.spans.span, src))?,
+        _ => RispExp::Set(list.iter().cloned().collect()),
+    };
+    Ok((Form { exp, spans: form.spans }, rest))
+}
+
+// + 1 2 3), 括号需要成对
+fn parse_expression<'a>(
+    open: &Token,
+    tokens: &'a [Token],
+    close: TokenKind,
+    src: &str,
+    depth: usize,
+) -> Result<(Form, &'a [Token]), RispError> {
+    let mut ret = Vec::new();
+    let mut children = Vec::new();
+    let mut cur = tokens;
+    loop {
+        match cur.first() {
+            Some(token) if token.kind == close => {
+                let span = Span { end: token.span.end, ..open.span };
+                let form = Form { exp: RispExp::List(ret.into()), spans: SpanTree { span, children } };
+                return Ok((form, &cur[1..]));
+            }
+            Some(_) => {}
+            None => return Err(RispError::spanned(format!("unclosed {}", open.text), open.span, src)),
+        }
+        let (form, rest) = parse_nested(cur, src, depth)?;
+        ret.push(form.exp);
+        children.push(form.spans);
+        cur = rest;
+    }
+}
+
+fn parse_quoted<'a>(
+    name: &str,
+    quote: &Token,
+    tokens: &'a [Token],
+    src: &str,
+    depth: usize,
+) -> Result<(Form, &'a [Token]), RispError> {
+    if tokens.is_empty() {
+        return Err(RispError::spanned(format!("expected form after {}", quote.text), quote.span, src));
+    }
+    let (form, rest) = parse_nested(tokens, src, depth)?;
+    let span = Span { end: form.spans.span.end, ..quote.span };
+    let exp = RispExp::List(vec![RispExp::Symbol(name.to_string()), form.exp].into());
+    let children = vec![SpanTree { span: quote.span, children: vec![] }, form.spans];
+    Ok((Form { exp, spans: SpanTree { span, children } }, rest))
+}
+
+// + 1 2 3
+fn parse_atom(token: &str) -> Result<RispExp, RispError> {
+    if token.starts_with('"') {
+        return parse_str(token).map(RispExp::Str);
+    }
+    if let Some(name) = token.strip_prefix('\\') {
+        return parse_char(name).map(RispExp::Char);
+    }
+    let exp = match token {
+        "true" => RispExp::Boolean(true),
+        "false" => RispExp::Boolean(false),
+        "nil" => RispExp::Nil,
+        _ if token.len() > 1 && token.starts_with(':') => RispExp::Keyword(token[1..].to_string()),
+        _ => match parse_number(token) {
+            Some(n) => n,
+            None => RispExp::Symbol(token.to_string()),
+        },
+    };
+    Ok(exp)
+}
+
+// 整数超出i64时为大整数, 其次按浮点数解析; inf、nan等是符号, 特殊的浮点数写作##Inf ##-Inf ##NaN
+pub(crate) fn parse_number(token: &str) -> Option<RispExp> {
+    if let Ok(n) = token.parse::<i64>() {
+        return Some(RispExp::Int(n));
+    }
+    let digits = token.strip_prefix(['-', '+']).unwrap_or(token);
+    if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+        return token.parse::<BigInt>().ok().map(RispExp::BigInt);
+    }
+    let n = match token {
+        "##Inf" => f64::INFINITY,
+        "##-Inf" => f64::NEG_INFINITY,
+        "##NaN" => f64::NAN,
+        _ if digits.starts_with(|c: char| c.is_ascii_digit() || c == '.') => token.parse::<f64>().ok()?,
+        _ => return None,
+    };
+    Some(RispExp::Number(n))
+}
+
+// "a\"b" -> a"b
+fn parse_str(token: &str) -> Result<String, RispError> {
+    let unterminated = || RispError::Reason(format!("unterminated string {}", token));
+    let mut chars = token[1..].chars();
+    let mut s = String::new();
+    loop {
+        match chars.next().ok_or_else(unterminated)? {
+            '"' => break,
+            '\\' => {
+                let c = match chars.next().ok_or_else(unterminated)? {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    '0' => '\0',
+                    c @ ('"' | '\\') => c,
+                    c => return Err(RispError::Reason(format!("unknown escape \\{} in string", c))),
+                };
+                s.push(c);
+            }
+            c => s.push(c),
+        }
+    }
+    match chars.next() {
+        Some(_) => Err(RispError::Reason(format!("unexpected token after string {}", token))),
+        None => Ok(s),
+    }
+}
+
+// \a \space \newline
+fn parse_char(name: &str) -> Result<char, RispError> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => CHAR_NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, c)| *c)
+            .ok_or(RispError::Reason(format!("unknown char \\{}", name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_should_limit_nesting() {
+        let nested = |n: usize, open: &str, close: &str| format!("{}{}", open.repeat(n), close.repeat(n));
+        assert!(read(&nested(MAX_NESTING, "[", "]")).is_ok());
+        for src in [nested(2000, "[", "]"), format!("(quote {})", nested(3000, "(", ")")), "'".repeat(1000) + "x"] {
+            assert_eq!(read(&src).unwrap_err(), nesting_error());
+        }
+    }
+
+    #[test]
+    fn read_should_locate_syntax_errors() {
+        let cases = [
+            ("(+ 1 2))", "unexpected token )", (1, 8)),
+            ("(+ 1\n  (* 2", "unclosed (", (2, 3)),
+            ("[1 2)", "unexpected token )", (1, 5)),
+            ("{:a 1 :b}", "expected an even number of forms for a map", (1, 1)),
+            ("(a 'b '", "expected form after '", (1, 7)),
+            ("\\nope", "unknown char \\nope", (1, 1)),
+        ];
+        for (src, msg, (line, col)) in cases {
+            let err = read(src).unwrap_err();
+            assert_eq!(err.message(), msg, "{}", src);
+            let span = err.span().unwrap();
+            assert_eq!((span.line, span.col), (line, col), "{}", src);
+        }
+    }
+
+    #[test]
+    fn read_should_record_spans_of_nested_forms() {
+        let src = "(def x\n  '[1 \"a\"])";
+        let form = read(src).unwrap().remove(0);
+        assert_eq!(form.exp.to_string(), "(def x (quote [1 \"a\"]))");
+        let quoted = &form.spans.children[2];
+        assert_eq!((quoted.span.line, quoted.span.col), (2, 3));
+        assert_eq!(&src[quoted.span.start..quoted.span.end], "'[1 \"a\"]");
+        let string = &quoted.children[1].children[1];
+        assert_eq!(&src[string.span.start..string.span.end], "\"a\"");
+    }
+
+    #[test]
+    fn special_floats_should_read_back() {
+        for n in [f64::INFINITY, f64::NEG_INFINITY, 0.5, -2.0] {
+            assert_eq!(read(&RispExp::Number(n).to_string()).unwrap()[0].exp, RispExp::Number(n));
+        }
+        let nan = &read(&RispExp::Number(f64::NAN).to_string()).unwrap()[0].exp;
+        assert!(matches!(nan, RispExp::Number(n) if n.is_nan()));
+        for name in ["inf", "-inf", "nan", "infinity"] {
+            assert_eq!(read(name).unwrap()[0].exp, RispExp::Symbol(name.to_string()));
+        }
+    }
+}
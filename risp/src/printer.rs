@@ -0,0 +1,53 @@
+use crate::RispExp;
+
+// 格式化输出, 列表超过width时第一个元素之后每个元素一行, 缩进两个空格
+pub fn pretty(exp: &RispExp, width: usize) -> String {
+    let mut out = String::new();
+    write_pretty(exp, 0, width, &mut out);
+    out
+}
+
+fn flat(exp: &RispExp) -> String {
+    match exp {
+        RispExp::List(list) => format!("({})", list.iter().map(flat).collect::<Vec<_>>().join(" ")),
+        _ => exp.to_string(),
+    }
+}
+
+fn write_pretty(exp: &RispExp, indent: usize, width: usize, out: &mut String) {
+    let line = flat(exp);
+    match exp {
+        RispExp::List(list) if !list.is_empty() && indent + line.chars().count() > width => {
+            let mut items = list.iter();
+            out.push('(');
+            if let Some(first) = items.next() {
+                write_pretty(first, indent + 1, width, out);
+            }
+            for item in items {
+                out.push('\n');
+                out.push_str(&" ".repeat(indent + 2));
+                write_pretty(item, indent + 2, width, out);
+            }
+            out.push(')');
+        }
+        _ => out.push_str(&line),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read;
+
+    fn pretty_str(src: &str, width: usize) -> String {
+        pretty(&read(src).unwrap()[0].exp, width)
+    }
+
+    #[test]
+    fn pretty_should_break_long_lists() {
+        assert_eq!(pretty_str("(a (b c) [1 2])", 80), "(a (b c) [1 2])");
+        // 放得下的子列表保持在一行, 向量不换行
+        let expected = "(define\n  (f x)\n  (if\n    (> x 0)\n    [x x x x x x]\n    (- x)))";
+        assert_eq!(pretty_str("(define (f x) (if (> x 0) [x x x x x x] (- x)))", 16), expected);
+    }
+}
@@ -0,0 +1,46 @@
+use std::{ops::BitOr, time::Instant};
+
+// 求值的资源限制, None为不限制; 由Interpreter::set_limits设置, 之后的求值共用这些额度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Limits {
+    // 求值步数: 树遍历求值每次调用eval, 虚拟机每条指令
+    pub fuel: Option<u64>,
+    pub deadline: Option<Instant>,
+    // 设置之后新增的存活托管对象数: cons单元、向量/映射/集合、作用域、调用帧和闭包
+    pub max_objects: Option<usize>,
+    // 内置函数返回的单个字符串或大整数的字节数, 字符串和大整数不是托管对象, 不计入max_objects
+    pub max_value_bytes: Option<usize>,
+}
+
+// 允许使用的宿主能力, 不可信的代码使用NONE
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities(u8);
+
+impl Capabilities {
+    pub const NONE: Self = Self(0);
+    // load、read-file、write-file
+    pub const FILE: Self = Self(1);
+    // getenv、exit
+    pub const PROCESS: Self = Self(1 << 1);
+    // println
+    pub const STDOUT: Self = Self(1 << 2);
+    pub const ALL: Self = Self(Self::FILE.0 | Self::PROCESS.0 | Self::STDOUT.0);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
@@ -0,0 +1,441 @@
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use crate::{
+    compiler::{compile, Op, Proto},
+    eval::{apply_lambda, eval, grow_stack, is_anonymous, macroexpand_all, record_error_stack, run_try},
+    gc::{Gc, Trace},
+    EvalContext, RispEnv, RispError, RispExp, RispMap,
+};
+
+// 函数调用的局部变量, parent为定义函数时的调用帧; 函数体中def的变量在执行def之前为None
+#[derive(Debug, Default)]
+struct Frame {
+    slots: RefCell<Vec<Option<RispExp>>>,
+    parent: Option<Gc<Frame>>,
+}
+
+// parent链不会成环, 清空slot即可打断闭包与调用帧的循环引用
+impl Trace for Frame {
+    fn trace(&self, visit: &mut dyn FnMut(usize)) {
+        if let Ok(slots) = self.slots.try_borrow() {
+            slots.iter().flatten().for_each(|v| v.trace(visit));
+        }
+        if let Some(p) = &self.parent {
+            visit(p.id());
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut slots) = self.slots.try_borrow_mut() {
+            slots.clear();
+        }
+    }
+}
+
+impl Frame {
+    fn outer(frame: &Gc<Self>, depth: u16) -> Result<&Gc<Self>, RispError> {
+        let mut frame = frame;
+        for _ in 0..depth {
+            frame = frame
+                .parent
+                .as_ref()
+                .ok_or(RispError::Reason("invalid outer variable".to_string()))?;
+        }
+        Ok(frame)
+    }
+}
+
+// 虚拟机中的闭包, 捕获定义时的调用帧和全局环境
+pub struct Closure {
+    proto: Rc<Proto>,
+    frame: Gc<Frame>,
+    env: RispEnv,
+}
+
+impl Trace for Closure {
+    fn trace(&self, visit: &mut dyn FnMut(usize)) {
+        visit(self.frame.id());
+        visit(self.env.id());
+    }
+}
+
+impl Closure {
+    pub fn name(&self) -> &str {
+        &self.proto.name
+    }
+
+    // 按参数个数绑定实参, 其余slot未绑定
+    fn bind(&self, mut args: Vec<RispExp>) -> Result<Gc<Frame>, RispError> {
+        let proto = &self.proto;
+        if proto.rest {
+            if args.len() < proto.params {
+                return Err(RispError::Reason(format!(
+                    "expected at least {} arguments, got {}",
+                    proto.params,
+                    args.len()
+                )));
+            }
+            let rest = args.split_off(proto.params);
+            args.push(RispExp::List(rest.into()));
+        } else if args.len() != proto.params {
+            return Err(RispError::Reason(format!(
+                "expected {} arguments, got {}",
+                proto.params,
+                args.len()
+            )));
+        }
+        let mut slots: Vec<_> = args.into_iter().map(Some).collect();
+        slots.resize(proto.slots, None);
+        Ok(Gc::new(Frame {
+            slots: RefCell::new(slots),
+            parent: Some(self.frame.clone()),
+        }))
+    }
+}
+
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Closure").field("name", &self.proto.name).finish()
+    }
+}
+
+struct CallFrame {
+    proto: Rc<Proto>,
+    ip: usize,
+    frame: Gc<Frame>,
+    // 调用前栈的长度, 返回时恢复
+    base: usize,
+}
+
+// 基于栈的虚拟机, 闭包之间的调用不增加rust调用栈
+struct Vm<'a> {
+    env: &'a RispEnv,
+    ctx: &'a mut EvalContext,
+    stack: Vec<RispExp>,
+    frames: Vec<CallFrame>,
+}
+
+// 编译并执行顶层表达式, 顶层的begin逐个编译执行, 使前面定义的宏可以在后面使用
+pub fn run(exp: &RispExp, env: &RispEnv, ctx: &mut EvalContext) -> Result<RispExp, RispError> {
+    if let RispExp::List(list) = exp {
+        if let (Some(RispExp::Symbol(s)), forms) = (list.first(), list.rest()) {
+            if s == "begin" && !forms.is_empty() {
+                let mut res = RispExp::Nil;
+                for form in &forms {
+                    let form = macroexpand_all(form, env, ctx)?;
+                    res = run(&form, env, ctx)?;
+                }
+                return Ok(res);
+            }
+        }
+    }
+    let proto = compile(exp)?;
+    let frame = CallFrame {
+        proto,
+        ip: 0,
+        frame: Gc::new(Frame::default()),
+        base: 0,
+    };
+    Vm::new(env, ctx).execute(frame)
+}
+
+// 树遍历求值器和try在新的虚拟机中调用闭包; 每次进入都占用rust调用栈, 与嵌套的eval一样计入递归深度
+pub(crate) fn call_closure(closure: &Closure, args: Vec<RispExp>, ctx: &mut EvalContext) -> Result<RispExp, RispError> {
+    if ctx.depth >= ctx.max_depth {
+        return Err(ctx.depth_error());
+    }
+    let frame = closure.bind(args)?;
+    let frame = CallFrame {
+        proto: closure.proto.clone(),
+        ip: 0,
+        frame,
+        base: 0,
+    };
+    ctx.depth += 1;
+    ctx.stack.push(closure.proto.name.clone());
+    let res = grow_stack(|| Vm::new(&closure.env, ctx).execute(frame));
+    ctx.stack.pop();
+    ctx.depth -= 1;
+    res
+}
+
+impl<'a> Vm<'a> {
+    fn new(env: &'a RispEnv, ctx: &'a mut EvalContext) -> Self {
+        Self {
+            env,
+            ctx,
+            stack: vec![],
+            frames: vec![],
+        }
+    }
+
+    // 出错时用出错指令对应的表达式标记错误, 并恢复递归深度和调用栈
+    fn execute(&mut self, frame: CallFrame) -> Result<RispExp, RispError> {
+        let (depth, len) = (self.ctx.depth, self.ctx.stack.len());
+        self.frames.push(frame);
+        self.run().map_err(|e| {
+            record_error_stack(self.ctx);
+            self.ctx.depth = depth;
+            self.ctx.stack.truncate(len);
+            match self.frames.last() {
+                Some(cf) => match cf.proto.chunk.form_at(cf.ip.saturating_sub(1)) {
+                    Some(form) => e.in_form(form),
+                    None => e,
+                },
+                None => e,
+            }
+        })
+    }
+
+    fn run(&mut self) -> Result<RispExp, RispError> {
+        loop {
+            if !self.ctx.tick() {
+                return Err(self.ctx.limit_error());
+            }
+            let cf = self.frames.last_mut().expect("call frame");
+            let op = cf.proto.chunk.code[cf.ip];
+            cf.ip += 1;
+            match op {
+                Op::Const(i) => self.stack.push(cf.proto.chunk.consts[i as usize].clone()),
+                Op::Nil => self.stack.push(RispExp::Nil),
+                Op::Local(i) => {
+                    let value = cf.frame.slots.borrow()[i as usize].clone();
+                    let value = match value {
+                        Some(v) => v,
+                        None => unbound_local(self.env, cf)?,
+                    };
+                    self.stack.push(value);
+                }
+                Op::SetLocal(i) => {
+                    let value = self.stack.last().cloned().unwrap_or(RispExp::Nil);
+                    cf.frame.slots.borrow_mut()[i as usize] = Some(value);
+                }
+                Op::Outer(depth, i) => {
+                    let value = Frame::outer(&cf.frame, depth)?.slots.borrow()[i as usize].clone();
+                    let value = match value {
+                        Some(v) => v,
+                        None => unbound_local(self.env, cf)?,
+                    };
+                    self.stack.push(value);
+                }
+                Op::SetOuter(depth, i) => {
+                    let value = self.stack.last().cloned().unwrap_or(RispExp::Nil);
+                    Frame::outer(&cf.frame, depth)?.slots.borrow_mut()[i as usize] = Some(value);
+                }
+                Op::Global(i) => {
+                    let name = symbol(&cf.proto.chunk.consts[i as usize]);
+                    let value = self
+                        .env
+                        .get(name)
+                        .ok_or_else(|| RispError::Reason(format!("unexpected symbol k='{}'", name)))?;
+                    self.stack.push(value);
+                }
+                Op::DefGlobal(i) => {
+                    let value = self.stack.pop().unwrap_or(RispExp::Nil);
+                    self.env.insert(symbol(&cf.proto.chunk.consts[i as usize]), value);
+                }
+                Op::SetGlobal(i) => {
+                    let value = self.stack.last().cloned().unwrap_or(RispExp::Nil);
+                    self.env.set(symbol(&cf.proto.chunk.consts[i as usize]), value)?;
+                }
+                Op::Closure(i) => {
+                    let closure = Closure {
+                        proto: cf.proto.chunk.protos[i as usize].clone(),
+                        frame: cf.frame.clone(),
+                        env: self.env.clone(),
+                    };
+                    self.stack.push(RispExp::Closure(Gc::new(closure)));
+                }
+                Op::Call(argc) => self.call(argc as usize, false)?,
+                Op::TailCall(argc) => self.call(argc as usize, true)?,
+                Op::Jump(target) => cf.ip = target as usize,
+                Op::JumpIfFalse(target) => {
+                    if !self.pop().is_truthy() {
+                        self.frames.last_mut().expect("call frame").ip = target as usize;
+                    }
+                }
+                Op::Pop => {
+                    self.pop();
+                }
+                Op::Return => {
+                    let res = self.pop();
+                    let cf = self.frames.pop().expect("call frame");
+                    self.stack.truncate(cf.base);
+                    if self.frames.is_empty() {
+                        return Ok(res);
+                    }
+                    self.ctx.depth -= 1;
+                    self.ctx.stack.pop();
+                    self.stack.push(res);
+                }
+                Op::List(n) => {
+                    let items = self.stack.split_off(self.stack.len() - n as usize);
+                    self.stack.push(RispExp::List(items.into()));
+                }
+                Op::Concat(n) => {
+                    let mut list = vec![];
+                    for part in self.stack.split_off(self.stack.len() - n as usize) {
+                        match part {
+                            RispExp::List(items) => list.extend(items.iter().cloned()),
+                            RispExp::Nil => {}
+                            exp => {
+                                return Err(RispError::Reason(format!(
+                                    "unquote-splicing expected a list, got {}",
+                                    exp
+                                )))
+                            }
+                        }
+                    }
+                    self.stack.push(RispExp::List(list.into()));
+                }
+                Op::Vector | Op::Map | Op::Set => {
+                    let items = match self.pop() {
+                        RispExp::List(list) => list.to_vec(),
+                        _ => vec![],
+                    };
+                    let coll = match op {
+                        Op::Vector => RispExp::Vector(items.into()),
+                        Op::Set => RispExp::Set(items.into_iter().collect()),
+                        _ => RispExp::Map(RispMap::from_pairs(items)?),
+                    };
+                    self.stack.push(coll);
+                }
+                Op::Try => self.call_try()?,
+                Op::Tree(i) => {
+                    let exp = cf.proto.chunk.consts[i as usize].clone();
+                    let value = eval(&exp, self.env, self.ctx)?;
+                    self.stack.push(value);
+                }
+            }
+        }
+    }
+
+    fn pop(&mut self) -> RispExp {
+        self.stack.pop().unwrap_or(RispExp::Nil)
+    }
+
+    // body、catch和finally的闭包在新的虚拟机中执行, body出错时由run_try调用catch
+    fn call_try(&mut self) -> Result<(), RispError> {
+        let finally = self.pop();
+        let handler = self.pop();
+        let body = self.pop();
+        let handler = match handler {
+            RispExp::Closure(c) => Some(move |err, ctx: &mut EvalContext| call_closure(&c, vec![err], ctx)),
+            _ => None,
+        };
+        let finally = match finally {
+            RispExp::Closure(c) => Some(move |ctx: &mut EvalContext| call_closure(&c, vec![], ctx)),
+            _ => None,
+        };
+        // 与树遍历求值中try表达式本身的eval对应, try和body各计入一层递归深度
+        if self.ctx.depth >= self.ctx.max_depth {
+            return Err(self.ctx.depth_error());
+        }
+        self.ctx.depth += 1;
+        let res = match body {
+            RispExp::Closure(c) => run_try(self.ctx, |ctx| call_closure(&c, vec![], ctx), handler, finally),
+            _ => Ok(RispExp::Nil),
+        };
+        self.ctx.depth -= 1;
+        self.stack.push(res?);
+        Ok(())
+    }
+
+    // 栈上依次为函数和参数, 调用闭包时压入新的调用帧, 尾调用时替换当前调用帧
+    fn call(&mut self, argc: usize, tail: bool) -> Result<(), RispError> {
+        let callee = self.stack.len() - argc - 1;
+        match &self.stack[callee] {
+            RispExp::Closure(closure) => {
+                let closure = closure.clone();
+                let args = self.stack.split_off(callee + 1);
+                self.stack.pop();
+                let frame = closure.bind(args)?;
+                if tail {
+                    match self.ctx.stack.last_mut() {
+                        Some(name) if !is_anonymous(&closure.proto.name) => *name = closure.proto.name.clone(),
+                        _ => {}
+                    }
+                    let cf = self.frames.last_mut().expect("call frame");
+                    self.stack.truncate(cf.base);
+                    *cf = CallFrame {
+                        proto: closure.proto.clone(),
+                        ip: 0,
+                        frame,
+                        base: cf.base,
+                    };
+                    return Ok(());
+                }
+                if self.ctx.depth >= self.ctx.max_depth {
+                    return Err(self.ctx.depth_error());
+                }
+                self.ctx.depth += 1;
+                self.ctx.stack.push(closure.proto.name.clone());
+                self.frames.push(CallFrame {
+                    proto: closure.proto.clone(),
+                    ip: 0,
+                    frame,
+                    base: callee,
+                });
+            }
+            RispExp::Func(f) => {
+                let f = f.clone();
+                let res = f.call(&self.stack[callee + 1..])?;
+                self.ctx.check_value(&res)?;
+                self.stack.truncate(callee);
+                self.stack.push(res);
+            }
+            RispExp::Lambda(lambda) => {
+                let lambda = lambda.clone();
+                let args = self.stack.split_off(callee + 1);
+                self.stack.pop();
+                let res = apply_lambda(&lambda, args, self.ctx)?;
+                self.stack.push(res);
+            }
+            RispExp::Macro(_) => {
+                return Err(RispError::Reason(
+                    "macro must be defined before the top level form using it".to_string(),
+                ))
+            }
+            _ => return Err(RispError::Reason("first form must be a function".to_string())),
+        }
+        Ok(())
+    }
+}
+
+// 编译时保证全局变量的常量为符号
+fn symbol(exp: &RispExp) -> &str {
+    match exp {
+        RispExp::Symbol(s) => s,
+        _ => "",
+    }
+}
+
+// 读取还未执行def的局部变量时与树遍历求值一样查找全局变量, 名字取自正在执行的符号
+fn unbound_local(env: &RispEnv, cf: &CallFrame) -> Result<RispExp, RispError> {
+    let name = cf.proto.chunk.form_at(cf.ip - 1).map_or("", symbol);
+    env.get(name).ok_or_else(|| RispError::Reason(format!("unexpected symbol k='{}'", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{init_env, read};
+
+    #[test]
+    fn tail_calls_should_reuse_call_frames() {
+        let (env, mut ctx) = (init_env(), EvalContext::new(100));
+        let src = "
+            (def even? (fn (n) (if (= n 0) true (odd? (- n 1)))))
+            (def odd? (fn (n) (if (= n 0) false (begin nil (even? (- n 1))))))
+            (def sum (fn (n) (if (= n 0) 0 (+ n (sum (- n 1))))))";
+        for form in read(src).unwrap() {
+            run(&form.exp, &env, &mut ctx).unwrap();
+        }
+        let call = |src: &str, ctx: &mut EvalContext| run(&read(src).unwrap()[0].exp, &env, ctx);
+        assert_eq!(call("(even? 10001)", &mut ctx).unwrap(), RispExp::Boolean(false));
+        assert_eq!(call("(sum 99)", &mut ctx).unwrap(), RispExp::Int(4950));
+        let err = call("(sum 1000)", &mut ctx).unwrap_err();
+        assert_eq!(err.message(), "maximum recursion depth 100 exceeded");
+        assert_eq!(ctx.depth(), 0);
+    }
+}
@@ -0,0 +1,60 @@
+use risp::{Backend, Interpreter};
+
+// 同一段程序在树遍历求值和虚拟机中的结果(或错误)应当相同
+const PROGRAMS: &[&str] = &[
+    // 数值: 整数溢出转为大整数, 整除保持整数, 浮点数保留小数点
+    "[(+ 9223372036854775807 1) (* 3037000500 3037000500) (/ 6 3) (/ 7 2) (mod -7 3) (- 0.5 0.5) (/ 1.0 0)]",
+    "(< 1 2.5 9223372036854775808)",
+    // 字符串、字符和nil
+    r#"[(str "a" \b 1 nil) (string->number "12") (string->number "x") \newline]"#,
+    // 闭包共享作用域, set!修改定义处的值
+    "(defn counter () (def n 0) (fn () (set! n (+ n 1)))) (def c (counter)) (c) (c) [(c) ((counter))]",
+    "(def x 1) (defn get-x () x) (let [x 2] [x (get-x)])",
+    // 函数中的def在整个函数体中可见, 执行之前未绑定
+    r#"(defn f (n) (def ev? (fn (n) (if (= n 0) true (od? (- n 1)))))
+                  (def od? (fn (n) (if (= n 0) false (ev? (- n 1)))))
+                  (ev? n))
+       (f 10)"#,
+    "(defn k () (if true 1 (def z 2)) z) (k)",
+    "(def g 1) (defn h () (def before g) (def g 2) [before g]) [(h) g]",
+    // 尾调用和可变参数
+    "(defn loop (n acc) (if (= n 0) acc (loop (- n 1) (+ acc n)))) (loop 10000 0)",
+    "(defn f (a & rest) [a rest]) [(f 1) (f 1 2 3)]",
+    "(deep 900)",
+    // 宏和quasiquote
+    "(defmacro unless (test & body) `(if ~test nil (begin ~@body))) [(unless false 1 2) (unless true 1)]",
+    "(def xs '(2 3)) `(1 ~@xs [~(car xs)] {:k ~@(cdr xs)})",
+    "[(and 1 2) (and 1 nil 2) (or nil false) (or nil 3) (cond false 1 (= 1 1) 2)]",
+    // 标准库
+    "[(map (fn (x) (* x x)) (range 0 5)) (filter (fn (x) (> x 2)) '(1 2 3 4)) (reduce + 0 '(1 2 3))]",
+    // 向量、映射和集合
+    "(def m {:b 1 :a 2}) [(assoc m :c 3 :b 4) (dissoc m :b) (get m :a) (keys m) (assoc {1 :int} 1.0 :float)]",
+    "[(conj #{1 2} 2 3) (disj #{1 2 3} 2) (contains? #{[1 2]} [1 2]) (= {:a #{1 2}} {:a #{2 1}}) (conj [1] 2)]",
+    // 异常
+    r#"(defn check (n) (if (< n 0) (raise {:kind :bad :message "neg"}) n)) (defn f (n) (+ 1 (check n)))
+       (try (f -1) (catch e [(get e :kind) (get e :message) (get e :stack)]))"#,
+    "(def log '()) (try (try (/ 1 0) (finally (set! log (cons 1 log)))) (catch e (cons (get e :message) log)))",
+    // 错误信息和位置
+    "(defn f (x) (+ x undefined-name)) (f 1)",
+    "(car 1)",
+    "((fn (a b) a) 1)",
+    "(raise {:kind :custom :message \"boom\"})",
+    "(deep 5000)",
+];
+
+fn run(backend: Backend, src: &str) -> Result<String, String> {
+    let mut risp = Interpreter::new();
+    risp.set_backend(backend);
+    risp.eval_str("(defn deep (n) (if (= n 0) 0 (+ 1 (deep (- n 1)))))").unwrap();
+    risp.eval_all(src).map(|v| v.to_string()).map_err(|e| e.to_string())
+}
+
+// 在默认大小的测试线程中执行, 递归求值按需扩展栈, 树遍历求值也可以达到默认的最大递归深度
+#[test]
+fn tree_and_vm_should_agree() {
+    for src in PROGRAMS {
+        let tree = run(Backend::Tree, src);
+        let vm = run(Backend::Vm, src);
+        assert_eq!(tree, vm, "{}", src);
+    }
+}
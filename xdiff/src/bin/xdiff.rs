@@ -1,7 +1,7 @@
 use anyhow::{Result, Ok, anyhow};
 use clap::Parser;
 use dialoguer::{theme::ColorfulTheme, Input, MultiSelect};
-use xdiff::{cli::{Args, Action, RunArgs}, process_error_output, DiffConfig, LoadConfig, highlight_text, RequestProfile, ExtraArgs, DiffProfile, ResponseProfile};
+use xdiff::{cli::{Args, Action, RunArgs}, process_error_output, DiffConfig, LoadConfig, highlight_text, RequestProfile, ExtraArgs, DiffProfile, ResponseProfile, CookieJar};
 use std::io::Write;
 
 #[tokio::main]
@@ -25,19 +25,45 @@ async fn main() -> Result<()> {
     let result: std::result::Result<(), anyhow::Error> = match args.action {
         Action::Run(args) => run(args).await,
         Action::Parse => parse().await,
-        _ => panic!("Not implemented"),
+        Action::Accept(args) => accept(args).await,
     };
     process_error_output(result)
 }
 
 async fn run(args: RunArgs) -> anyhow::Result<()> {
     let config_file = args.config.unwrap_or_else(|| "./xdiff.yaml".to_string());
-    let config = DiffConfig::load_yaml(&config_file).await?;
-    let profile = config.get_profile(&args.profile).ok_or_else(|| {
+    let mut config = DiffConfig::load_yaml(&config_file).await?;
+    let mut profile = config.profiles.remove(&args.profile).ok_or_else(|| {
         anyhow!("Profile {} not found in config file {}", args.profile, config_file)
     })?;
+    let jar = load_cookie_jar(args.cookie_jar).await?;
+    if let Some(jar) = &jar {
+        profile = profile.with_cookie_jar(jar.clone());
+    }
     let extra_args = args.extra_params.into();
+    if profile.matrix.is_some() {
+        let clean = run_matrix(&profile, &extra_args).await?;
+        save_cookie_jar(jar).await?;
+        // 有行出现差异或请求失败时以非0退出
+        if !clean {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if profile.expected.is_some() {
+        let report = profile.diff_baseline(&extra_args).await?;
+        let mut stdout = std::io::stdout().lock();
+        write!(stdout, "{}", report)?;
+        stdout.flush()?;
+        save_cookie_jar(jar).await?;
+        // 有新的变化或预期的变化消失时以非0退出, CI中可以直接发现回归
+        if !report.is_clean() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
     let output = profile.diff(&extra_args).await?;
+    save_cookie_jar(jar).await?;
 
     // 终端高亮输出
     let stdout = std::io::stdout();
@@ -53,6 +79,60 @@ async fn run(args: RunArgs) -> anyhow::Result<()> {
     Ok(())
 }
 
+async fn accept(args: RunArgs) -> anyhow::Result<()> {
+    let config_file = args.config.unwrap_or_else(|| "./xdiff.yaml".to_string());
+    let mut config = DiffConfig::load_yaml(&config_file).await?;
+    let mut profile = config.profiles.remove(&args.profile).ok_or_else(|| {
+        anyhow!("Profile {} not found in config file {}", args.profile, config_file)
+    })?;
+    let jar = load_cookie_jar(args.cookie_jar).await?;
+    if let Some(jar) = &jar {
+        profile = profile.with_cookie_jar(jar.clone());
+    }
+    let extra_args = args.extra_params.into();
+    let report = profile.accept(&extra_args).await?;
+    save_cookie_jar(jar).await?;
+
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "accepted changes of profile {}", args.profile)?;
+    write!(stdout, "{}", report)?;
+    Ok(())
+}
+
+async fn load_cookie_jar(path: Option<String>) -> anyhow::Result<Option<CookieJar>> {
+    match path {
+        Some(path) => Ok(Some(CookieJar::load(path).await?)),
+        None => Ok(None),
+    }
+}
+
+async fn save_cookie_jar(jar: Option<CookieJar>) -> anyhow::Result<()> {
+    match jar {
+        Some(jar) => jar.save().await,
+        None => Ok(()),
+    }
+}
+
+// 逐行输出有差异的结果, 最后输出汇总; 返回是否所有行都没有差异且请求成功
+async fn run_matrix(profile: &DiffProfile, extra_args: &ExtraArgs) -> anyhow::Result<bool> {
+    let report = profile.diff_matrix(extra_args).await?;
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+    for result in report.diverged() {
+        writeln!(stdout, "{:=^1$}", format!(" row {}: {} ", result.index, serde_json::Value::Object(result.row.clone())), 80)?;
+        if atty::is(atty::Stream::Stdout) {
+            write!(stdout, "{}", highlight_text(&result.output, "yaml", None)?)?;
+        } else {
+            write!(stdout, "{}", result.output)?;
+        }
+    }
+    write!(stdout, "{}", report)?;
+    stdout.flush()?;
+
+    Ok(report.is_empty())
+}
+
 async fn parse() -> anyhow::Result<()> {
     let default = ColorfulTheme::default();
     let url1:String = Input::with_theme(&default)
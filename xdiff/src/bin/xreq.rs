@@ -5,21 +5,22 @@ use std::io::Write as _;
 use anyhow::{Ok, Context};
 use dialoguer::{theme::ColorfulTheme, Input};
 use xdiff::{
-    cli::{Action, Args, RunArgs},
+    cli::{HistoryAction, HistoryArgs, ReqAction, ReqArgs, RunArgs, ServeArgs},
     process_error_output,
     RequestConfig,
-    LoadConfig, get_status_text, get_header_text, get_body_text, highlight_text, RequestProfile,
+    LoadConfig, highlight_text, RequestProfile, History, HistoryEntry, diff_text, MockConfig, CookieJar, ExtraArgs,
 };
 
 use clap::Parser;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
+    let args = ReqArgs::parse();
     let result = match args.action {
-        Action::Run(args) => run(args).await,
-        Action::Parse => parse().await,
-        _ => panic!("Not implemented"),
+        ReqAction::Run(args) => run(args).await,
+        ReqAction::Parse => parse().await,
+        ReqAction::History(args) => history(args).await,
+        ReqAction::Serve(args) => serve(args).await,
     };
     process_error_output(result)
 }
@@ -35,24 +36,103 @@ async fn run(args: RunArgs) -> anyhow::Result<()> {
         )
     })?;
     let extra_args = args.extra_params.into();
-    let url = profile.get_url(&extra_args)?;
-    let res = profile.send(&extra_args).await?.into_inner();
+    let jar = load_cookie_jar(args.cookie_jar).await?;
+    let entry = HistoryEntry::send_with_jar(&args.profile, profile, &extra_args, jar.as_ref()).await?;
+    save_cookie_jar(jar).await?;
+    // 保存到历史记录
+    let entry = History::open_default()?.append(entry).await?;
+    print_entry(&entry)
+}
+
+async fn history(args: HistoryArgs) -> anyhow::Result<()> {
+    let history = History::open_default()?;
+    match args.action {
+        HistoryAction::List { search, limit } => {
+            let entries = history.search(search.as_deref(), limit).await?;
+            let mut output = String::new();
+            for e in entries {
+                writeln!(
+                    &mut output,
+                    "{:<6}{:<22}{:<6}{:>8}ms  {:<16}{}",
+                    e.id,
+                    e.time_text(),
+                    e.status,
+                    e.elapsed,
+                    e.profile,
+                    e.url
+                )?;
+            }
+            write!(std::io::stdout().lock(), "{}", output)?;
+            Ok(())
+        }
+        HistoryAction::Show { id } => print_entry(&history.get(id).await?),
+        HistoryAction::Rerun { id, extra_params, cookie_jar } => {
+            let old = history.get(id).await?;
+            let extra: ExtraArgs = extra_params.into();
+            let mut args = old.args.clone();
+            args.headers.extend(extra.headers);
+            args.query.extend(extra.query);
+            args.body.extend(extra.body);
+            let jar = load_cookie_jar(cookie_jar).await?;
+            let entry = HistoryEntry::send_with_jar(&old.profile, &old.request, &args, jar.as_ref()).await?;
+            save_cookie_jar(jar).await?;
+            let entry = history.append(entry).await?;
+            print_entry(&entry)
+        }
+        HistoryAction::Diff { id1, id2 } => {
+            let text1 = history.get(id1).await?.get_text()?;
+            let text2 = history.get(id2).await?.get_text()?;
+            let (output, _output1, _output2) = diff_text(&text1, &text2)?;
+
+            let stdout = std::io::stdout();
+            let mut stdout = stdout.lock();
+            if atty::is(atty::Stream::Stdout) {
+                write!(stdout, "{}", highlight_text(&output, "yaml", None)?)?;
+            } else {
+                write!(stdout, "{}", output)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn serve(args: ServeArgs) -> anyhow::Result<()> {
+    let config_file = args.config.unwrap_or_else(|| "./mock.yaml".to_string());
+    let config = MockConfig::load_yaml(&config_file).await.context(format!("load {} error, please check -c xx/mock.yaml", config_file))?;
+    let listener = std::net::TcpListener::bind((args.host.as_str(), args.port))?;
+    println!("mock server listening on http://{}", listener.local_addr()?);
+    xdiff::serve(config, listener).await
+}
+
+async fn load_cookie_jar(path: Option<String>) -> anyhow::Result<Option<CookieJar>> {
+    match path {
+        Some(path) => Ok(Some(CookieJar::load(path).await?)),
+        None => Ok(None),
+    }
+}
+
+async fn save_cookie_jar(jar: Option<CookieJar>) -> anyhow::Result<()> {
+    match jar {
+        Some(jar) => jar.save().await,
+        None => Ok(()),
+    }
+}
 
+fn print_entry(entry: &HistoryEntry) -> anyhow::Result<()> {
     let mut output = String::new();
-    let status = get_status_text(&res)?;
-    let headers = get_header_text(&res, &[])?;
-    let body = get_body_text(res, &[]).await?;
     if atty::is(atty::Stream::Stdout) {
-        writeln!(&mut output, "Url: {}\n", url)?;
-        write!(&mut output, "{}", status)?;
+        writeln!(&mut output, "Url: {}\n", entry.url)?;
+        writeln!(&mut output, "{} {}", entry.version, entry.status_text())?;
+        let head = entry.get_head_text()?;
+        let headers = head.split_once('\n').map_or("", |(_, h)| h);
         write!(
             &mut output,
             "{}",
-            highlight_text(&headers, "yaml", Some("InspiredGitHub"))?
+            highlight_text(headers, "yaml", Some("InspiredGitHub"))?
         )?;
-        write!(&mut output, "{}", highlight_text(&body, "json", None)?)?;
+        write!(&mut output, "{}", highlight_text(&entry.body, "json", None)?)?;
     } else {
-        write!(&mut output, "{}", body)?;
+        write!(&mut output, "{}", entry.body)?;
     }
 
     let stdout = std::io::stdout();
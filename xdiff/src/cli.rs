@@ -10,12 +10,34 @@ pub struct Args {
 }
 
 #[derive(Subcommand, Debug)]
-#[non_exhaustive] // 还有其他扩展，实现需要加"- => {}"配置
 pub enum Action {
     /// 根据给定的profile对比两个api返回的差异
     Run(RunArgs),
     /// 解析URLs并生成profile
     Parse,
+    /// 将profile当前的差异写入expected文件, 作为已接受的变化
+    Accept(RunArgs),
+}
+
+/// 发送profile中配置的http请求并展示响应
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+pub struct ReqArgs {
+    #[clap(subcommand)]
+    pub action: ReqAction,
+}
+
+// xreq的子命令, 与xdiff的Action分开, 每个二进制只暴露自己支持的命令
+#[derive(Subcommand, Debug)]
+pub enum ReqAction {
+    /// 根据给定的profile发送请求并展示响应
+    Run(RunArgs),
+    /// 解析URL并生成profile
+    Parse,
+    /// 查看、搜索、重新执行和对比xreq的历史请求
+    History(HistoryArgs),
+    /// 启动本地mock服务, 按配置返回预设的响应
+    Serve(ServeArgs),
 }
 
 #[derive(Parser, Debug)]
@@ -34,6 +56,58 @@ pub struct RunArgs {
     /// 配置文件
     #[clap(short, long)]
     pub config: Option<String>,
+
+    /// cookie jar文件, 请求时带上其中的cookie, 结束后保存响应中的set-cookie
+    #[clap(long)]
+    pub cookie_jar: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+pub struct ServeArgs {
+    /// mock配置文件
+    #[clap(short, long)]
+    pub config: Option<String>,
+
+    /// 监听地址
+    #[clap(long, default_value = "127.0.0.1")]
+    pub host: String,
+
+    /// 监听端口
+    #[clap(short, long, default_value_t = 8080)]
+    pub port: u16,
+}
+
+#[derive(Parser, Debug)]
+pub struct HistoryArgs {
+    #[clap(subcommand)]
+    pub action: HistoryAction,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HistoryAction {
+    /// 列出历史请求, 最新的在前
+    List {
+        /// 按profile、url、状态码或body中的关键字搜索
+        #[clap(short, long)]
+        search: Option<String>,
+        /// 最多展示的条数
+        #[clap(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// 展示历史请求的响应
+    Show { id: u64 },
+    /// 重新执行历史请求
+    Rerun {
+        id: u64,
+        /// 追加的参数, 历史记录中不保存认证header, 需要时用`-e %authorization=...`重新提供
+        #[clap(short, long, value_parser = perse_key_val, number_of_values = 1)]
+        extra_params: Vec<KeyVal>,
+        /// cookie jar文件
+        #[clap(long)]
+        cookie_jar: Option<String>,
+    },
+    /// 对比两个历史请求的响应
+    Diff { id1: u64, id2: u64 },
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -114,4 +188,14 @@ mod cli_tests {
             ]
         )
     }
+
+    #[test]
+    fn each_binary_should_only_accept_its_own_actions() {
+        assert!(matches!(Args::try_parse_from(["xdiff", "accept", "-p", "todo"]).unwrap().action, Action::Accept(_)));
+        assert!(Args::try_parse_from(["xdiff", "history", "list"]).is_err());
+        assert!(Args::try_parse_from(["xdiff", "serve"]).is_err());
+
+        assert!(matches!(ReqArgs::try_parse_from(["xreq", "serve"]).unwrap().action, ReqAction::Serve(_)));
+        assert!(ReqArgs::try_parse_from(["xreq", "accept", "-p", "todo"]).is_err());
+    }
 }
@@ -0,0 +1,200 @@
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+use anyhow::{anyhow, Context};
+use brotli_decompressor::DecompressorWriter;
+use flate2::write::{GzDecoder, ZlibDecoder};
+use reqwest::{header, Response};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// 检测二进制内容时检查的字节数
+const SNIFF_SIZE: usize = 8192;
+
+// 按content-type判断为二进制的前缀
+const BINARY_CONTENT_TYPES: [&str; 9] = [
+    "image/",
+    "audio/",
+    "video/",
+    "font/",
+    "application/octet-stream",
+    "application/pdf",
+    "application/zip",
+    "application/gzip",
+    "application/x-protobuf",
+];
+
+// body读取规则: 按块读取并计算摘要, 最多保留max_size字节(解压后)
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+pub struct BodyOptions {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub max_size: Option<usize>,
+    #[serde(skip_serializing_if = "is_hash", default)]
+    pub oversize: Oversize,
+    // 是否按content-encoding解压gzip、deflate、br, 默认解压
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub decompress: Option<bool>,
+}
+
+// 超过max_size时的处理: hash只比较大小和摘要, truncate比较截断后的文本
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Oversize {
+    #[default]
+    Hash,
+    Truncate,
+}
+
+fn is_hash(oversize: &Oversize) -> bool {
+    *oversize == Oversize::Hash
+}
+
+impl BodyOptions {
+    pub fn new(max_size: Option<usize>, oversize: Oversize, decompress: Option<bool>) -> Self {
+        Self { max_size, oversize, decompress }
+    }
+
+    pub(crate) fn validate(&self) -> anyhow::Result<()> {
+        if self.max_size == Some(0) {
+            return Err(anyhow!("body max_size must be greater than 0"));
+        }
+        Ok(())
+    }
+
+    // 逐块读取body, 不会在内存中保留超过max_size的内容
+    pub(crate) async fn read(&self, mut res: Response) -> anyhow::Result<RawBody> {
+        let encoding = match self.decompress.unwrap_or(true) {
+            true => res
+                .headers()
+                .get(header::CONTENT_ENCODING)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.trim().to_ascii_lowercase()),
+            false => None,
+        };
+        let sink = BodySink::new(self.max_size);
+        let mut decoder = match encoding.as_deref() {
+            Some("gzip" | "x-gzip") => BodyDecoder::Gzip(GzDecoder::new(sink)),
+            Some("deflate") => BodyDecoder::Deflate(ZlibDecoder::new(sink)),
+            Some("br") => BodyDecoder::Brotli(Box::new(DecompressorWriter::new(sink, 4096))),
+            _ => BodyDecoder::Identity(sink),
+        };
+        while let Some(chunk) = res.chunk().await? {
+            decoder.write_all(&chunk).context("failed to decode body")?;
+        }
+        let sink = decoder.finish().context("failed to decode body")?;
+        Ok(sink.into_body())
+    }
+}
+
+// 读取后的body, data最多为max_size字节, size和digest基于完整内容
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RawBody {
+    pub data: Vec<u8>,
+    pub size: usize,
+    pub digest: String,
+}
+
+impl RawBody {
+    pub fn is_oversize(&self) -> bool {
+        self.size > self.data.len()
+    }
+
+    // content-type为二进制类型, 或内容中有空字节、不是合法的utf8
+    pub fn is_binary(&self, content_type: Option<&str>) -> bool {
+        if content_type.is_some_and(|ct| BINARY_CONTENT_TYPES.iter().any(|b| ct.starts_with(b))) {
+            return true;
+        }
+        let sniff = &self.data[..self.data.len().min(SNIFF_SIZE)];
+        if sniff.contains(&0) {
+            return true;
+        }
+        // 截断处可能是不完整的字符
+        matches!(std::str::from_utf8(&self.data), Err(e) if e.error_len().is_some())
+    }
+
+    pub fn summary(&self, kind: &str) -> String {
+        format!("<{}: {} bytes, sha256: {}>", kind, self.size, self.digest)
+    }
+
+    pub fn truncated_text(&self) -> String {
+        let mut text = String::from_utf8_lossy(&self.data).into_owned();
+        let _ = write!(
+            &mut text,
+            "\n<truncated: {} of {} bytes, sha256: {}>",
+            self.data.len(),
+            self.size,
+            self.digest
+        );
+        text
+    }
+}
+
+// 计算完整内容的摘要, 只保留前max_size字节
+struct BodySink {
+    data: Vec<u8>,
+    size: usize,
+    max_size: Option<usize>,
+    hasher: Sha256,
+}
+
+impl BodySink {
+    fn new(max_size: Option<usize>) -> Self {
+        Self { data: vec![], size: 0, max_size, hasher: Sha256::new() }
+    }
+
+    fn into_body(self) -> RawBody {
+        let digest = self.hasher.finalize().iter().fold(String::new(), |mut s, b| {
+            let _ = write!(&mut s, "{:02x}", b);
+            s
+        });
+        RawBody { data: self.data, size: self.size, digest }
+    }
+}
+
+impl Write for BodySink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.hasher.update(buf);
+        self.size += buf.len();
+        let keep = match self.max_size {
+            Some(max) => max.saturating_sub(self.data.len()).min(buf.len()),
+            None => buf.len(),
+        };
+        self.data.extend_from_slice(&buf[..keep]);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+enum BodyDecoder {
+    Identity(BodySink),
+    Gzip(GzDecoder<BodySink>),
+    Deflate(ZlibDecoder<BodySink>),
+    Brotli(Box<DecompressorWriter<BodySink>>),
+}
+
+impl BodyDecoder {
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match self {
+            BodyDecoder::Identity(w) => w.write_all(buf),
+            BodyDecoder::Gzip(w) => w.write_all(buf),
+            BodyDecoder::Deflate(w) => w.write_all(buf),
+            BodyDecoder::Brotli(w) => w.write_all(buf),
+        }
+    }
+
+    fn finish(self) -> io::Result<BodySink> {
+        match self {
+            BodyDecoder::Identity(w) => Ok(w),
+            BodyDecoder::Gzip(w) => w.finish(),
+            BodyDecoder::Deflate(w) => w.finish(),
+            BodyDecoder::Brotli(mut w) => {
+                w.close()?;
+                w.into_inner()
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "incomplete brotli stream"))
+            }
+        }
+    }
+}
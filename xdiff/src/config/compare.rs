@@ -0,0 +1,164 @@
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+// 数值容差, e: `{ path: data.price, absolute: 0.01 }`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct NumericTolerance {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub absolute: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub relative: Option<f64>,
+}
+
+impl NumericTolerance {
+    pub fn new(path: impl Into<String>, absolute: Option<f64>, relative: Option<f64>) -> Self {
+        Self {
+            path: path.into(),
+            absolute,
+            relative,
+        }
+    }
+
+    // 两个数的差值是否在容差范围内
+    pub fn accept(&self, a: f64, b: f64) -> bool {
+        let delta = (a - b).abs();
+        let absolute = self.absolute.is_some_and(|t| delta <= t);
+        let relative = self
+            .relative
+            .is_some_and(|t| delta <= t * a.abs().max(b.abs()));
+        absolute || relative
+    }
+}
+
+// 数组比较规则，没有sort_by时把数组当做集合(按元素整体排序)
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+pub struct ArrayRule {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub sort_by: Option<String>,
+}
+
+impl ArrayRule {
+    pub fn new(path: impl Into<String>, sort_by: Option<String>) -> Self {
+        Self {
+            path: path.into(),
+            sort_by,
+        }
+    }
+
+    fn sort(&self, value: &mut Value) {
+        if let Value::Array(items) = value {
+            match self.sort_by.as_deref() {
+                Some(key) => items.sort_by(|a, b| compare(lookup(a, key), lookup(b, key))),
+                None => items.sort_by(|a, b| compare(Some(a), Some(b))),
+            }
+        }
+    }
+}
+
+// 在渲染diff之前对两个json应用比较规则
+pub fn apply_rules(
+    v1: &mut Value,
+    v2: &mut Value,
+    tolerances: &[NumericTolerance],
+    arrays: &[ArrayRule],
+) {
+    // 先排序，保证数组元素对齐后再比较数值
+    for rule in arrays {
+        let segments = split_path(&rule.path);
+        for_each_path(v1, &segments, &mut |v| rule.sort(v));
+        for_each_path(v2, &segments, &mut |v| rule.sort(v));
+    }
+    for tolerance in tolerances {
+        for_each_pair(v1, v2, &split_path(&tolerance.path), &mut |a, b| {
+            if let (Some(x), Some(y)) = (a.as_f64(), b.as_f64()) {
+                if x != y && tolerance.accept(x, y) {
+                    *b = a.clone();
+                }
+            }
+        });
+    }
+}
+
+// path 以`.`分隔，`*`匹配数组所有元素或对象所有key, e: `data.items.*.price`
+fn split_path(path: &str) -> Vec<&str> {
+    path.split('.').filter(|s| !s.is_empty()).collect()
+}
+
+fn for_each_path(value: &mut Value, segments: &[&str], f: &mut impl FnMut(&mut Value)) {
+    let Some((first, rest)) = segments.split_first() else {
+        f(value);
+        return;
+    };
+    match (value, *first) {
+        (Value::Array(items), "*") => items.iter_mut().for_each(|v| for_each_path(v, rest, f)),
+        (Value::Object(map), "*") => map.values_mut().for_each(|v| for_each_path(v, rest, f)),
+        (value, key) => {
+            if let Some(v) = child_mut(value, key) {
+                for_each_path(v, rest, f);
+            }
+        }
+    }
+}
+
+// 同时遍历两个json中相同path的值
+fn for_each_pair(
+    v1: &mut Value,
+    v2: &mut Value,
+    segments: &[&str],
+    f: &mut impl FnMut(&mut Value, &mut Value),
+) {
+    let Some((first, rest)) = segments.split_first() else {
+        f(v1, v2);
+        return;
+    };
+    match (v1, v2, *first) {
+        (Value::Array(a), Value::Array(b), "*") => a
+            .iter_mut()
+            .zip(b.iter_mut())
+            .for_each(|(a, b)| for_each_pair(a, b, rest, f)),
+        (Value::Object(a), Value::Object(b), "*") => {
+            for (k, a) in a.iter_mut() {
+                if let Some(b) = b.get_mut(k) {
+                    for_each_pair(a, b, rest, f);
+                }
+            }
+        }
+        (v1, v2, key) => {
+            if let (Some(a), Some(b)) = (child_mut(v1, key), child_mut(v2, key)) {
+                for_each_pair(a, b, rest, f);
+            }
+        }
+    }
+}
+
+fn child_mut<'a>(value: &'a mut Value, key: &str) -> Option<&'a mut Value> {
+    match value {
+        Value::Object(map) => map.get_mut(key),
+        Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get_mut(i)),
+        _ => None,
+    }
+}
+
+fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    split_path(path).into_iter().try_fold(value, |v, key| match v {
+        Value::Object(map) => map.get(key),
+        Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => None,
+    })
+}
+
+// 数字按数值排序，其余按序列化后的字符串排序
+fn compare(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    match (a, b) {
+        (Some(Value::Number(x)), Some(Value::Number(y))) => x
+            .as_f64()
+            .partial_cmp(&y.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Some(x), Some(y)) => x.to_string().cmp(&y.to_string()),
+        (x, y) => x.is_some().cmp(&y.is_some()),
+    }
+}
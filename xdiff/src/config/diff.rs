@@ -1,10 +1,12 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 use anyhow::{Ok, Context};
 use serde::{Deserialize, Serialize};
 
-use crate::{ExtraArgs, diff_text};
-use super::{LoadConfig, RequestProfile, ValidateConfig};
+use crate::{ExtraArgs, diff_text, CookieJar, DiffReport, BaselineReport};
+use super::{LoadConfig, RequestProfile, ValidateConfig, ArrayRule, BodyOptions, NumericTolerance, ResponseBody};
+use super::matrix::{render_request, Matrix, MatrixReport, MatrixResult, MatrixRow};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DiffConfig {
@@ -35,54 +37,203 @@ impl ValidateConfig for DiffConfig {
   }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct DiffProfile {
     pub req1: RequestProfile,
     pub req2: RequestProfile,
     #[serde(skip_serializing_if = "is_default", default)]
     pub res: ResponseProfile,
+    // 参数矩阵, 每一行生成一次对比
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub matrix: Option<Matrix>,
+    // 已接受的差异文件, 由`xdiff accept`生成
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub expected: Option<PathBuf>,
+    // 两个请求共用的cookie jar, 不从配置文件读取
+    #[serde(skip)]
+    pub cookie_jar: Option<CookieJar>,
 }
 
 impl DiffProfile {
     pub fn new(req1: RequestProfile, req2: RequestProfile, res: ResponseProfile) -> Self {
-        Self { req1, req2, res }
+        Self { req1, req2, res, matrix: None, expected: None, cookie_jar: None }
     }
 
-    pub async fn diff(&self, extra_args: &ExtraArgs) -> anyhow::Result<String> {
-        let res1 = self.req1.send(extra_args).await?;
-        let res2 = self.req2.send(extra_args).await?;
+    pub fn with_expected(mut self, expected: impl Into<PathBuf>) -> Self {
+        self.expected = Some(expected.into());
+        self
+    }
+
+    pub fn with_matrix(mut self, matrix: Matrix) -> Self {
+        self.matrix = Some(matrix);
+        self
+    }
+
+    pub fn with_cookie_jar(mut self, jar: CookieJar) -> Self {
+        self.cookie_jar = Some(jar);
+        self
+    }
 
-        let text1 = res1.get_text(&self.res).await?;
-        let text2 = res2.get_text(&self.res).await?;
+    pub async fn diff(&self, extra_args: &ExtraArgs) -> anyhow::Result<String> {
+        let [head1, body1, head2, body2] = self.get_texts(extra_args).await?;
+        let text1 = format!("{}{}", head1, body1);
+        let text2 = format!("{}{}", head2, body2);
 
         // 对比两个文本
        let (output, _output1, _output2)  = diff_text(&text1, &text2)?;
        
        Ok(output)
     }
+
+    // 返回结构化的对比结果, 方便在测试中断言
+    pub async fn diff_report(&self, extra_args: &ExtraArgs) -> anyhow::Result<DiffReport> {
+        let [head1, body1, head2, body2] = self.get_texts(extra_args).await?;
+        Ok(DiffReport::new(&head1, &body1, &head2, &body2))
+    }
+
+    // 与已接受的差异比较, 区分预期的、新的和已解决的变化
+    pub async fn diff_baseline(&self, extra_args: &ExtraArgs) -> anyhow::Result<BaselineReport> {
+        let expected = self.load_expected().await?;
+        let report = self.diff_report(extra_args).await?;
+        Ok(report.classify(&expected))
+    }
+
+    // 将当前的差异写入expected文件
+    pub async fn accept(&self, extra_args: &ExtraArgs) -> anyhow::Result<DiffReport> {
+        let path = self.expected_path()?;
+        let report = self.diff_report(extra_args).await?;
+        tokio::fs::write(path, serde_yaml::to_string(&report)?)
+            .await
+            .with_context(|| format!("failed to write expected file {}", path.display()))?;
+        Ok(report)
+    }
+
+    // 文件不存在时视为没有已接受的差异
+    async fn load_expected(&self) -> anyhow::Result<DiffReport> {
+        let path = self.expected_path()?;
+        match tokio::fs::read_to_string(path).await {
+            std::result::Result::Ok(content) => serde_yaml::from_str(&content)
+                .with_context(|| format!("failed to parse expected file {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(DiffReport::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn expected_path(&self) -> anyhow::Result<&PathBuf> {
+        self.expected
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("profile has no expected file"))
+    }
+
+    // 按matrix中的每一行替换请求参数后对比, 行号从1开始
+    pub async fn diff_matrix(&self, extra_args: &ExtraArgs) -> anyhow::Result<MatrixReport> {
+        let matrix = self.matrix.as_ref().ok_or_else(|| anyhow::anyhow!("profile has no matrix"))?;
+        let mut results = vec![];
+        for (i, row) in matrix.load_rows().await?.into_iter().enumerate() {
+            let index = i + 1;
+            // 单行渲染或请求失败时记录在该行, 继续对比其他行
+            let (report, output, error) = self
+                .diff_row(&row, extra_args)
+                .await
+                .map(|(report, output)| (report, output, None))
+                .unwrap_or_else(|e| (DiffReport::default(), String::new(), Some(format!("{:#}", e))));
+            results.push(MatrixResult { index, row, report, output, error });
+        }
+        Ok(MatrixReport { results })
+    }
+
+    async fn diff_row(&self, row: &MatrixRow, extra_args: &ExtraArgs) -> anyhow::Result<(DiffReport, String)> {
+        let req1 = render_request(&self.req1, row)?;
+        let req2 = render_request(&self.req2, row)?;
+        let [head1, body1, head2, body2] = self.get_request_texts(&req1, &req2, extra_args).await?;
+        let report = DiffReport::new(&head1, &body1, &head2, &body2);
+        let (output, _output1, _output2) = diff_text(&format!("{}{}", head1, body1), &format!("{}{}", head2, body2))?;
+        Ok((report, output))
+    }
+
+    async fn get_texts(&self, extra_args: &ExtraArgs) -> anyhow::Result<[String; 4]> {
+        self.get_request_texts(&self.req1, &self.req2, extra_args).await
+    }
+
+    // 发送请求并应用比较规则, 返回两个响应的head和body文本
+    async fn get_request_texts(&self, req1: &RequestProfile, req2: &RequestProfile, extra_args: &ExtraArgs) -> anyhow::Result<[String; 4]> {
+        let res1 = req1.send_with_jar(extra_args, self.cookie_jar.as_ref()).await?;
+        let res2 = req2.send_with_jar(extra_args, self.cookie_jar.as_ref()).await?;
+
+        let (head1, body1) = res1.get_parts(&self.res).await?;
+        let (head2, body2) = res2.get_parts(&self.res).await?;
+        let (body1, body2) = self.res.compare_bodies(body1, body2)?;
+        Ok([head1, body1, head2, body2])
+    }
 }
 
 impl ValidateConfig for DiffProfile {
   fn validate(&self) -> anyhow::Result<()> {
       self.req1.validate().context("req1 failed to validate")?;
       self.req2.validate().context("req2 failed to validate")?;
+      self.res.validate().context("res failed to validate")?;
+      if let Some(matrix) = self.matrix.as_ref() {
+          matrix.validate().context("matrix failed to validate")?;
+          if self.expected.is_some() {
+              return Err(anyhow::anyhow!("matrix and expected can not be used together"));
+          }
+      }
       Ok(())
   }
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Default)]
 pub struct ResponseProfile {
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub skip_headers: Vec<String>,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub skip_body: Vec<String>,
+    // 数值容差规则
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub tolerances: Vec<NumericTolerance>,
+    // 数组排序规则(当做集合或按key排序)
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub arrays: Vec<ArrayRule>,
+    // body大小限制、二进制检测和解压规则
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub body: BodyOptions,
 }
 
 impl ResponseProfile {
     pub fn new(skip_headers: Vec<String>, skip_body: Vec<String>) -> Self {
-        Self { skip_headers, skip_body }
+        Self { skip_headers, skip_body, ..Default::default() }
     }
-    
+
+    // 两个body都是json时应用比较规则，再转为文本
+    pub fn compare_bodies(&self, body1: ResponseBody, body2: ResponseBody) -> anyhow::Result<(String, String)> {
+        match (body1, body2) {
+            (ResponseBody::Json(mut v1), ResponseBody::Json(mut v2)) => {
+                self.normalize(&mut v1, &mut v2);
+                Ok((serde_json::to_string_pretty(&v1)?, serde_json::to_string_pretty(&v2)?))
+            }
+            (body1, body2) => Ok((body1.to_text()?, body2.to_text()?)),
+        }
+    }
+
+    // 应用数值容差和数组排序规则
+    pub fn normalize(&self, v1: &mut serde_json::Value, v2: &mut serde_json::Value) {
+        super::compare::apply_rules(v1, v2, &self.tolerances, &self.arrays);
+    }
+}
+
+impl ValidateConfig for ResponseProfile {
+  fn validate(&self) -> anyhow::Result<()> {
+      self.body.validate()?;
+      for tolerance in &self.tolerances {
+          if tolerance.absolute.is_none() && tolerance.relative.is_none() {
+              return Err(anyhow::anyhow!("tolerance for {} must set absolute or relative", tolerance.path));
+          }
+          if tolerance.absolute.unwrap_or_default() < 0.0 || tolerance.relative.unwrap_or_default() < 0.0 {
+              return Err(anyhow::anyhow!("tolerance for {} must not be negative", tolerance.path));
+          }
+      }
+      Ok(())
+  }
 }
 
 pub fn is_default<T>(value: &T) -> bool
@@ -0,0 +1,84 @@
+use anyhow::anyhow;
+use reqwest::Response;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::{build_response, empty_json_value};
+
+// graphql请求, body参数(`-e @key=value`)会合并到variables
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+pub struct GraphqlRequest {
+    pub query: String,
+    #[serde(skip_serializing_if = "empty_json_value", default)]
+    pub variables: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub operation_name: Option<String>,
+    // 响应中包含errors时不报错，继续对比
+    #[serde(skip_serializing_if = "std::ops::Not::not", default)]
+    pub allow_errors: bool,
+}
+
+impl GraphqlRequest {
+    pub fn new(query: impl Into<String>, variables: Option<Value>) -> Self {
+        Self {
+            query: query.into(),
+            variables,
+            ..Default::default()
+        }
+    }
+
+    // 生成graphql请求body
+    pub fn payload(&self, variables: Value) -> Value {
+        let mut payload = json!({ "query": self.query, "variables": variables });
+        if let Some(name) = &self.operation_name {
+            payload["operationName"] = json!(name);
+        }
+        payload
+    }
+
+    // 检查响应中的errors, 读取后重新构造Response
+    pub async fn check_response(&self, res: Response) -> anyhow::Result<Response> {
+        let status = res.status();
+        let version = res.version();
+        let headers = res.headers().clone();
+        let body = res.bytes().await?;
+
+        if !self.allow_errors {
+            if let Some(errors) = get_errors(&body) {
+                return Err(anyhow!("graphql errors: {}", errors.join("; ")));
+            }
+        }
+        Ok(build_response(status, version, headers, body.to_vec()))
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.query.trim().is_empty() {
+            return Err(anyhow!("graphql query must not be empty"));
+        }
+        if let Some(variables) = self.variables.as_ref() {
+            if !variables.is_object() {
+                return Err(anyhow!(
+                    "graphql variables must be an object but got\n{}",
+                    serde_yaml::to_string(variables)?
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn get_errors(body: &[u8]) -> Option<Vec<String>> {
+    let json: Value = serde_json::from_slice(body).ok()?;
+    let errors = json.get("errors")?.as_array()?;
+    if errors.is_empty() {
+        return None;
+    }
+    let messages = errors
+        .iter()
+        .map(|e| match e.get("message").and_then(Value::as_str) {
+            Some(msg) => msg.to_string(),
+            None => e.to_string(),
+        })
+        .collect();
+    Some(messages)
+}
@@ -0,0 +1,251 @@
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context};
+use prost_reflect::prost::Message;
+use prost_reflect::prost_types::{FileDescriptorProto, FileDescriptorSet};
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor, MethodDescriptor};
+use reqwest::{header::HeaderMap, Response, StatusCode, Version};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio_stream::StreamExt;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::metadata::{MetadataKey, MetadataValue};
+use tonic::transport::{Channel, Endpoint};
+use tonic::Status;
+use tonic_reflection::pb::v1::{
+    server_reflection_client::ServerReflectionClient,
+    server_reflection_request::MessageRequest, server_reflection_response::MessageResponse,
+    ServerReflectionRequest,
+};
+use url::Url;
+
+use super::{build_response, empty_json_value};
+
+// grpc请求，没有配置proto时使用服务反射获取描述
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+pub struct GrpcRequest {
+    // 方法全名, e: `helloworld.Greeter/SayHello`
+    pub method: String,
+    // 本地.proto文件(需要protoc)或protoc生成的descriptor set文件
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub proto: Option<PathBuf>,
+    // proto import路径
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub includes: Vec<PathBuf>,
+    // 请求消息(json格式), body参数(`-e @key=value`)会合并进来
+    #[serde(skip_serializing_if = "empty_json_value", default)]
+    pub message: Option<Value>,
+}
+
+impl GrpcRequest {
+    pub fn new(method: impl Into<String>, proto: Option<PathBuf>, message: Option<Value>) -> Self {
+        Self {
+            method: method.into(),
+            proto,
+            message,
+            ..Default::default()
+        }
+    }
+
+    // 拆分为service和method
+    pub fn service_and_method(&self) -> anyhow::Result<(&str, &str)> {
+        self.method
+            .rsplit_once('/')
+            .filter(|(s, m)| !s.is_empty() && !m.is_empty())
+            .ok_or_else(|| anyhow!("grpc method must be `package.Service/Method` but got {}", self.method))
+    }
+
+    pub fn path(&self) -> anyhow::Result<String> {
+        let (service, method) = self.service_and_method()?;
+        Ok(format!("/{}/{}", service, method))
+    }
+
+    // 发送unary请求，将响应消息转为json的Response
+    pub async fn send(&self, url: &Url, headers: &HeaderMap, message: Value) -> anyhow::Result<Response> {
+        let channel = Endpoint::from_shared(url.to_string())?
+            .connect()
+            .await
+            .with_context(|| format!("failed to connect grpc server {}", url))?;
+        let method = self.method_descriptor(channel.clone()).await?;
+
+        let message = DynamicMessage::deserialize(method.input(), message)
+            .with_context(|| format!("invalid message for {}", method.input().full_name()))?;
+        let mut request = tonic::Request::new(message);
+        for (k, v) in headers.iter() {
+            // content-type由grpc设置
+            if k == reqwest::header::CONTENT_TYPE {
+                continue;
+            }
+            request.metadata_mut().insert(
+                MetadataKey::from_str(k.as_str())?,
+                MetadataValue::try_from(v.as_bytes())?,
+            );
+        }
+
+        let mut client = tonic::client::Grpc::new(channel);
+        client.ready().await?;
+        let path = PathAndQuery::from_str(&self.path()?)?;
+        let response = client
+            .unary(request, path, DynamicCodec(method.output()))
+            .await
+            .map_err(|s| anyhow!("grpc error: {:?} {}", s.code(), s.message()))?;
+
+        let mut headers = HeaderMap::new();
+        for (k, v) in response.metadata().clone().into_headers().iter() {
+            headers.insert(
+                reqwest::header::HeaderName::from_str(k.as_str())?,
+                reqwest::header::HeaderValue::from_bytes(v.as_bytes())?,
+            );
+        }
+        // 响应消息转为json展示
+        headers.insert(
+            reqwest::header::CONTENT_TYPE,
+            reqwest::header::HeaderValue::from_static("application/json"),
+        );
+        let body = serde_json::to_vec(response.get_ref())?;
+        Ok(build_response(StatusCode::OK, Version::HTTP_2, headers, body))
+    }
+
+    async fn method_descriptor(&self, channel: Channel) -> anyhow::Result<MethodDescriptor> {
+        let (service, method) = self.service_and_method()?;
+        let pool = match &self.proto {
+            Some(proto) => self.load_proto(proto).await?,
+            None => reflect_pool(channel, service).await?,
+        };
+        let service_desc = pool
+            .get_service_by_name(service)
+            .ok_or_else(|| anyhow!("grpc service {} not found", service))?;
+        let found = service_desc.methods().find(|m| m.name() == method);
+        found.ok_or_else(|| anyhow!("grpc method {} not found in {}", method, service))
+    }
+
+    // .proto文件通过protoc编译为descriptor set，其他文件直接当做descriptor set解析
+    async fn load_proto(&self, proto: &Path) -> anyhow::Result<DescriptorPool> {
+        if proto.extension() != Some(OsStr::new("proto")) {
+            let bytes = tokio::fs::read(proto).await?;
+            return Ok(DescriptorPool::decode(bytes.as_slice())?);
+        }
+
+        // 每次编译使用单独的临时文件, 同时加载多个profile时互不覆盖, drop时删除
+        let out = tempfile::Builder::new().prefix("xdiff-").suffix(".pb").tempfile()?;
+        let mut cmd = tokio::process::Command::new("protoc");
+        cmd.arg("--include_imports")
+            .arg(format!("--descriptor_set_out={}", out.path().display()));
+        let parent = proto.parent().filter(|p| !p.as_os_str().is_empty());
+        for include in self.includes.iter().map(|p| p.as_path()).chain(parent) {
+            cmd.arg(format!("-I{}", include.display()));
+        }
+        let output = cmd
+            .arg(proto)
+            .output()
+            .await
+            .context("failed to run protoc, please install it or use server reflection")?;
+        if !output.status.success() {
+            return Err(anyhow!("protoc failed: {}", String::from_utf8_lossy(&output.stderr)));
+        }
+        let bytes = tokio::fs::read(out.path()).await?;
+        Ok(DescriptorPool::decode(bytes.as_slice())?)
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.service_and_method()?;
+        if let Some(message) = self.message.as_ref() {
+            if !message.is_object() {
+                return Err(anyhow!(
+                    "grpc message must be an object but got\n{}",
+                    serde_yaml::to_string(message)?
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// 通过服务反射获取service及其依赖的文件描述
+async fn reflect_pool(channel: Channel, service: &str) -> anyhow::Result<DescriptorPool> {
+    let mut client = ServerReflectionClient::new(channel);
+    let mut files: Vec<FileDescriptorProto> = vec![];
+    let mut seen = HashSet::new();
+    let mut requests = vec![MessageRequest::FileContainingSymbol(service.to_string())];
+
+    while let Some(request) = requests.pop() {
+        let request = ServerReflectionRequest {
+            host: String::new(),
+            message_request: Some(request),
+        };
+        let mut stream = client
+            .server_reflection_info(tokio_stream::once(request))
+            .await
+            .context("grpc server reflection is not available")?
+            .into_inner();
+        while let Some(res) = stream.next().await {
+            let res = match res?.message_response {
+                Some(MessageResponse::FileDescriptorResponse(res)) => res,
+                Some(MessageResponse::ErrorResponse(e)) => {
+                    return Err(anyhow!("grpc reflection error: {}", e.error_message))
+                }
+                _ => continue,
+            };
+            for bytes in res.file_descriptor_proto {
+                let file = FileDescriptorProto::decode(bytes.as_slice())?;
+                if seen.insert(file.name().to_string()) {
+                    files.push(file);
+                }
+            }
+        }
+        // 补齐缺失的依赖
+        for dep in files.iter().flat_map(|f| f.dependency.iter()) {
+            if !seen.contains(dep) && !requests.iter().any(|r| matches!(r, MessageRequest::FileByFilename(f) if f == dep)) {
+                requests.push(MessageRequest::FileByFilename(dep.clone()));
+            }
+        }
+    }
+
+    Ok(DescriptorPool::from_file_descriptor_set(FileDescriptorSet { file: files })?)
+}
+
+// 基于消息描述动态编解码
+struct DynamicCodec(MessageDescriptor);
+
+impl Codec for DynamicCodec {
+    type Encode = DynamicMessage;
+    type Decode = DynamicMessage;
+    type Encoder = DynamicEncoder;
+    type Decoder = DynamicDecoder;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        DynamicEncoder
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        DynamicDecoder(self.0.clone())
+    }
+}
+
+struct DynamicEncoder;
+
+impl Encoder for DynamicEncoder {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn encode(&mut self, item: Self::Item, dst: &mut EncodeBuf<'_>) -> Result<(), Self::Error> {
+        item.encode(dst).map_err(|e| Status::internal(e.to_string()))
+    }
+}
+
+struct DynamicDecoder(MessageDescriptor);
+
+impl Decoder for DynamicDecoder {
+    type Item = DynamicMessage;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        DynamicMessage::decode(self.0.clone(), src)
+            .map(Some)
+            .map_err(|e| Status::internal(e.to_string()))
+    }
+}
@@ -0,0 +1,184 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use url::Url;
+
+use crate::DiffReport;
+use super::RequestProfile;
+
+pub type MatrixRow = Map<String, Value>;
+
+// 参数矩阵, 每一行的值替换请求中的`{{key}}`, 行来自rows和file(csv或jsonl)
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+pub struct Matrix {
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub rows: Vec<MatrixRow>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub file: Option<PathBuf>,
+}
+
+impl Matrix {
+    pub fn new(rows: Vec<MatrixRow>, file: Option<PathBuf>) -> Self {
+        Self { rows, file }
+    }
+
+    pub async fn load_rows(&self) -> anyhow::Result<Vec<MatrixRow>> {
+        let mut rows = self.rows.clone();
+        if let Some(file) = &self.file {
+            let content = tokio::fs::read_to_string(file)
+                .await
+                .with_context(|| format!("failed to read matrix file {}", file.display()))?;
+            let is_csv = file.extension().is_some_and(|ext| ext == "csv");
+            let file_rows = if is_csv { parse_csv(&content)? } else { parse_jsonl(&content)? };
+            rows.extend(file_rows);
+        }
+        Ok(rows)
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.rows.is_empty() && self.file.is_none() {
+            return Err(anyhow!("matrix must have rows or file"));
+        }
+        Ok(())
+    }
+}
+
+// 用矩阵中的一行替换url、params、body、headers中的`{{key}}`
+pub fn render_request(req: &RequestProfile, row: &MatrixRow) -> anyhow::Result<RequestProfile> {
+    let mut req = req.clone();
+    req.url = Url::parse(&render_str(req.url.as_str(), row))?;
+    req.params = req.params.map(|v| render_value(v, row));
+    req.body = req.body.map(|v| render_value(v, row));
+    for v in req.headers.values_mut() {
+        if let Ok(s) = v.to_str() {
+            *v = render_str(s, row).parse()?;
+        }
+    }
+    if let Some(graphql) = req.graphql.as_mut() {
+        graphql.variables = graphql.variables.take().map(|v| render_value(v, row));
+    }
+    if let Some(grpc) = req.grpc.as_mut() {
+        grpc.message = grpc.message.take().map(|v| render_value(v, row));
+    }
+    if let Some(websocket) = req.websocket.as_mut() {
+        let messages = std::mem::take(&mut websocket.messages);
+        websocket.messages = messages.into_iter().map(|v| render_value(v, row)).collect();
+    }
+    Ok(req)
+}
+
+// 值恰好是`{{key}}`时保留原始类型, 否则按字符串替换
+pub(crate) fn render_value(value: Value, row: &MatrixRow) -> Value {
+    match value {
+        Value::String(s) => {
+            let key = s.strip_prefix("{{").and_then(|s| s.strip_suffix("}}"));
+            match key.and_then(|k| row.get(k)) {
+                Some(v) => v.clone(),
+                None => Value::String(render_str(&s, row)),
+            }
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(|v| render_value(v, row)).collect()),
+        Value::Object(map) => Value::Object(map.into_iter().map(|(k, v)| (k, render_value(v, row))).collect()),
+        v => v,
+    }
+}
+
+// url中的`{{key}}`会被编码为`%7B%7Bkey%7D%7D`
+pub(crate) fn render_str(s: &str, row: &MatrixRow) -> String {
+    let mut output = s.to_string();
+    for (k, v) in row {
+        let v = match v {
+            Value::String(s) => s.clone(),
+            v => v.to_string(),
+        };
+        output = output
+            .replace(&format!("{{{{{}}}}}", k), &v)
+            .replace(&format!("%7B%7B{}%7D%7D", k), &v);
+    }
+    output
+}
+
+// csv第一行为列名, 值是json时按json解析
+fn parse_csv(content: &str) -> anyhow::Result<Vec<MatrixRow>> {
+    let mut reader = csv::Reader::from_reader(content.as_bytes());
+    let headers = reader.headers()?.clone();
+    let mut rows = vec![];
+    for record in reader.records() {
+        let record = record?;
+        let row = headers
+            .iter()
+            .zip(record.iter())
+            .map(|(k, v)| {
+                let v = serde_json::from_str(v).unwrap_or_else(|_| Value::String(v.to_string()));
+                (k.to_string(), v)
+            })
+            .collect();
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn parse_jsonl(content: &str) -> anyhow::Result<Vec<MatrixRow>> {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| match serde_json::from_str(line)? {
+            Value::Object(row) => Ok(row),
+            v => Err(anyhow!("matrix row must be an object but got {}", v)),
+        })
+        .collect()
+}
+
+// 矩阵中一行的对比结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatrixResult {
+    pub index: usize,
+    pub row: MatrixRow,
+    pub report: DiffReport,
+    // 带颜色的diff输出
+    pub output: String,
+    // 渲染或请求失败时的错误, 此时report和output为空
+    pub error: Option<String>,
+}
+
+// 所有行的对比结果
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MatrixReport {
+    pub results: Vec<MatrixResult>,
+}
+
+impl MatrixReport {
+    pub fn diverged(&self) -> Vec<&MatrixResult> {
+        self.results.iter().filter(|r| !r.report.is_empty()).collect()
+    }
+
+    pub fn failed(&self) -> Vec<&MatrixResult> {
+        self.results.iter().filter(|r| r.error.is_some()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diverged().is_empty() && self.failed().is_empty()
+    }
+}
+
+impl fmt::Display for MatrixReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let diverged = self.diverged();
+        let failed = self.failed();
+        write!(f, "{} rows, {} diverged", self.results.len(), diverged.len())?;
+        if !failed.is_empty() {
+            write!(f, ", {} failed", failed.len())?;
+        }
+        writeln!(f)?;
+        for r in diverged {
+            writeln!(f, "  row {}: {}", r.index, Value::Object(r.row.clone()))?;
+        }
+        for r in failed {
+            writeln!(f, "  row {}: {} failed: {}", r.index, Value::Object(r.row.clone()), r.error.as_deref().unwrap_or_default())?;
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,11 @@
+mod body;
+mod compare;
 mod diff;
+mod graphql;
+mod grpc;
+mod matrix;
 mod req;
+mod stream;
 
 use std::{str::FromStr, collections::HashSet};
 use std::fmt::Write as _;
@@ -7,14 +13,21 @@ use std::fmt::Write as _;
 use anyhow::Ok;
 use async_trait::async_trait;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use reqwest::{Method, header::{HeaderMap, HeaderName, HeaderValue, self}, Response};
+use reqwest::{Method, header::{HeaderMap, HeaderName, HeaderValue, self}, Response, StatusCode, Version};
 use serde_json::json;
 use url::Url;
 
+pub use body::{BodyOptions, Oversize};
+pub use compare::{ArrayRule, NumericTolerance};
 pub use diff::{DiffConfig, ResponseProfile, DiffProfile};
+pub use graphql::GraphqlRequest;
+pub use grpc::GrpcRequest;
+pub use matrix::{Matrix, MatrixReport, MatrixResult, MatrixRow};
+pub(crate) use matrix::{render_str, render_value};
 pub use req::RequestConfig;
+pub use stream::{SseRequest, StreamLimit, WebSocketRequest};
 
-use crate::ExtraArgs;
+use crate::{CookieJar, ExtraArgs};
 
 pub trait ValidateConfig {
     fn validate(&self) -> anyhow::Result<()>;
@@ -50,7 +63,7 @@ where
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct RequestProfile {
-    #[serde(with = "http_serde::method")]
+    #[serde(with = "http_serde::method", default)]
     pub method: Method,
     pub url: Url,
     #[serde(skip_serializing_if = "empty_json_value", default)]
@@ -63,6 +76,18 @@ pub struct RequestProfile {
     pub headers: HeaderMap,
     #[serde(skip_serializing_if = "empty_json_value", default)]
     pub body: Option<serde_json::Value>,
+    // graphql请求, 使用POST发送query和variables
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub graphql: Option<GraphqlRequest>,
+    // grpc unary请求
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub grpc: Option<GrpcRequest>,
+    // websocket请求, 收集服务端推送的消息
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub websocket: Option<WebSocketRequest>,
+    // sse请求, 收集事件流中的消息
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub sse: Option<SseRequest>,
 }
  
 impl RequestProfile {
@@ -73,25 +98,98 @@ impl RequestProfile {
             params,
             headers,
             body,
+            graphql: None,
+            grpc: None,
+            websocket: None,
+            sse: None,
+        }
+    }
+
+    pub fn new_graphql(url: Url, headers: HeaderMap, graphql: GraphqlRequest) -> Self {
+        Self {
+            graphql: Some(graphql),
+            ..Self::new(Method::POST, url, None, headers, None)
+        }
+    }
+
+    pub fn new_grpc(url: Url, headers: HeaderMap, grpc: GrpcRequest) -> Self {
+        Self {
+            grpc: Some(grpc),
+            ..Self::new(Method::POST, url, None, headers, None)
+        }
+    }
+
+    pub fn new_websocket(url: Url, headers: HeaderMap, websocket: WebSocketRequest) -> Self {
+        Self {
+            websocket: Some(websocket),
+            ..Self::new(Method::GET, url, None, headers, None)
+        }
+    }
+
+    pub fn new_sse(url: Url, headers: HeaderMap, sse: SseRequest) -> Self {
+        Self {
+            sse: Some(sse),
+            ..Self::new(Method::GET, url, None, headers, None)
         }
     }
 
     pub async fn send(&self, args: &ExtraArgs) -> anyhow::Result<ResponseExt> {
-        let (headers, query, body) = self.generate(args)?;
-        let client = reqwest::Client::new();
+        self.send_with_jar(args, None).await
+    }
+
+    // 使用cookie jar发送请求, 请求时带上jar中的cookie, 响应中的set-cookie保存到jar
+    pub async fn send_with_jar(&self, args: &ExtraArgs, jar: Option<&CookieJar>) -> anyhow::Result<ResponseExt> {
+        let (mut headers, query, body) = self.generate(args)?;
+        if let Some(grpc) = &self.grpc {
+            let message = serde_json::from_str(&body)?;
+            let res = grpc.send(&self.url, &headers, message).await?;
+            return Ok(ResponseExt(res));
+        }
+        if let Some(websocket) = &self.websocket {
+            let url = Url::parse(&self.get_url(args)?)?;
+            if let Some(jar) = jar {
+                jar.add_cookie_header(&url, &mut headers);
+            }
+            let res = websocket.send(&url, &headers).await?;
+            if let Some(jar) = jar {
+                jar.store_response_cookies(&url, res.headers());
+            }
+            return Ok(ResponseExt(res));
+        }
+        if self.sse.is_some() {
+            headers.insert(header::ACCEPT, HeaderValue::from_static("text/event-stream"));
+        }
+
+        // graphql 统一使用POST
+        let method = match self.graphql {
+            Some(_) => Method::POST,
+            None => self.method.clone(),
+        };
+        let client = match jar {
+            Some(jar) => jar.client_builder().build()?,
+            None => reqwest::Client::new(),
+        };
         let req = client
-            .request(self.method.clone(), self.url.clone())
+            .request(method, self.url.clone())
             .headers(headers)
             .query(&query)
             .body(body)
             .build()?;
         let res = client.execute(req).await?;
-        
-        Ok(ResponseExt(res))
+
+        match (&self.graphql, &self.sse) {
+            (Some(graphql), _) => Ok(ResponseExt(graphql.check_response(res).await?)),
+            (_, Some(sse)) => Ok(ResponseExt(sse.collect(res).await?)),
+            _ => Ok(ResponseExt(res)),
+        }
     }
 
     pub fn get_url(&self, args: &ExtraArgs) -> anyhow::Result<String> {
         let mut url = self.url.clone();
+        if let Some(grpc) = &self.grpc {
+            url.set_path(&grpc.path()?);
+            return Ok(url.to_string());
+        }
         let (_, params, _) = self.generate(args)?;
         if !params.as_object().unwrap().is_empty() {
             // url query转string
@@ -104,7 +202,13 @@ impl RequestProfile {
     pub fn generate(&self, args: &ExtraArgs) -> anyhow::Result<(HeaderMap, serde_json::Value, String)> {
         let mut headers = self.headers.clone();
         let mut query = self.params.clone().unwrap_or_else(|| serde_json::json!({}));
-        let mut body = self.body.clone().unwrap_or_else(|| serde_json::json!({}));
+        // graphql的body参数为variables, grpc的body参数为message
+        let mut body = match (&self.graphql, &self.grpc) {
+            (Some(graphql), _) => graphql.variables.clone(),
+            (_, Some(grpc)) => grpc.message.clone(),
+            _ => self.body.clone(),
+        }
+        .unwrap_or_else(|| serde_json::json!({}));
         // 将client参数加入到headers
         for (k, v) in args.headers.iter() {
             headers.insert(HeaderName::from_str(k)?, HeaderValue::from_str(v)?);
@@ -120,6 +224,9 @@ impl RequestProfile {
         for (k, v) in args.body.iter() {
             body[k] = v.parse()?;
         }
+        if let Some(graphql) = &self.graphql {
+            body = graphql.payload(body);
+        }
 
         let content_type = get_content_type(&headers);
         match content_type.as_deref() {
@@ -150,6 +257,27 @@ impl ValidateConfig for RequestProfile {
                 serde_yaml::to_string(body)?));
             }
         }
+        let protocols = [
+            self.graphql.is_some(),
+            self.grpc.is_some(),
+            self.websocket.is_some(),
+            self.sse.is_some(),
+        ];
+        if protocols.iter().filter(|p| **p).count() > 1 {
+            return Err(anyhow::anyhow!("only one of graphql, grpc, websocket and sse can be used"));
+        }
+        if let Some(graphql) = self.graphql.as_ref() {
+            graphql.validate()?;
+        }
+        if let Some(grpc) = self.grpc.as_ref() {
+            grpc.validate()?;
+        }
+        if let Some(websocket) = self.websocket.as_ref() {
+            websocket.validate()?;
+        }
+        if let Some(sse) = self.sse.as_ref() {
+            sse.validate()?;
+        }
         Ok(())
     }
 }
@@ -173,6 +301,10 @@ impl FromStr for RequestProfile {
             params: Some(params),
             headers: HeaderMap::new(),
             body: None,
+            graphql: None,
+            grpc: None,
+            websocket: None,
+            sse: None,
         };
 
         Ok(profile)
@@ -188,6 +320,15 @@ impl ResponseExt {
     }
 
     pub async fn get_text(self, profile: &ResponseProfile) -> anyhow::Result<String> {
+        let (mut output, body) = self.get_parts(profile).await?;
+        // body
+        write!(&mut output, "{}", body.to_text()?)?;
+
+        Ok(output)
+    }
+
+    // 拆分为status+headers文本和body
+    pub async fn get_parts(self, profile: &ResponseProfile) -> anyhow::Result<(String, ResponseBody)> {
         let res = self.0;
         let mut output = get_status_text(&res)?;
         // version headers
@@ -197,11 +338,8 @@ impl ResponseExt {
             get_header_text(&res, &profile.skip_headers)?
         )?;
 
-        // body
-        let body = get_body_text(res, &profile.skip_body).await?;
-        write!(&mut output, "{}", body)?;
-
-        Ok(output)
+        let body = get_body_with(res, &profile.skip_body, &profile.body).await?;
+        Ok((output, body))
     }
 
     pub fn get_header_keys(&self) -> Vec<String>{
@@ -229,6 +367,15 @@ pub fn empty_json_value(v: &Option<serde_json::Value>) -> bool {
   })
 }
 
+// 根据读取后的数据重新构造Response
+pub(crate) fn build_response(status: StatusCode, version: Version, headers: HeaderMap, body: Vec<u8>) -> Response {
+  let mut res = http::Response::new(body);
+  *res.status_mut() = status;
+  *res.version_mut() = version;
+  *res.headers_mut() = headers;
+  Response::from(res)
+}
+
 // 处理请求头中的content-type, e: application/json;charset=utf-8
 fn get_content_type(headers: &HeaderMap) -> Option<String> {
   headers
@@ -236,7 +383,7 @@ fn get_content_type(headers: &HeaderMap) -> Option<String> {
       .and_then(|v| v.to_str().unwrap().split(';').next().map(|v| v.to_string()))
 }
 
-fn filter_json(text: &str, skips: &[String]) -> anyhow::Result<String> {
+fn filter_json(text: &str, skips: &[String]) -> anyhow::Result<serde_json::Value> {
   let mut json: serde_json::Value = serde_json::from_str(text)?;
   // 如果json是对象，删除skip的值
   if let serde_json::Value::Object(ref mut v) = json {
@@ -245,7 +392,32 @@ fn filter_json(text: &str, skips: &[String]) -> anyhow::Result<String> {
       }
   }
 
-  Ok(serde_json::to_string_pretty(&json)?)
+  Ok(json)
+}
+
+fn filter_ndjson(text: &str, skips: &[String]) -> anyhow::Result<serde_json::Value> {
+  let messages = text
+      .lines()
+      .filter(|line| !line.trim().is_empty())
+      .map(|line| filter_json(line, skips))
+      .collect::<anyhow::Result<Vec<_>>>()?;
+  Ok(serde_json::Value::Array(messages))
+}
+
+// 响应body, json会先解析以便应用比较规则
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResponseBody {
+    Json(serde_json::Value),
+    Text(String),
+}
+
+impl ResponseBody {
+    pub fn to_text(&self) -> anyhow::Result<String> {
+        match self {
+            ResponseBody::Json(v) => Ok(serde_json::to_string_pretty(v)?),
+            ResponseBody::Text(t) => Ok(t.clone()),
+        }
+    }
 }
 
 pub fn get_status_text(res: &Response) -> anyhow::Result<String> {
@@ -265,10 +437,32 @@ pub fn get_header_text(res: &Response, skip_headers: &[String]) -> anyhow::Resul
 }
 
 pub async fn get_body_text(res: Response, skip_body: &[String]) -> anyhow::Result<String> {
+  get_body(res, skip_body).await?.to_text()
+}
+
+pub async fn get_body(res: Response, skip_body: &[String]) -> anyhow::Result<ResponseBody> {
+  get_body_with(res, skip_body, &BodyOptions::default()).await
+}
+
+// 按块读取body, 二进制和超过max_size的body转为大小和摘要(或截断的文本)
+pub async fn get_body_with(res: Response, skip_body: &[String], options: &BodyOptions) -> anyhow::Result<ResponseBody> {
   let content_type = get_content_type(res.headers());
-  let text = res.text().await?;
+  let body = options.read(res).await?;
+  if body.is_binary(content_type.as_deref()) {
+      return Ok(ResponseBody::Text(body.summary("binary body")));
+  }
+  if body.is_oversize() {
+      let text = match options.oversize {
+          Oversize::Hash => body.summary("body too large"),
+          Oversize::Truncate => body.truncated_text(),
+      };
+      return Ok(ResponseBody::Text(text));
+  }
+  let text = String::from_utf8_lossy(&body.data).into_owned();
   match content_type.as_deref() {
-      Some("application/json") => filter_json(&text, &skip_body),
-      _ => Ok(text),
+      Some("application/json") => Ok(ResponseBody::Json(filter_json(&text, skip_body)?)),
+      // 流式响应收集到的消息, 每行一条
+      Some(stream::NDJSON_CONTENT_TYPE | "text/event-stream") => Ok(ResponseBody::Json(filter_ndjson(&text, skip_body)?)),
+      _ => Ok(ResponseBody::Text(text)),
   }
 }
\ No newline at end of file
@@ -0,0 +1,221 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use futures_util::SinkExt;
+use reqwest::{
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    Response, StatusCode, Version,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time::{timeout_at, Instant};
+use tokio_stream::StreamExt;
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+use url::Url;
+
+use super::build_response;
+
+// 流式响应收集的消息按行保存的content-type
+pub const NDJSON_CONTENT_TYPE: &str = "application/x-ndjson";
+
+const DEFAULT_TIMEOUT: u64 = 10;
+
+// 收集消息的条件: 收到count条消息或超时(秒)
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+pub struct StreamLimit {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub timeout: Option<u64>,
+}
+
+impl StreamLimit {
+    pub fn new(count: Option<usize>, timeout: Option<u64>) -> Self {
+        Self { count, timeout }
+    }
+
+    fn deadline(&self) -> Instant {
+        Instant::now() + Duration::from_secs(self.timeout.unwrap_or(DEFAULT_TIMEOUT))
+    }
+
+    fn is_full(&self, messages: &[Value]) -> bool {
+        self.count.is_some_and(|c| messages.len() >= c)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.count == Some(0) {
+            return Err(anyhow!("stream count must be greater than 0"));
+        }
+        Ok(())
+    }
+}
+
+// websocket请求，连接后依次发送messages, 字符串按原样发送，其他按json发送
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+pub struct WebSocketRequest {
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub messages: Vec<Value>,
+    #[serde(flatten)]
+    pub limit: StreamLimit,
+}
+
+impl WebSocketRequest {
+    pub fn new(messages: Vec<Value>, limit: StreamLimit) -> Self {
+        Self { messages, limit }
+    }
+
+    pub async fn send(&self, url: &Url, headers: &HeaderMap) -> anyhow::Result<Response> {
+        let mut req = url.as_str().into_client_request()?;
+        for (k, v) in headers.iter() {
+            if k == header::CONTENT_TYPE {
+                continue;
+            }
+            req.headers_mut().insert(
+                tokio_tungstenite::tungstenite::http::HeaderName::from_str(k.as_str())?,
+                tokio_tungstenite::tungstenite::http::HeaderValue::from_bytes(v.as_bytes())?,
+            );
+        }
+        let deadline = self.limit.deadline();
+        let (mut ws, res) = timeout_at(deadline, tokio_tungstenite::connect_async(req))
+            .await
+            .map_err(|_| anyhow!("websocket connect timeout"))?
+            .with_context(|| format!("failed to connect websocket {}", url))?;
+
+        for msg in &self.messages {
+            let text = match msg {
+                Value::String(s) => s.clone(),
+                v => serde_json::to_string(v)?,
+            };
+            ws.send(Message::text(text)).await?;
+        }
+
+        let mut messages = vec![];
+        while !self.limit.is_full(&messages) {
+            let msg = match timeout_at(deadline, ws.next()).await {
+                Ok(Some(msg)) => msg?,
+                // 超时或连接关闭
+                _ => break,
+            };
+            match msg {
+                Message::Text(text) => messages.push(parse_message(text.as_str())),
+                Message::Binary(data) => messages.push(parse_message(&String::from_utf8_lossy(&data))),
+                Message::Close(_) => break,
+                _ => {}
+            }
+        }
+        let _ = ws.close(None).await;
+
+        let mut headers = HeaderMap::new();
+        for (k, v) in res.headers().iter() {
+            headers.append(HeaderName::from_str(k.as_str())?, HeaderValue::from_bytes(v.as_bytes())?);
+        }
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(NDJSON_CONTENT_TYPE));
+        let status = StatusCode::from_u16(res.status().as_u16())?;
+        Ok(build_response(status, Version::HTTP_11, headers, to_ndjson(&messages)?))
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.limit.validate()
+    }
+}
+
+// sse请求, 每个事件的data作为一条消息，带事件名时为`{event, data}`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Default)]
+pub struct SseRequest {
+    #[serde(flatten)]
+    pub limit: StreamLimit,
+}
+
+impl SseRequest {
+    pub fn new(limit: StreamLimit) -> Self {
+        Self { limit }
+    }
+
+    // 读取sse流直到满足条件，收集的消息作为body
+    pub async fn collect(&self, res: Response) -> anyhow::Result<Response> {
+        let status = res.status();
+        let version = res.version();
+        let headers = res.headers().clone();
+        let deadline = self.limit.deadline();
+
+        let mut stream = res.bytes_stream();
+        let mut parser = SseParser::default();
+        let mut messages = vec![];
+        while !self.limit.is_full(&messages) {
+            let chunk = match timeout_at(deadline, stream.next()).await {
+                Ok(Some(chunk)) => chunk?,
+                _ => break,
+            };
+            parser.feed(&chunk, &mut messages);
+        }
+        if let Some(c) = self.limit.count {
+            messages.truncate(c);
+        }
+        Ok(build_response(status, version, headers, to_ndjson(&messages)?))
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.limit.validate()
+    }
+}
+
+// 按sse协议解析事件, 缓存原始字节, 只解码完整的行, 多字节字符跨chunk时不会被截断
+#[derive(Debug, Default)]
+struct SseParser {
+    buf: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    fn feed(&mut self, chunk: &[u8], messages: &mut Vec<Value>) {
+        self.buf.extend_from_slice(chunk);
+        while let Some(pos) = self.buf.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buf.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() {
+                self.dispatch(messages);
+                continue;
+            }
+            let (field, value) = match line.split_once(':') {
+                Some((f, v)) => (f, v.strip_prefix(' ').unwrap_or(v)),
+                None => (line, ""),
+            };
+            match field {
+                "event" => self.event = Some(value.to_string()),
+                "data" => self.data.push(value.to_string()),
+                // 注释及id、retry字段不参与对比
+                _ => {}
+            }
+        }
+    }
+
+    fn dispatch(&mut self, messages: &mut Vec<Value>) {
+        let event = self.event.take();
+        if self.data.is_empty() {
+            return;
+        }
+        let data = parse_message(&self.data.join("\n"));
+        self.data.clear();
+        match event {
+            Some(event) => messages.push(serde_json::json!({ "event": event, "data": data })),
+            None => messages.push(data),
+        }
+    }
+}
+
+// 消息是json时解析，否则保留字符串
+fn parse_message(text: &str) -> Value {
+    serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string()))
+}
+
+fn to_ndjson(messages: &[Value]) -> anyhow::Result<Vec<u8>> {
+    let mut body = vec![];
+    for msg in messages {
+        serde_json::to_writer(&mut body, msg)?;
+        body.push(b'\n');
+    }
+    Ok(body)
+}
@@ -0,0 +1,144 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::{anyhow, Context};
+use cookie_store::CookieStore;
+use reqwest::header::{self, HeaderMap, HeaderValue};
+use tokio::io::AsyncWriteExt;
+use url::Url;
+
+// cookie jar, clone后共享同一份cookie, 用于在一次运行的多个请求间保持会话
+// 设置了path时可以从文件加载, 并在请求后保存, 供之后的xreq/xdiff复用
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    store: Arc<SharedStore>,
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Default)]
+struct SharedStore(Mutex<CookieStore>);
+
+impl SharedStore {
+    fn lock(&self) -> MutexGuard<'_, CookieStore> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+// 按请求url匹配cookie, 重定向过程中的set-cookie也会被保存
+impl reqwest::cookie::CookieStore for SharedStore {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let mut store = self.lock();
+        for value in cookie_headers {
+            if let Ok(s) = value.to_str() {
+                let _ = store.parse(s, url);
+            }
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let value = self
+            .lock()
+            .get_request_values(url)
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if value.is_empty() {
+            return None;
+        }
+        HeaderValue::from_str(&value).ok()
+    }
+}
+
+impl PartialEq for CookieJar {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.store, &other.store) && self.path == other.path
+    }
+}
+
+impl CookieJar {
+    // 只在内存中保存
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 从文件加载, 文件不存在时为空, 过期的cookie会被忽略
+    pub async fn load(path: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let path = path.into();
+        let store = match tokio::fs::read(&path).await {
+            Ok(content) => CookieStore::load_json(content.as_slice())
+                .map_err(|e| anyhow!(e))
+                .with_context(|| format!("failed to parse cookie jar {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => CookieStore::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            store: Arc::new(SharedStore(Mutex::new(store))),
+            path: Some(path),
+        })
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    // 保存到文件, 包括会话cookie, 没有path时不做处理
+    pub async fn save(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut content = vec![];
+        self.store
+            .lock()
+            .save_incl_expired_and_nonpersistent_json(&mut content)
+            .map_err(|e| anyhow!(e))?;
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        // cookie里是会话凭据, 只允许自己读写
+        let mut options = tokio::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options
+            .open(path)
+            .await
+            .with_context(|| format!("failed to save cookie jar {}", path.display()))?;
+        file.write_all(&content).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    // 请求url对应的cookie(name, value)
+    pub fn cookies(&self, url: &Url) -> Vec<(String, String)> {
+        self.store
+            .lock()
+            .get_request_values(url)
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.store.lock().iter_unexpired().next().is_none()
+    }
+
+    pub(crate) fn client_builder(&self) -> reqwest::ClientBuilder {
+        reqwest::Client::builder().cookie_provider(self.store.clone())
+    }
+
+    // 请求中没有cookie时添加jar中的cookie, 用于websocket等不经过reqwest client的请求
+    pub(crate) fn add_cookie_header(&self, url: &Url, headers: &mut HeaderMap) {
+        use reqwest::cookie::CookieStore as _;
+        if headers.contains_key(header::COOKIE) {
+            return;
+        }
+        if let Some(value) = self.store.cookies(url) {
+            headers.insert(header::COOKIE, value);
+        }
+    }
+
+    pub(crate) fn store_response_cookies(&self, url: &Url, headers: &HeaderMap) {
+        use reqwest::cookie::CookieStore as _;
+        self.store
+            .set_cookies(&mut headers.get_all(header::SET_COOKIE).iter(), url);
+    }
+}
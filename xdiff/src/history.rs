@@ -0,0 +1,258 @@
+use std::fmt::Write as _;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+use crate::{get_body_text, CookieJar, ExtraArgs, RequestProfile};
+
+// 历史记录文件路径环境变量, 默认 ~/.xdiff/history.jsonl
+pub const HISTORY_ENV: &str = "XDIFF_HISTORY";
+// 设置为1时保存认证相关的header, 默认保存前去掉
+pub const HISTORY_SECRETS_ENV: &str = "XDIFF_HISTORY_SECRETS";
+
+// 不写入历史记录的header
+const SENSITIVE_HEADERS: &[&str] = &["authorization", "proxy-authorization", "cookie", "set-cookie", "x-api-key"];
+const REDACTED: &str = "<redacted>";
+
+// 从文件末尾向前读取的块大小
+const TAIL_BLOCK: u64 = 4096;
+
+// 一次xreq请求及其响应
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: u64,
+    // unix时间戳(秒)
+    pub time: u64,
+    pub profile: String,
+    pub url: String,
+    pub request: RequestProfile,
+    pub args: ExtraArgs,
+    pub status: u16,
+    pub version: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    // 请求耗时(毫秒)
+    pub elapsed: u64,
+}
+
+impl HistoryEntry {
+    // 发送请求并记录响应, id和时间在保存时分配
+    pub async fn send(profile: &str, request: &RequestProfile, args: &ExtraArgs) -> anyhow::Result<Self> {
+        Self::send_with_jar(profile, request, args, None).await
+    }
+
+    pub async fn send_with_jar(
+        profile: &str,
+        request: &RequestProfile,
+        args: &ExtraArgs,
+        jar: Option<&CookieJar>,
+    ) -> anyhow::Result<Self> {
+        let url = request.get_url(args)?;
+        let start = Instant::now();
+        let res = request.send_with_jar(args, jar).await?.into_inner();
+        let status = res.status().as_u16();
+        let version = format!("{:?}", res.version());
+        let headers = res
+            .headers()
+            .iter()
+            .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).to_string()))
+            .collect();
+        let body = get_body_text(res, &[]).await?;
+
+        Ok(Self {
+            id: 0,
+            time: 0,
+            profile: profile.to_string(),
+            url,
+            request: request.clone(),
+            args: args.clone(),
+            status,
+            version,
+            headers,
+            body,
+            elapsed: start.elapsed().as_millis() as u64,
+        })
+    }
+
+    // 去掉请求中认证相关的header, 响应中的替换为<redacted>
+    pub fn redact(&mut self) {
+        let sensitive = |k: &str| SENSITIVE_HEADERS.iter().any(|h| k.eq_ignore_ascii_case(h));
+        for h in SENSITIVE_HEADERS {
+            self.request.headers.remove(*h);
+        }
+        self.args.headers.retain(|(k, _)| !sensitive(k));
+        for (k, v) in self.headers.iter_mut() {
+            if sensitive(k) {
+                *v = REDACTED.to_string();
+            }
+        }
+    }
+
+    // 与ResponseExt::get_text格式一致的文本, 用于展示和对比
+    pub fn get_text(&self) -> anyhow::Result<String> {
+        let mut output = self.get_head_text()?;
+        write!(&mut output, "{}", self.body)?;
+        Ok(output)
+    }
+
+    pub fn get_head_text(&self) -> anyhow::Result<String> {
+        let mut output = String::new();
+        writeln!(&mut output, "{} {}", self.version, self.status_text())?;
+        for (k, v) in &self.headers {
+            writeln!(&mut output, "{}: {:?}", k, v)?;
+        }
+        writeln!(&mut output)?;
+        Ok(output)
+    }
+
+    pub fn status_text(&self) -> String {
+        match reqwest::StatusCode::from_u16(self.status) {
+            Ok(status) => status.to_string(),
+            Err(_) => self.status.to_string(),
+        }
+    }
+
+    pub fn time_text(&self) -> String {
+        time::OffsetDateTime::from_unix_timestamp(self.time as i64)
+            .ok()
+            .and_then(|t| t.format(&time::format_description::well_known::Rfc3339).ok())
+            .unwrap_or_else(|| self.time.to_string())
+    }
+
+    // profile、url、状态码或body中包含关键字
+    pub fn matches(&self, keyword: &str) -> bool {
+        self.profile.contains(keyword)
+            || self.url.contains(keyword)
+            || self.status.to_string() == keyword
+            || self.body.contains(keyword)
+    }
+}
+
+// 历史记录以json lines格式追加保存, 文件只有当前用户可读写
+#[derive(Debug, Clone)]
+pub struct History {
+    path: PathBuf,
+    keep_secrets: bool,
+}
+
+impl History {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            keep_secrets: false,
+        }
+    }
+
+    // 保存认证相关的header, rerun时不需要重新提供
+    pub fn keep_secrets(mut self, keep: bool) -> Self {
+        self.keep_secrets = keep;
+        self
+    }
+
+    // 优先使用环境变量中的路径
+    pub fn open_default() -> anyhow::Result<Self> {
+        let keep = std::env::var(HISTORY_SECRETS_ENV).is_ok_and(|v| v == "1");
+        if let Ok(path) = std::env::var(HISTORY_ENV) {
+            return Ok(Self::new(path).keep_secrets(keep));
+        }
+        let home = dirs::home_dir().ok_or_else(|| anyhow!("can not find home dir, please set {}", HISTORY_ENV))?;
+        Ok(Self::new(home.join(".xdiff").join("history.jsonl")).keep_secrets(keep))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub async fn entries(&self) -> anyhow::Result<Vec<HistoryEntry>> {
+        let content = match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect::<anyhow::Result<Vec<_>>>()
+            .with_context(|| format!("failed to parse history {}", self.path.display()))
+    }
+
+    pub async fn get(&self, id: u64) -> anyhow::Result<HistoryEntry> {
+        self.entries()
+            .await?
+            .into_iter()
+            .find(|e| e.id == id)
+            .ok_or_else(|| anyhow!("history {} not found", id))
+    }
+
+    // 按关键字搜索, 最新的在前
+    pub async fn search(&self, keyword: Option<&str>, limit: usize) -> anyhow::Result<Vec<HistoryEntry>> {
+        let entries = self.entries().await?;
+        Ok(entries
+            .into_iter()
+            .rev()
+            .filter(|e| keyword.is_none_or(|k| e.matches(k)))
+            .take(limit)
+            .collect())
+    }
+
+    // 分配id和时间后追加保存, id为最后一条记录的id加1
+    pub async fn append(&self, mut entry: HistoryEntry) -> anyhow::Result<HistoryEntry> {
+        entry.id = self.last().await?.map_or(1, |e| e.id + 1);
+        entry.time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        if !self.keep_secrets {
+            entry.redact();
+        }
+        if let Some(dir) = self.path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let mut line = serde_json::to_string(&entry)?;
+        line.push('\n');
+        let mut options = tokio::fs::OpenOptions::new();
+        options.create(true).append(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options.open(&self.path).await?;
+        file.write_all(line.as_bytes()).await?;
+        Ok(entry)
+    }
+
+    // 从文件末尾向前读取最后一条记录, 不解析整个文件
+    pub async fn last(&self) -> anyhow::Result<Option<HistoryEntry>> {
+        let mut file = match tokio::fs::File::open(&self.path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut pos = file.metadata().await?.len();
+        let mut tail: Vec<u8> = vec![];
+        loop {
+            let end = tail.iter().rposition(|b| !b.is_ascii_whitespace()).map_or(0, |i| i + 1);
+            if let Some(start) = tail[..end].iter().rposition(|b| *b == b'\n') {
+                tail = tail[start + 1..end].to_vec();
+                break;
+            }
+            if pos == 0 {
+                tail.truncate(end);
+                break;
+            }
+            let n = TAIL_BLOCK.min(pos);
+            pos -= n;
+            file.seek(SeekFrom::Start(pos)).await?;
+            let mut block = vec![0; n as usize];
+            file.read_exact(&mut block).await?;
+            block.extend_from_slice(&tail);
+            tail = block;
+        }
+        if tail.is_empty() {
+            return Ok(None);
+        }
+        let entry = serde_json::from_slice(&tail)
+            .with_context(|| format!("failed to parse history {}", self.path.display()))?;
+        Ok(Some(entry))
+    }
+}
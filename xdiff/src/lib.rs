@@ -1,12 +1,22 @@
 pub mod cli;
 mod utils;
 mod config;
+mod cookie;
+mod history;
+mod mock;
+mod report;
 
 pub use utils::{process_error_output, highlight_text, diff_text};
-pub use config::{DiffConfig, RequestProfile, LoadConfig, DiffProfile, ResponseProfile, RequestConfig, get_status_text, get_header_text, get_body_text, ResponseExt, ValidateConfig};
+pub use cookie::CookieJar;
+pub use history::{History, HistoryEntry, HISTORY_ENV, HISTORY_SECRETS_ENV};
+pub use mock::{serve, MockConfig, MockRoute};
+pub use report::{DiffReport, LineChange, ChangeKind, BaselineReport, diff_lines};
+pub use config::{DiffConfig, RequestProfile, LoadConfig, DiffProfile, ResponseProfile, RequestConfig, get_status_text, get_header_text, get_body_text, get_body, get_body_with, ResponseExt, ResponseBody, ValidateConfig, NumericTolerance, ArrayRule, GraphqlRequest, GrpcRequest, WebSocketRequest, SseRequest, StreamLimit, BodyOptions, Oversize, Matrix, MatrixReport, MatrixResult, MatrixRow};
+
+use serde::{Deserialize, Serialize};
 
 // 提供解析参数给外部使用
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub struct ExtraArgs {
     pub headers: Vec<(String, String)>,
     pub query: Vec<(String, String)>,
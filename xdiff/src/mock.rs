@@ -0,0 +1,175 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+use reqwest::{
+    header::{self, HeaderMap, HeaderValue},
+    Method, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::config::{render_str, render_value};
+use crate::{LoadConfig, ValidateConfig};
+
+// 本地mock服务配置, 按顺序匹配第一个符合的route
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct MockConfig {
+    pub routes: Vec<MockRoute>,
+}
+
+impl LoadConfig for MockConfig {}
+
+impl MockConfig {
+    pub fn new(routes: Vec<MockRoute>) -> Self {
+        Self { routes }
+    }
+
+    // 返回匹配的route及模板变量
+    pub fn find_route(&self, method: &Method, path: &str) -> Option<(&MockRoute, Map<String, Value>)> {
+        self.routes
+            .iter()
+            .find_map(|r| r.match_path(method, path).map(|vars| (r, vars)))
+    }
+}
+
+impl ValidateConfig for MockConfig {
+    fn validate(&self) -> anyhow::Result<()> {
+        for route in &self.routes {
+            if !route.path.starts_with('/') {
+                return Err(anyhow!("mock route path must start with / but got {}", route.path));
+            }
+        }
+        Ok(())
+    }
+}
+
+// path中`{name}`匹配一段并作为模板变量, 结尾的`*`匹配剩余部分
+// body中的`{{name}}`会被path参数、query参数和json请求体中的字段替换
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Default)]
+pub struct MockRoute {
+    // 不设置时匹配所有method
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub method: Option<String>,
+    pub path: String,
+    #[serde(with = "http_serde::status_code", default)]
+    pub status: StatusCode,
+    #[serde(
+        skip_serializing_if = "HeaderMap::is_empty",
+        with = "http_serde::header_map",
+        default
+    )]
+    pub headers: HeaderMap,
+    // 字符串原样返回, 其他按json返回
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub body: Option<Value>,
+    // 响应延迟(毫秒)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub delay: Option<u64>,
+}
+
+impl MockRoute {
+    pub fn match_path(&self, method: &Method, path: &str) -> Option<Map<String, Value>> {
+        if let Some(m) = &self.method {
+            if !m.eq_ignore_ascii_case(method.as_str()) {
+                return None;
+            }
+        }
+        let mut vars = Map::new();
+        let mut segments = path.trim_matches('/').split('/');
+        for pattern in self.path.trim_matches('/').split('/') {
+            if pattern == "*" {
+                return Some(vars);
+            }
+            let segment = segments.next()?;
+            match pattern.strip_prefix('{').and_then(|p| p.strip_suffix('}')) {
+                Some(name) => {
+                    vars.insert(name.to_string(), parse_var(segment));
+                }
+                None if pattern == segment => {}
+                None => return None,
+            }
+        }
+        segments.next().is_none().then_some(vars)
+    }
+
+    fn render(&self, vars: &Map<String, Value>) -> anyhow::Result<Response<Body>> {
+        let mut headers = self.headers.clone();
+        let body = match &self.body {
+            None => String::new(),
+            Some(Value::String(s)) => render_str(s, vars),
+            Some(v) => {
+                if !headers.contains_key(header::CONTENT_TYPE) {
+                    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+                }
+                serde_json::to_string_pretty(&render_value(v.clone(), vars))?
+            }
+        };
+        let mut res = Response::new(Body::from(body));
+        *res.status_mut() = self.status;
+        *res.headers_mut() = headers;
+        Ok(res)
+    }
+}
+
+// 按配置启动mock服务, 直到进程退出
+pub async fn serve(config: MockConfig, listener: std::net::TcpListener) -> anyhow::Result<()> {
+    listener.set_nonblocking(true)?;
+    let config = Arc::new(config);
+    let make_svc = make_service_fn(move |_| {
+        let config = config.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let config = config.clone();
+                async move { Ok::<_, Infallible>(handle(&config, req).await) }
+            }))
+        }
+    });
+    Server::from_tcp(listener)?
+        .serve(make_svc)
+        .await
+        .context("mock server error")
+}
+
+async fn handle(config: &MockConfig, req: Request<Body>) -> Response<Body> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let query = req.uri().query().unwrap_or_default().to_string();
+    let Some((route, mut vars)) = config.find_route(&method, &path) else {
+        return error_response(StatusCode::NOT_FOUND, format!("no mock route for {} {}", method, path));
+    };
+
+    for (k, v) in url::form_urlencoded::parse(query.as_bytes()) {
+        vars.entry(k.to_string()).or_insert_with(|| parse_var(&v));
+    }
+    if let Ok(body) = hyper::body::to_bytes(req.into_body()).await {
+        if let Ok(Value::Object(fields)) = serde_json::from_slice(&body) {
+            for (k, v) in fields {
+                vars.entry(k).or_insert(v);
+            }
+        }
+    }
+
+    if let Some(delay) = route.delay {
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+    }
+    route
+        .render(&vars)
+        .unwrap_or_else(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+fn error_response(status: StatusCode, msg: String) -> Response<Body> {
+    let mut res = Response::new(Body::from(json!({ "error": msg }).to_string()));
+    *res.status_mut() = status;
+    res.headers_mut()
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    res
+}
+
+// 数字、布尔等按json解析, 其余为字符串
+fn parse_var(s: &str) -> Value {
+    serde_json::from_str(s).unwrap_or_else(|_| Value::String(s.to_string()))
+}
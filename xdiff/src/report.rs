@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+
+// 比较两个响应，结果按status、headers、body分段, 不包含终端颜色
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct DiffReport {
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub status: Vec<LineChange>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub headers: Vec<LineChange>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub body: Vec<LineChange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Delete,
+    Insert,
+}
+
+// 单行差异, 行号从1开始, 删除行只有old_line, 新增行只有new_line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineChange {
+    pub kind: ChangeKind,
+    pub old_line: Option<usize>,
+    pub new_line: Option<usize>,
+    pub value: String,
+}
+
+impl DiffReport {
+    // head为status+headers文本
+    pub fn new(head1: &str, body1: &str, head2: &str, body2: &str) -> Self {
+        let (status1, headers1) = head1.split_once('\n').unwrap_or((head1, ""));
+        let (status2, headers2) = head2.split_once('\n').unwrap_or((head2, ""));
+        Self {
+            status: diff_lines(status1, status2),
+            headers: diff_lines(headers1, headers2),
+            body: diff_lines(body1, body2),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.status.is_empty() && self.headers.is_empty() && self.body.is_empty()
+    }
+
+    pub fn sections(&self) -> [(&'static str, &[LineChange]); 3] {
+        [
+            ("status", &self.status),
+            ("headers", &self.headers),
+            ("body", &self.body),
+        ]
+    }
+
+    // 与已接受的差异比较, 忽略行号只比较内容
+    pub fn classify(&self, expected: &DiffReport) -> BaselineReport {
+        let status = classify_changes(&self.status, &expected.status);
+        let headers = classify_changes(&self.headers, &expected.headers);
+        let body = classify_changes(&self.body, &expected.body);
+        BaselineReport {
+            accepted: DiffReport { status: status.0, headers: headers.0, body: body.0 },
+            new: DiffReport { status: status.1, headers: headers.1, body: body.1 },
+            resolved: DiffReport { status: status.2, headers: headers.2, body: body.2 },
+        }
+    }
+}
+
+// 按已接受的差异分类: accepted为预期的变化, new为新出现的变化, resolved为不再出现的预期变化
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BaselineReport {
+    pub accepted: DiffReport,
+    pub new: DiffReport,
+    pub resolved: DiffReport,
+}
+
+impl BaselineReport {
+    // 没有新的变化也没有已解决的变化
+    pub fn is_clean(&self) -> bool {
+        self.new.is_empty() && self.resolved.is_empty()
+    }
+}
+
+impl fmt::Display for BaselineReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let count = |r: &DiffReport| r.sections().iter().map(|(_, c)| c.len()).sum::<usize>();
+        writeln!(
+            f,
+            "{} accepted, {} new, {} resolved",
+            count(&self.accepted),
+            count(&self.new),
+            count(&self.resolved)
+        )?;
+        if !self.new.is_empty() {
+            write!(f, "=== new\n{}", self.new)?;
+        }
+        if !self.resolved.is_empty() {
+            write!(f, "=== resolved\n{}", self.resolved)?;
+        }
+        Ok(())
+    }
+}
+
+type ChangeGroups = (Vec<LineChange>, Vec<LineChange>, Vec<LineChange>);
+
+fn classify_changes(current: &[LineChange], expected: &[LineChange]) -> ChangeGroups {
+    let mut remaining: HashMap<(ChangeKind, &str), usize> = HashMap::new();
+    for change in expected {
+        *remaining.entry((change.kind, change.value.as_str())).or_default() += 1;
+    }
+    let (mut accepted, mut new) = (vec![], vec![]);
+    for change in current {
+        match remaining.get_mut(&(change.kind, change.value.as_str())) {
+            Some(n) if *n > 0 => {
+                *n -= 1;
+                accepted.push(change.clone());
+            }
+            _ => new.push(change.clone()),
+        }
+    }
+    let mut resolved = vec![];
+    for change in expected {
+        if let Some(n) = remaining.get_mut(&(change.kind, change.value.as_str())) {
+            if *n > 0 {
+                *n -= 1;
+                resolved.push(change.clone());
+            }
+        }
+    }
+    (accepted, new, resolved)
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, changes) in self.sections() {
+            if changes.is_empty() {
+                continue;
+            }
+            writeln!(f, "--- {}", name)?;
+            for change in changes {
+                writeln!(f, "{}", change)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for LineChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (sign, line) = match self.kind {
+            ChangeKind::Delete => ("-", self.old_line),
+            ChangeKind::Insert => ("+", self.new_line),
+        };
+        write!(f, "{}{:<4}|{}", sign, line.unwrap_or_default(), self.value)
+    }
+}
+
+// 按行比较, 只保留删除和新增的行
+pub fn diff_lines(text1: &str, text2: &str) -> Vec<LineChange> {
+    TextDiff::from_lines(text1, text2)
+        .iter_all_changes()
+        .filter_map(|change| {
+            let kind = match change.tag() {
+                ChangeTag::Delete => ChangeKind::Delete,
+                ChangeTag::Insert => ChangeKind::Insert,
+                ChangeTag::Equal => return None,
+            };
+            Some(LineChange {
+                kind,
+                old_line: change.old_index().map(|i| i + 1),
+                new_line: change.new_index().map(|i| i + 1),
+                value: change.value().trim_end_matches(['\n', '\r']).to_string(),
+            })
+        })
+        .collect()
+}
+
+/// 断言profile中两个请求的响应没有差异, 需要在async上下文中使用
+///
+/// # Examples
+///
+/// ```no_run
+/// use xdiff::{assert_no_diff, DiffConfig, LoadConfig};
+///
+/// # async fn run() {
+/// let config = DiffConfig::load_yaml("./xdiff.yaml").await.unwrap();
+/// let profile = config.get_profile("todo").unwrap();
+/// assert_no_diff!(profile);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! assert_no_diff {
+    ($profile:expr) => {
+        $crate::assert_no_diff!($profile, &$crate::ExtraArgs::default())
+    };
+    ($profile:expr, $args:expr) => {{
+        let report = $profile
+            .diff_report($args)
+            .await
+            .expect("failed to diff profile");
+        assert!(report.is_empty(), "responses are different:\n{}", report);
+    }};
+}
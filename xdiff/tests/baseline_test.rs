@@ -0,0 +1,108 @@
+use serde_json::json;
+use xdiff::{diff_lines, DiffProfile, DiffReport, ExtraArgs, RequestProfile, ResponseProfile};
+
+#[test]
+fn classify_should_work() {
+    let expected = DiffReport {
+        body: diff_lines("a\nb\nc\n", "a\nB\nC\n"),
+        ..Default::default()
+    };
+    // b -> B 仍然存在, c -> C 已解决, d -> D 为新的变化
+    let current = DiffReport {
+        body: diff_lines("x\nb\nc\nd\n", "x\nB\nc\nD\n"),
+        ..Default::default()
+    };
+    let baseline = current.classify(&expected);
+    let values = |r: &DiffReport| r.body.iter().map(|c| c.value.clone()).collect::<Vec<_>>();
+    assert_eq!(values(&baseline.accepted), vec!["b", "B"]);
+    assert_eq!(values(&baseline.new), vec!["d", "D"]);
+    assert_eq!(values(&baseline.resolved), vec!["c", "C"]);
+    assert!(!baseline.is_clean());
+    assert!(baseline.to_string().starts_with("2 accepted, 2 new, 2 resolved\n"));
+}
+
+#[tokio::test]
+async fn accept_and_diff_baseline_should_work() {
+    let mut server = mockito::Server::new_async().await;
+    for (path, title) in [("/v1/todo", "a"), ("/v2/todo", "b")] {
+        server
+            .mock("GET", path)
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({ "title": title }).to_string())
+            .create_async()
+            .await;
+    }
+    let expected = std::env::temp_dir().join(format!("xdiff-expected-{}.yaml", std::process::id()));
+    let _ = std::fs::remove_file(&expected);
+    let req1: RequestProfile = format!("{}/v1/todo", server.url()).parse().unwrap();
+    let req2: RequestProfile = format!("{}/v2/todo", server.url()).parse().unwrap();
+    let res = ResponseProfile::new(vec!["date".into()], vec![]);
+    let profile = DiffProfile::new(req1, req2, res).with_expected(&expected);
+
+    let baseline = profile.diff_baseline(&ExtraArgs::default()).await.unwrap();
+    assert_eq!(baseline.new.body.len(), 2);
+
+    let report = profile.accept(&ExtraArgs::default()).await.unwrap();
+    assert_eq!(report.body.len(), 2);
+    let baseline = profile.diff_baseline(&ExtraArgs::default()).await.unwrap();
+    assert!(baseline.is_clean());
+    assert_eq!(baseline.accepted, report);
+
+    std::fs::remove_file(&expected).unwrap();
+}
+
+#[tokio::test]
+async fn xdiff_run_should_fail_on_regressions() {
+    let mut server = mockito::Server::new_async().await;
+    let v2 = server
+        .mock("GET", "/v2/todo")
+        .with_status(200)
+        .with_body(json!({ "title": "b" }).to_string())
+        .create_async()
+        .await;
+    server
+        .mock("GET", "/v1/todo")
+        .with_status(200)
+        .with_body(json!({ "title": "a" }).to_string())
+        .create_async()
+        .await;
+    let dir = std::env::temp_dir().join(format!("xdiff-run-baseline-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("xdiff.yaml");
+    let expected = dir.join("todo.expected.yaml");
+    std::fs::write(
+        &config,
+        format!(
+            "todo:\n  req1:\n    url: {0}/v1/todo\n  req2:\n    url: {0}/v2/todo\n  res:\n    skip_headers: [date]\n  expected: {1}\n",
+            server.url(),
+            expected.display()
+        ),
+    )
+    .unwrap();
+    let xdiff = |action: &str| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_xdiff"))
+            .args([action, "-p", "todo", "-c", config.to_str().unwrap()])
+            .output()
+            .unwrap()
+    };
+
+    // 还没有接受任何变化, 全部为新的变化
+    assert_eq!(xdiff("run").status.code(), Some(1));
+    assert!(xdiff("accept").status.success());
+    let output = xdiff("run");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("2 accepted, 0 new, 0 resolved"));
+
+    // 响应恢复一致后, 已接受的变化消失也视为回归
+    v2.remove_async().await;
+    server
+        .mock("GET", "/v2/todo")
+        .with_status(200)
+        .with_body(json!({ "title": "a" }).to_string())
+        .create_async()
+        .await;
+    assert_eq!(xdiff("run").status.code(), Some(1));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
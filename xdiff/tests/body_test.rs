@@ -0,0 +1,98 @@
+use std::io::Write;
+
+use flate2::{write::GzEncoder, Compression};
+use reqwest::Method;
+use url::Url;
+use xdiff::{BodyOptions, ExtraArgs, Oversize, RequestProfile, ResponseBody, ResponseProfile, ValidateConfig};
+
+async fn get_body(server: &mockito::Server, path: &str, options: BodyOptions) -> ResponseBody {
+    let url = Url::parse(&format!("{}{}", server.url(), path)).unwrap();
+    let req = RequestProfile::new(Method::GET, url, None, Default::default(), None);
+    let res = req.send(&ExtraArgs::default()).await.unwrap();
+    let profile = ResponseProfile { body: options, ..Default::default() };
+    let (_, body) = res.get_parts(&profile).await.unwrap();
+    body
+}
+
+#[tokio::test]
+async fn oversize_body_should_be_hashed_or_truncated() {
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server
+        .mock("GET", "/export")
+        .with_header("content-type", "text/plain")
+        .with_body("hello world")
+        .create_async()
+        .await;
+
+    let body = get_body(&server, "/export", BodyOptions::new(Some(5), Oversize::Hash, None)).await;
+    assert_eq!(
+        body,
+        ResponseBody::Text(
+            "<body too large: 11 bytes, sha256: b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9>".into()
+        )
+    );
+
+    let body = get_body(&server, "/export", BodyOptions::new(Some(5), Oversize::Truncate, None)).await;
+    let ResponseBody::Text(text) = body else { panic!("expect text body") };
+    assert!(text.starts_with("hello\n<truncated: 5 of 11 bytes"));
+
+    let body = get_body(&server, "/export", BodyOptions::new(Some(11), Oversize::Hash, None)).await;
+    assert_eq!(body, ResponseBody::Text("hello world".into()));
+}
+
+#[tokio::test]
+async fn binary_body_should_diff_by_digest() {
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server
+        .mock("GET", "/logo.png")
+        .with_header("content-type", "image/png")
+        .with_body([0x89, b'P', b'N', b'G'])
+        .create_async()
+        .await;
+    let _mock = server
+        .mock("GET", "/raw")
+        .with_body([b'a', 0, b'b'])
+        .create_async()
+        .await;
+
+    let ResponseBody::Text(text) = get_body(&server, "/logo.png", BodyOptions::default()).await else {
+        panic!("expect text body")
+    };
+    assert!(text.starts_with("<binary body: 4 bytes, sha256: "));
+    let ResponseBody::Text(text) = get_body(&server, "/raw", BodyOptions::default()).await else {
+        panic!("expect text body")
+    };
+    assert!(text.starts_with("<binary body: 3 bytes, sha256: "));
+}
+
+#[tokio::test]
+async fn gzip_body_should_be_decompressed() {
+    let mut encoder = GzEncoder::new(vec![], Compression::default());
+    encoder.write_all(br#"{"id": 1, "name": "todo"}"#).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server
+        .mock("GET", "/todo")
+        .with_header("content-type", "application/json")
+        .with_header("content-encoding", "gzip")
+        .with_body(compressed)
+        .create_async()
+        .await;
+
+    let body = get_body(&server, "/todo", BodyOptions::default()).await;
+    assert_eq!(body, ResponseBody::Json(serde_json::json!({"id": 1, "name": "todo"})));
+
+    let body = get_body(&server, "/todo", BodyOptions::new(None, Oversize::Hash, Some(false))).await;
+    let ResponseBody::Text(text) = body else { panic!("expect text body") };
+    assert!(text.starts_with("<binary body: "));
+}
+
+#[test]
+fn zero_max_size_validate_should_fail() {
+    let profile = ResponseProfile {
+        body: BodyOptions::new(Some(0), Oversize::Hash, None),
+        ..Default::default()
+    };
+    assert!(profile.validate().is_err());
+}
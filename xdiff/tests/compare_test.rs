@@ -0,0 +1,41 @@
+use serde_json::json;
+use xdiff::{ArrayRule, NumericTolerance, ResponseBody, ResponseProfile, ValidateConfig};
+
+#[test]
+fn numeric_tolerance_should_work() {
+    let profile = ResponseProfile {
+        tolerances: vec![
+            NumericTolerance::new("price", Some(0.01), None),
+            NumericTolerance::new("items.*.rate", None, Some(0.001)),
+        ],
+        ..Default::default()
+    };
+    let mut v1 = json!({"price": 1.001, "items": [{"rate": 1000.0}, {"rate": 10.0}]});
+    let mut v2 = json!({"price": 1.005, "items": [{"rate": 1000.5}, {"rate": 11.0}]});
+    profile.normalize(&mut v1, &mut v2);
+    assert_eq!(v2, json!({"price": 1.001, "items": [{"rate": 1000.0}, {"rate": 11.0}]}));
+}
+
+#[test]
+fn array_rules_should_work() {
+    let profile = ResponseProfile {
+        arrays: vec![
+            ArrayRule::new("tags", None),
+            ArrayRule::new("data.users", Some("id".into())),
+        ],
+        ..Default::default()
+    };
+    let body1 = ResponseBody::Json(json!({"tags": ["b", "a"], "data": {"users": [{"id": 2}, {"id": 10}]}}));
+    let body2 = ResponseBody::Json(json!({"tags": ["a", "b"], "data": {"users": [{"id": 10}, {"id": 2}]}}));
+    let (text1, text2) = profile.compare_bodies(body1, body2).unwrap();
+    assert_eq!(text1, text2);
+}
+
+#[test]
+fn tolerance_without_bounds_validate_should_fail() {
+    let profile = ResponseProfile {
+        tolerances: vec![NumericTolerance::new("price", None, None)],
+        ..Default::default()
+    };
+    assert!(profile.validate().is_err());
+}
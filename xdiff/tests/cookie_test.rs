@@ -0,0 +1,71 @@
+use reqwest::Method;
+use url::Url;
+use xdiff::{CookieJar, DiffProfile, ExtraArgs, RequestProfile, ResponseProfile};
+
+fn get_request(server: &mockito::Server, path: &str) -> RequestProfile {
+    let url = Url::parse(&format!("{}{}", server.url(), path)).unwrap();
+    RequestProfile::new(Method::GET, url, None, Default::default(), None)
+}
+
+#[tokio::test]
+async fn cookie_jar_should_keep_session_across_profiles() {
+    let mut server = mockito::Server::new_async().await;
+    let _login = server
+        .mock("GET", "/login")
+        .with_header("set-cookie", "session=abc; Path=/")
+        .with_body("ok")
+        .create_async()
+        .await;
+    let _me = server
+        .mock("GET", "/me")
+        .match_header("cookie", "session=abc")
+        .with_body("alice")
+        .create_async()
+        .await;
+
+    let args = ExtraArgs::default();
+    let jar = CookieJar::new();
+    get_request(&server, "/login").send_with_jar(&args, Some(&jar)).await.unwrap();
+    let res = get_request(&server, "/me").send_with_jar(&args, Some(&jar)).await.unwrap();
+    assert_eq!(res.into_inner().status(), 200);
+
+    // 不使用jar时没有cookie
+    let res = get_request(&server, "/me").send(&args).await.unwrap();
+    assert_eq!(res.into_inner().status(), 501);
+
+    // diff的两个请求共用jar
+    let profile = DiffProfile::new(get_request(&server, "/me"), get_request(&server, "/me"), ResponseProfile::default())
+        .with_cookie_jar(jar);
+    let report = profile.diff_report(&args).await.unwrap();
+    assert!(report.is_empty());
+}
+
+#[tokio::test]
+async fn cookie_jar_should_persist_to_file() {
+    let mut server = mockito::Server::new_async().await;
+    let _login = server
+        .mock("GET", "/login")
+        .with_header("set-cookie", "session=abc; Path=/")
+        .create_async()
+        .await;
+
+    let path = std::env::temp_dir().join(format!("xdiff-cookies-{}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let jar = CookieJar::load(&path).await.unwrap();
+    assert!(jar.is_empty());
+    get_request(&server, "/login")
+        .send_with_jar(&ExtraArgs::default(), Some(&jar))
+        .await
+        .unwrap();
+    jar.save().await.unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    let jar = CookieJar::load(&path).await.unwrap();
+    let url = Url::parse(&format!("{}/me", server.url())).unwrap();
+    assert_eq!(jar.cookies(&url), vec![("session".to_string(), "abc".to_string())]);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,163 @@
+use std::convert::Infallible;
+use std::task::{Context, Poll};
+
+use prost_reflect::prost::Message;
+use prost_reflect::prost_types::{
+    field_descriptor_proto::{Label, Type},
+    DescriptorProto, FieldDescriptorProto, FileDescriptorProto, FileDescriptorSet, MethodDescriptorProto,
+    ServiceDescriptorProto,
+};
+use reqwest::header::HeaderMap;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::codegen::{http, BoxFuture, Service};
+use tonic::server::{Grpc, NamedService, UnaryService};
+use tonic::transport::Server;
+use url::Url;
+use xdiff::{ExtraArgs, GrpcRequest, RequestProfile};
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct HelloRequest {
+    #[prost(string, tag = "1")]
+    name: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct HelloReply {
+    #[prost(string, tag = "1")]
+    message: String,
+}
+
+// 与protoc生成的服务代码相同, 只实现test.Greeter/SayHello
+#[derive(Clone)]
+struct Greeter;
+
+impl NamedService for Greeter {
+    const NAME: &'static str = "test.Greeter";
+}
+
+impl<B> Service<http::Request<B>> for Greeter
+where
+    B: tonic::codegen::Body + Send + 'static,
+    B::Error: Into<tonic::codegen::StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        Box::pin(async move {
+            let mut grpc = Grpc::new(tonic_prost::ProstCodec::<HelloReply, HelloRequest>::default());
+            Ok(grpc.unary(SayHello, req).await)
+        })
+    }
+}
+
+struct SayHello;
+
+impl UnaryService<HelloRequest> for SayHello {
+    type Response = HelloReply;
+    type Future = BoxFuture<tonic::Response<HelloReply>, tonic::Status>;
+
+    fn call(&mut self, req: tonic::Request<HelloRequest>) -> Self::Future {
+        let token = req.metadata().get("x-token").and_then(|v| v.to_str().ok()).unwrap_or_default().to_string();
+        let name = req.into_inner().name;
+        Box::pin(async move {
+            Ok(tonic::Response::new(HelloReply {
+                message: format!("hello {}{}", name, token),
+            }))
+        })
+    }
+}
+
+#[tokio::test]
+async fn grpc_send_with_reflection_should_work() {
+    let url = start_server().await;
+    let grpc = GrpcRequest::new("test.Greeter/SayHello", None, Some(json!({"name": "xdiff"})));
+    let mut headers = HeaderMap::new();
+    headers.insert("x-token", "!".parse().unwrap());
+    let profile = RequestProfile::new_grpc(url, headers, grpc);
+    let res = profile.send(&ExtraArgs::default()).await.unwrap().into_inner();
+    assert_eq!(res.status(), 200);
+    let body: Value = serde_json::from_slice(&res.bytes().await.unwrap()).unwrap();
+    assert_eq!(body, json!({"message": "hello xdiff!"}));
+}
+
+#[tokio::test]
+async fn grpc_send_with_descriptor_set_should_work() {
+    let url = start_server().await;
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), descriptor_set()).unwrap();
+    let grpc = GrpcRequest::new("test.Greeter/SayHello", Some(file.path().to_path_buf()), None);
+    let profile = RequestProfile::new_grpc(url, HeaderMap::new(), grpc);
+    let args = ExtraArgs::new_with_body(vec![("name".into(), r#""proto""#.into())]);
+    let res = profile.send(&args).await.unwrap().into_inner();
+    let body: Value = serde_json::from_slice(&res.bytes().await.unwrap()).unwrap();
+    assert_eq!(body, json!({"message": "hello proto"}));
+}
+
+#[tokio::test]
+async fn grpc_send_with_unknown_method_should_fail() {
+    let url = start_server().await;
+    let grpc = GrpcRequest::new("test.Greeter/SayBye", None, None);
+    let profile = RequestProfile::new_grpc(url, HeaderMap::new(), grpc);
+    let err = profile.send(&ExtraArgs::default()).await.unwrap_err();
+    assert_eq!(err.to_string(), "grpc method SayBye not found in test.Greeter");
+}
+
+// 启动带服务反射的grpc服务, 返回地址
+async fn start_server() -> Url {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let reflection = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(&descriptor_set())
+        .build_v1()
+        .unwrap();
+    tokio::spawn(
+        Server::builder()
+            .add_service(Greeter)
+            .add_service(reflection)
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+    Url::parse(&format!("http://{}", addr)).unwrap()
+}
+
+// greeter.proto编译后的descriptor set
+fn descriptor_set() -> Vec<u8> {
+    let string_field = |name: &str| FieldDescriptorProto {
+        name: Some(name.into()),
+        number: Some(1),
+        label: Some(Label::Optional as i32),
+        r#type: Some(Type::String as i32),
+        json_name: Some(name.into()),
+        ..Default::default()
+    };
+    let message = |name: &str, field: &str| DescriptorProto {
+        name: Some(name.into()),
+        field: vec![string_field(field)],
+        ..Default::default()
+    };
+    let file = FileDescriptorProto {
+        name: Some("greeter.proto".into()),
+        package: Some("test".into()),
+        message_type: vec![message("HelloRequest", "name"), message("HelloReply", "message")],
+        service: vec![ServiceDescriptorProto {
+            name: Some("Greeter".into()),
+            method: vec![MethodDescriptorProto {
+                name: Some("SayHello".into()),
+                input_type: Some(".test.HelloRequest".into()),
+                output_type: Some(".test.HelloReply".into()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        syntax: Some("proto3".into()),
+        ..Default::default()
+    };
+    FileDescriptorSet { file: vec![file] }.encode_to_vec()
+}
@@ -0,0 +1,115 @@
+use serde_json::json;
+use xdiff::{ExtraArgs, History, HistoryEntry, RequestProfile};
+
+
+#[tokio::test]
+async fn history_append_and_search_should_work() {
+    let mut server = mockito::Server::new_async().await;
+    for (path, title) in [("/todo/1", "hello"), ("/todo/2", "world")] {
+        server
+            .mock("GET", path)
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({ "title": title }).to_string())
+            .create_async()
+            .await;
+    }
+    let path = std::env::temp_dir().join(format!("xdiff-history-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let history = History::new(&path);
+
+    for p in ["/todo/1", "/todo/2"] {
+        let req: RequestProfile = format!("{}{}", server.url(), p).parse().unwrap();
+        let entry = HistoryEntry::send("todo", &req, &ExtraArgs::default()).await.unwrap();
+        history.append(entry).await.unwrap();
+    }
+
+    let entries = history.search(None, 10).await.unwrap();
+    assert_eq!(entries.iter().map(|e| e.id).collect::<Vec<_>>(), vec![2, 1]);
+    let entries = history.search(Some("world"), 10).await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].url, format!("{}/todo/2", server.url()));
+
+    let entry = history.get(1).await.unwrap();
+    assert_eq!(entry.status, 200);
+    assert!(entry.get_text().unwrap().starts_with("HTTP/1.1 200 OK\n"));
+    assert!(entry.get_text().unwrap().ends_with("{\n  \"title\": \"hello\"\n}"));
+    assert!(history.get(3).await.is_err());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn history_should_redact_secrets_and_restrict_permissions() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", "/me")
+        .match_header("authorization", "Bearer secret")
+        .with_status(200)
+        .with_header("set-cookie", "session=abc")
+        .with_body("{}")
+        .create_async()
+        .await;
+    let path = std::env::temp_dir().join(format!("xdiff-history-secrets-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let history = History::new(&path);
+
+    let mut req: RequestProfile = format!("{}/me", server.url()).parse().unwrap();
+    req.headers.insert("cookie", "session=abc".parse().unwrap());
+    let args = ExtraArgs::new_with_headers(vec![("authorization".into(), "Bearer secret".into())]);
+    let entry = HistoryEntry::send("me", &req, &args).await.unwrap();
+    history.append(entry).await.unwrap();
+
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(!content.contains("secret"));
+    assert!(!content.contains("session=abc"));
+    let entry = history.last().await.unwrap().unwrap();
+    assert!(entry.args.headers.is_empty());
+    assert!(entry.request.headers.get("cookie").is_none());
+    assert_eq!(entry.headers.iter().find(|(k, _)| k == "set-cookie").unwrap().1, "<redacted>");
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    // keep_secrets时原样保存, id接着最后一条记录
+    let history = History::new(&path).keep_secrets(true);
+    let entry = HistoryEntry::send("me", &req, &args).await.unwrap();
+    assert_eq!(history.append(entry).await.unwrap().id, 2);
+    assert!(std::fs::read_to_string(&path).unwrap().contains("Bearer secret"));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn history_last_should_read_long_entries_from_the_end() {
+    let path = std::env::temp_dir().join(format!("xdiff-history-last-{}.jsonl", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let history = History::new(&path);
+    assert!(history.last().await.unwrap().is_none());
+
+    let req: RequestProfile = "http://localhost/todo".parse().unwrap();
+    let mut entry = HistoryEntry {
+        id: 0,
+        time: 0,
+        profile: "todo".into(),
+        url: "http://localhost/todo".into(),
+        request: req,
+        args: ExtraArgs::default(),
+        status: 200,
+        version: "HTTP/1.1".into(),
+        headers: vec![],
+        body: "x".repeat(10000),
+        elapsed: 0,
+    };
+    for _ in 0..3 {
+        entry = history.append(entry).await.unwrap();
+    }
+    let last = history.last().await.unwrap().unwrap();
+    assert_eq!(last.id, 3);
+    assert_eq!(last.body.len(), 10000);
+
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,218 @@
+use futures_util::{SinkExt, StreamExt};
+use serde_json::json;
+use tokio_tungstenite::tungstenite::Message;
+use xdiff::{DiffConfig, ExtraArgs, LoadConfig};
+
+#[tokio::test]
+async fn diff_matrix_should_work() {
+    let mut server = mockito::Server::new_async().await;
+    for (version, id, title) in [("v1", "1", "a"), ("v2", "1", "a"), ("v1", "2", "b"), ("v2", "2", "c")] {
+        server
+            .mock("GET", format!("/{}/todo/{}", version, id).as_str())
+            .match_query(mockito::Matcher::UrlEncoded("id".into(), id.into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({ "title": title }).to_string())
+            .create_async()
+            .await;
+    }
+    let config = DiffConfig::from_yaml(&format!(
+        r#"
+todo:
+  matrix:
+    rows:
+      - id: 1
+      - id: 2
+  req1:
+    url: {url}/v1/todo/{{{{id}}}}
+    params:
+      id: "{{{{id}}}}"
+  req2:
+    url: {url}/v2/todo/{{{{id}}}}
+    params:
+      id: "{{{{id}}}}"
+  res:
+    skip_headers:
+      - date
+"#,
+        url = server.url()
+    ))
+    .unwrap();
+    let profile = config.get_profile("todo").unwrap();
+    let report = profile.diff_matrix(&ExtraArgs::default()).await.unwrap();
+
+    assert_eq!(report.results.len(), 2);
+    let diverged = report.diverged();
+    assert_eq!(diverged.len(), 1);
+    assert_eq!(diverged[0].index, 2);
+    assert_eq!(diverged[0].report.body.len(), 2);
+    assert_eq!(report.to_string(), "2 rows, 1 diverged\n  row 2: {\"id\":2}\n");
+}
+
+#[tokio::test]
+async fn matrix_file_should_load() {
+    let dir = std::env::temp_dir();
+    let csv = dir.join(format!("xdiff-matrix-{}.csv", std::process::id()));
+    let jsonl = dir.join(format!("xdiff-matrix-{}.jsonl", std::process::id()));
+    std::fs::write(&csv, "id,title\n1,a\n2,\"{\"\"x\"\": 1}\"\n").unwrap();
+    std::fs::write(&jsonl, "{\"id\": 3}\n\n{\"id\": \"4\"}\n").unwrap();
+
+    for (file, expected) in [
+        (&csv, vec![json!({"id": 1, "title": "a"}), json!({"id": 2, "title": {"x": 1}})]),
+        (&jsonl, vec![json!({"id": 3}), json!({"id": "4"})]),
+    ] {
+        let matrix = xdiff::Matrix::new(vec![], Some(file.clone()));
+        let rows = matrix.load_rows().await.unwrap();
+        let rows: Vec<_> = rows.into_iter().map(serde_json::Value::Object).collect();
+        assert_eq!(rows, expected);
+        std::fs::remove_file(file).unwrap();
+    }
+}
+
+#[tokio::test]
+async fn diff_matrix_should_record_failed_rows_and_continue() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("GET", "/todo")
+        .with_status(200)
+        .with_body(json!({ "title": "a" }).to_string())
+        .create_async()
+        .await;
+    let config = DiffConfig::from_yaml(&format!(
+        r#"
+todo:
+  matrix:
+    rows:
+      - token: a
+      - token: "a\nb"
+      - token: c
+  req1:
+    url: {url}/todo
+    headers:
+      x-token: "{{{{token}}}}"
+  req2:
+    url: {url}/todo
+  res:
+    skip_headers:
+      - date
+"#,
+        url = server.url()
+    ))
+    .unwrap();
+    let profile = config.get_profile("todo").unwrap();
+    let report = profile.diff_matrix(&ExtraArgs::default()).await.unwrap();
+
+    assert_eq!(report.results.len(), 3);
+    assert!(report.diverged().is_empty());
+    let failed = report.failed();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].index, 2);
+    assert!(!report.is_empty());
+    assert!(report.to_string().starts_with("3 rows, 0 diverged, 1 failed\n  row 2: {\"token\":\"a\\nb\"} failed: "));
+}
+
+#[tokio::test]
+async fn diff_matrix_should_render_websocket_messages() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                while let Some(Ok(msg)) = ws.next().await {
+                    if let Message::Text(text) = msg {
+                        ws.send(Message::text(format!("echo {}", text))).await.unwrap();
+                    }
+                }
+            });
+        }
+    });
+    let config = DiffConfig::from_yaml(&format!(
+        r#"
+chat:
+  matrix:
+    rows:
+      - name: a
+      - name: b
+  req1:
+    url: ws://{addr}/ws
+    websocket:
+      messages: ["{{{{name}}}}"]
+      count: 1
+  req2:
+    url: ws://{addr}/ws
+    websocket:
+      messages: ["a"]
+      count: 1
+  res:
+    skip_headers:
+      - date
+      - sec-websocket-accept
+"#
+    ))
+    .unwrap();
+    let profile = config.get_profile("chat").unwrap();
+    let report = profile.diff_matrix(&ExtraArgs::default()).await.unwrap();
+
+    assert!(report.failed().is_empty());
+    let diverged = report.diverged();
+    assert_eq!(diverged.len(), 1);
+    assert_eq!(diverged[0].index, 2);
+}
+
+#[tokio::test]
+async fn xdiff_run_should_fail_on_diverged_or_failed_rows() {
+    let mut server = mockito::Server::new_async().await;
+    for (version, title) in [("v1", "a"), ("v2", "b")] {
+        server
+            .mock("GET", format!("/{}/todo", version).as_str())
+            .with_status(200)
+            .with_body(json!({ "title": title }).to_string())
+            .create_async()
+            .await;
+    }
+    let dir = std::env::temp_dir().join(format!("xdiff-run-matrix-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("xdiff.yaml");
+    let xdiff = |profile: &str| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_xdiff"))
+            .args(["run", "-p", profile, "-c", config.to_str().unwrap()])
+            .output()
+            .unwrap()
+    };
+    let profile = |name: &str, token: &str, version: &str| {
+        format!(
+            r#"
+{name}:
+  matrix:
+    rows:
+      - token: a
+      - token: {token}
+  req1:
+    url: {url}/v1/todo
+    headers:
+      x-token: "{{{{token}}}}"
+  req2:
+    url: {url}/{version}/todo
+  res:
+    skip_headers: [date]
+"#,
+            url = server.url()
+        )
+    };
+    std::fs::write(
+        &config,
+        [profile("same", "b", "v1"), profile("diverged", "b", "v2"), profile("failed", "\"a\\nb\"", "v1")].concat(),
+    )
+    .unwrap();
+
+    let output = xdiff("same");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("2 rows, 0 diverged"));
+    assert_eq!(xdiff("diverged").status.code(), Some(1));
+    let output = xdiff("failed");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("2 rows, 0 diverged, 1 failed"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,41 @@
+use serde_json::json;
+use xdiff::{get_body_text, LoadConfig, MockConfig};
+
+#[tokio::test]
+async fn mock_server_should_work() {
+    let config = MockConfig::load_yaml("fixtures/mock.yml").await.unwrap();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(xdiff::serve(config, listener));
+
+    let client = reqwest::Client::new();
+    let res = client.get(format!("{}/todos/1", url)).send().await.unwrap();
+    assert_eq!(res.status(), 200);
+    assert_eq!(res.headers()["x-powered-by"], "xreq");
+    let body: serde_json::Value = serde_json::from_str(&res.text().await.unwrap()).unwrap();
+    assert_eq!(body, json!({"id": 1, "title": "todo 1", "completed": false}));
+
+    let res = client
+        .post(format!("{}/todos", url))
+        .body(json!({"title": "hello"}).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), 201);
+    assert_eq!(get_body_text(res, &["id".into()]).await.unwrap(), "{\n  \"title\": \"hello\"\n}");
+
+    let res = client.get(format!("{}/slow/a/b", url)).send().await.unwrap();
+    assert_eq!(res.text().await.unwrap(), "slow response");
+
+    let res = client.delete(format!("{}/todos/1", url)).send().await.unwrap();
+    assert_eq!(res.status(), 404);
+}
+
+#[test]
+fn mock_route_match_path_should_work() {
+    let config = MockConfig::from_yaml("routes:\n  - path: /users/{id}/todos/{todo}\n").unwrap();
+    let (_, vars) = config.find_route(&reqwest::Method::GET, "/users/tom/todos/2").unwrap();
+    assert_eq!(serde_json::Value::Object(vars), json!({"id": "tom", "todo": 2}));
+    assert!(config.find_route(&reqwest::Method::GET, "/users/tom").is_none());
+    assert!(config.find_route(&reqwest::Method::GET, "/users/tom/todos/2/x").is_none());
+}
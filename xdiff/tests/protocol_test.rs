@@ -0,0 +1,97 @@
+use reqwest::header::HeaderMap;
+use serde_json::json;
+use url::Url;
+use xdiff::{ExtraArgs, GraphqlRequest, RequestConfig, RequestProfile, ResponseProfile, LoadConfig};
+
+#[tokio::test]
+async fn graphql_send_should_work() {
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server
+        .mock("POST", "/graphql")
+        .match_body(mockito::Matcher::Json(json!({
+            "query": "query($id: ID!) { todo(id: $id) { title } }",
+            "variables": {"id": 1},
+        })))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"data": {"todo": {"title": "todo"}}}"#)
+        .create_async()
+        .await;
+    let profile = get_graphql_profile(&server.url(), GraphqlRequest::new(
+        "query($id: ID!) { todo(id: $id) { title } }",
+        None,
+    ));
+    let args = ExtraArgs::new_with_body(vec![("id".into(), "1".into())]);
+    let res = profile.send(&args).await.unwrap();
+    let text = res
+        .get_text(&ResponseProfile::new(vec!["content-length".into(), "date".into(), "connection".into()], vec![]))
+        .await
+        .unwrap();
+    assert_eq!(
+        text,
+        "HTTP/1.1 200 OK\ncontent-type: \"application/json\"\n\n{\n  \"data\": {\n    \"todo\": {\n      \"title\": \"todo\"\n    }\n  }\n}"
+    );
+}
+
+#[tokio::test]
+async fn graphql_send_with_errors_should_fail() {
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server
+        .mock("POST", "/graphql")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"data": null, "errors": [{"message": "todo not found"}]}"#)
+        .create_async()
+        .await;
+    let mut graphql = GraphqlRequest::new("{ todo { title } }", None);
+    let profile = get_graphql_profile(&server.url(), graphql.clone());
+    let res = profile.send(&ExtraArgs::default()).await;
+    assert_eq!(res.unwrap_err().to_string(), "graphql errors: todo not found");
+
+    graphql.allow_errors = true;
+    let profile = get_graphql_profile(&server.url(), graphql);
+    assert!(profile.send(&ExtraArgs::default()).await.is_ok());
+}
+
+#[test]
+fn grpc_and_graphql_profile_should_load() {
+    let config = RequestConfig::from_yaml(
+        r#"
+todo-graphql:
+  url: http://localhost:4000/graphql
+  graphql:
+    query: "{ todo(id: 1) { title } }"
+todo-grpc:
+  url: http://localhost:50051
+  grpc:
+    method: todo.TodoService/GetTodo
+    message:
+      id: 1
+"#,
+    )
+    .unwrap();
+    let profile = config.get_profile("todo-grpc").unwrap();
+    assert_eq!(
+        profile.get_url(&ExtraArgs::default()).unwrap(),
+        "http://localhost:50051/todo.TodoService/GetTodo"
+    );
+    assert!(config.get_profile("todo-graphql").unwrap().graphql.is_some());
+}
+
+#[test]
+fn grpc_profile_with_bad_method_should_fail() {
+    let res = RequestConfig::from_yaml(
+        r#"
+todo-grpc:
+  url: http://localhost:50051
+  grpc:
+    method: GetTodo
+"#,
+    );
+    assert!(res.is_err());
+}
+
+fn get_graphql_profile(url: &str, graphql: GraphqlRequest) -> RequestProfile {
+    let url = Url::parse(&format!("{}/graphql", url)).unwrap();
+    RequestProfile::new_graphql(url, HeaderMap::new(), graphql)
+}
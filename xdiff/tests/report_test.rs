@@ -0,0 +1,67 @@
+use serde_json::json;
+use xdiff::{assert_no_diff, diff_lines, ChangeKind, DiffProfile, DiffReport, ExtraArgs, LineChange, RequestProfile, ResponseProfile};
+
+#[test]
+fn diff_report_should_work() {
+    let report = DiffReport::new(
+        "HTTP/1.1 200 OK\ncontent-type: \"application/json\"\n\n",
+        "{\n  \"title\": \"a\"\n}",
+        "HTTP/1.1 200 OK\ncontent-type: \"application/json\"\n\n",
+        "{\n  \"title\": \"b\"\n}",
+    );
+    assert!(report.status.is_empty());
+    assert!(report.headers.is_empty());
+    assert_eq!(
+        report.body,
+        vec![
+            LineChange {
+                kind: ChangeKind::Delete,
+                old_line: Some(2),
+                new_line: None,
+                value: "  \"title\": \"a\"".into(),
+            },
+            LineChange {
+                kind: ChangeKind::Insert,
+                old_line: None,
+                new_line: Some(2),
+                value: "  \"title\": \"b\"".into(),
+            },
+        ]
+    );
+    assert_eq!(report.to_string(), "--- body\n-2   |  \"title\": \"a\"\n+2   |  \"title\": \"b\"\n");
+}
+
+#[test]
+fn diff_lines_with_same_text_should_be_empty() {
+    assert!(diff_lines("hello\nworld", "hello\nworld").is_empty());
+}
+
+#[tokio::test]
+async fn diff_profile_report_should_work() {
+    let mut server = mockito::Server::new_async().await;
+    for (path, title) in [("/todo/1", "a"), ("/todo/2", "a"), ("/todo/3", "b")] {
+        server
+            .mock("GET", path)
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(json!({ "id": path, "title": title }).to_string())
+            .create_async()
+            .await;
+    }
+    let res = ResponseProfile::new(vec!["date".into()], vec!["id".into()]);
+    let profile = get_profile(&server.url(), "/todo/1", "/todo/2", res);
+    assert_no_diff!(profile);
+    assert_no_diff!(profile, &ExtraArgs::default());
+
+    let res = ResponseProfile::new(vec!["date".into()], vec!["id".into()]);
+    let profile = get_profile(&server.url(), "/todo/1", "/todo/3", res);
+    let report = profile.diff_report(&ExtraArgs::default()).await.unwrap();
+    assert!(!report.is_empty());
+    assert_eq!(report.body.len(), 2);
+}
+
+fn get_profile(url: &str, path1: &str, path2: &str, res: ResponseProfile) -> DiffProfile {
+    let req1: RequestProfile = format!("{}{}", url, path1).parse().unwrap();
+    let req2: RequestProfile = format!("{}{}", url, path2).parse().unwrap();
+    DiffProfile::new(req1, req2, res)
+}
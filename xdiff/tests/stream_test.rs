@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use futures_util::SinkExt;
+use reqwest::header::HeaderMap;
+use serde_json::json;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio_stream::StreamExt;
+use tokio_tungstenite::tungstenite::Message;
+use url::Url;
+use xdiff::{ExtraArgs, RequestProfile, ResponseBody, ResponseProfile, SseRequest, StreamLimit, WebSocketRequest};
+
+#[tokio::test]
+async fn sse_send_should_collect_messages() {
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server
+        .mock("GET", "/events")
+        .match_header("accept", "text/event-stream")
+        .with_status(200)
+        .with_header("content-type", "text/event-stream")
+        .with_body(": comment\ndata: {\"id\": 1, \"price\": 10}\n\nevent: done\ndata: bye\n\ndata: ignored\n\n")
+        .create_async()
+        .await;
+    let url = Url::parse(&format!("{}/events", server.url())).unwrap();
+    let profile = RequestProfile::new_sse(url, HeaderMap::new(), SseRequest::new(StreamLimit::new(Some(2), Some(5))));
+    let res = profile.send(&ExtraArgs::default()).await.unwrap();
+    let (_, body) = res
+        .get_parts(&ResponseProfile::new(vec![], vec!["id".into()]))
+        .await
+        .unwrap();
+    assert_eq!(
+        body,
+        ResponseBody::Json(json!([{"price": 10}, {"event": "done", "data": "bye"}]))
+    );
+}
+
+#[tokio::test]
+async fn sse_send_should_keep_multi_byte_chars_split_across_chunks() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let head = "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\nconnection: close\r\n\r\n";
+        let body = "data: {\"price\": \"价格\"}\n\n".as_bytes();
+        // 从"价"的第二个字节处拆开, 分两次发送
+        let split = body.iter().position(|b| *b >= 0x80).unwrap() + 1;
+        stream.write_all(head.as_bytes()).await.unwrap();
+        stream.write_all(&body[..split]).await.unwrap();
+        stream.flush().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        stream.write_all(&body[split..]).await.unwrap();
+        stream.shutdown().await.unwrap();
+    });
+
+    let url = Url::parse(&format!("http://{}/events", addr)).unwrap();
+    let profile = RequestProfile::new_sse(url, HeaderMap::new(), SseRequest::new(StreamLimit::new(Some(1), Some(5))));
+    let res = profile.send(&ExtraArgs::default()).await.unwrap();
+    let (_, body) = res.get_parts(&ResponseProfile::default()).await.unwrap();
+    assert_eq!(body, ResponseBody::Json(json!([{"price": "价格"}])));
+}
+
+#[tokio::test]
+async fn websocket_send_should_collect_messages() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        // 回显收到的消息后再推送一条
+        while let Some(Ok(msg)) = ws.next().await {
+            if let Message::Text(text) = msg {
+                ws.send(Message::text(format!("echo {}", text))).await.unwrap();
+                ws.send(Message::text(r#"{"tick": 1}"#)).await.unwrap();
+            }
+        }
+    });
+
+    let url = Url::parse(&format!("ws://{}/ws", addr)).unwrap();
+    let websocket = WebSocketRequest::new(vec![json!("hello")], StreamLimit::new(Some(2), Some(5)));
+    let profile = RequestProfile::new_websocket(url, HeaderMap::new(), websocket);
+    let res = profile.send(&ExtraArgs::default()).await.unwrap();
+    let (head, body) = res.get_parts(&ResponseProfile::default()).await.unwrap();
+    assert!(head.starts_with("HTTP/1.1 101 Switching Protocols\n"));
+    assert_eq!(body, ResponseBody::Json(json!(["echo hello", {"tick": 1}])));
+}